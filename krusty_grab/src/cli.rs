@@ -0,0 +1,239 @@
+use std::{path::PathBuf, str::FromStr, thread, time::Duration};
+
+use anyhow::{anyhow, bail, Error};
+use egui::{pos2, vec2, Rect};
+
+use crate::{
+    krustygrab::{Format, KrustyGrabConfig},
+    screenshot::{
+        capture_backend::{default_backend, ScreenSelection},
+        naming::{render_template, unique_path, CollisionPolicy, NamingContext},
+        screen_capture::{save_image, screens_number, take_screen},
+    },
+};
+
+pub const USAGE: &str = "Usage: krusty_grab capture [OPTIONS]
+
+Take a screenshot without opening the application window.
+Options not given on the command line are taken from the saved configuration.
+
+Options:
+    --screen <N|all>          Screen to capture, starting from 1, or all of them (default: 1)
+    --region <X,Y,W,H>        Capture only the given area of the screen
+    --delay <SECONDS>         Wait before taking the screenshot
+    --format <FORMAT>         Output format: png, jpg, gif, webp, bmp, tiff or qoi
+    --out <PATH>              Output file (default: file in the save folder named after the template)
+    -h, --help                Print this message";
+
+///Options of the headless capture, as parsed from the command line
+#[derive(Debug, Default, PartialEq)]
+pub struct CaptureArgs {
+    pub screen: Option<ScreenSelection>,
+    pub region: Option<Rect>,
+    pub delay: Option<u64>,
+    pub format: Option<Format>,
+    pub out: Option<PathBuf>,
+    pub help: bool,
+}
+
+impl CaptureArgs {
+    ///Parse the arguments following the `capture` subcommand
+    pub fn parse(args: &[String]) -> Result<Self, Error> {
+        let mut parsed = CaptureArgs::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow!("Missing value for {arg}"));
+
+            match arg.as_str() {
+                "--screen" => {
+                    let screen = value()?;
+                    if screen == "all" {
+                        parsed.screen = Some(ScreenSelection::All);
+                    } else {
+                        let screen: usize = screen.parse().map_err(|_| anyhow!("Invalid screen number"))?;
+                        if screen == 0 {
+                            bail!("Screens are numbered starting from 1");
+                        }
+                        parsed.screen = Some(ScreenSelection::Single(screen - 1));
+                    }
+                },
+                "--region" => parsed.region = Some(parse_region(value()?)?),
+                "--delay" => parsed.delay = Some(value()?.parse().map_err(|_| anyhow!("Invalid delay"))?),
+                "--format" => parsed.format = Some(Format::from_str(value()?)?),
+                "--out" => parsed.out = Some(PathBuf::from(value()?)),
+                "-h" | "--help" => parsed.help = true,
+                _ => bail!("Unknown option {arg}\n\n{USAGE}"),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+///Parse a region given as `x,y,width,height`
+fn parse_region(region: &str) -> Result<Rect, Error> {
+    let values = region
+        .split(',')
+        .map(|v| v.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|_| anyhow!("Invalid region {region:?}, expected X,Y,W,H"))?;
+
+    match values[..] {
+        [x, y, w, h] if x >= 0.0 && y >= 0.0 && w > 0.0 && h > 0.0 => Ok(Rect::from_min_size(pos2(x, y), vec2(w, h))),
+        _ => bail!("Invalid region {region:?}, expected X,Y,W,H with positive size"),
+    }
+}
+
+///Run the `capture` subcommand: take the screenshot and save it, without rendering any window
+pub fn run_capture(args: &[String]) -> Result<(), Error> {
+    let args = CaptureArgs::parse(args)?;
+    if args.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    let config: KrustyGrabConfig = confy::load("krustygrab", None).unwrap_or_default();
+
+    let backend = default_backend();
+
+    let screen = args.screen.unwrap_or_default();
+    let screens = screens_number(backend.as_ref())?;
+    if let ScreenSelection::Single(index) = screen {
+        if index >= screens {
+            bail!("Screen {} not found, {} screen(s) available", index + 1, screens);
+        }
+    }
+
+    //Resolve the output path and format, falling back on the configuration defaults.
+    //Without an output path the name is built from the template once the screenshot is taken, since it may depend on its size.
+    let (out, format) = match (args.out, args.format) {
+        (Some(out), format) => match (out.extension().and_then(|e| e.to_str()), format) {
+            (Some(ext), Some(format)) if Format::from_str(ext)? != format => {
+                bail!("Output extension .{ext} does not match format {format}")
+            },
+            (Some(ext), _) => {
+                let format = Format::from_str(ext)?;
+                (Some(out), format)
+            },
+            (None, format) => {
+                let format = format.unwrap_or(config.save_format);
+                (Some(out.with_extension(format.to_string())), format)
+            },
+        },
+        (None, format) => (None, format.unwrap_or(config.save_format)),
+    };
+
+    let delay = args.delay.unwrap_or(config.screenshot_delay as u64);
+    if delay > 0 {
+        tracing::info!("Waiting {delay}s before the screenshot");
+        thread::sleep(Duration::from_secs(delay));
+    }
+
+    let image = match (screen, args.region) {
+        (ScreenSelection::Single(index), Some(region)) => backend.capture_region(index, region)?,
+        (ScreenSelection::All, Some(region)) => {
+            let desktop = take_screen(backend.as_ref(), screen)?;
+            let bounds = Rect::from_min_size(pos2(0.0, 0.0), vec2(desktop.width() as f32, desktop.height() as f32));
+            if !bounds.contains_rect(region) {
+                bail!("Region {:?} is outside of the desktop ({}x{})", region, desktop.width(), desktop.height());
+            }
+            desktop.region(&region, None)
+        },
+        (_, None) => take_screen(backend.as_ref(), screen)?,
+    };
+
+    let (save_path, named) = match out {
+        Some(out) => (out, false),
+        None => (template_path(&config, format, screen, image.size)?, true),
+    };
+
+    tracing::info!("Saving {:?} screenshot in {:?}", format, save_path);
+    save_image(image, save_path.clone(), &config.encoder)?;
+    //The number of the file names is used up only once the file is written
+    if named {
+        KrustyGrabConfig::store_name_counter(config.naming.counter + 1);
+    }
+    println!("{}", save_path.display());
+
+    Ok(())
+}
+
+///Path in the save folder named after the template of the configuration.
+///The user cannot be asked what to do with an existing file, so the prompt policy fails in that case.
+fn template_path(config: &KrustyGrabConfig, format: Format, screen: ScreenSelection, size: [usize; 2]) -> Result<PathBuf, Error> {
+    let context = NamingContext::now(screen, size, config.naming.counter);
+    let mut save_path = config.save_folder.clone();
+    save_path.push(render_template(&config.naming.template, &context)?);
+    save_path.set_extension(format.to_string());
+
+    let save_path = match config.naming.collision {
+        CollisionPolicy::Overwrite => save_path,
+        CollisionPolicy::Suffix => unique_path(&save_path),
+        CollisionPolicy::Prompt if save_path.exists() => bail!("{} already exists, use --out to choose another file", save_path.display()),
+        CollisionPolicy::Prompt => save_path,
+    };
+    Ok(save_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CaptureArgs, Error> {
+        CaptureArgs::parse(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn every_option_is_parsed() {
+        let args = parse(&["--screen", "2", "--region", "10,20,800,600", "--delay", "3", "--format", "JPG", "--out", "shot.jpg"]).unwrap();
+
+        assert_eq!(args, CaptureArgs {
+            screen: Some(ScreenSelection::Single(1)),
+            region: Some(Rect::from_min_size(pos2(10., 20.), vec2(800., 600.))),
+            delay: Some(3),
+            format: Some(Format::Jpeg),
+            out: Some(PathBuf::from("shot.jpg")),
+            help: false,
+        });
+        assert_eq!(parse(&[]).unwrap(), CaptureArgs::default());
+        assert!(parse(&["-h"]).unwrap().help);
+        assert_eq!(parse(&["--screen", "all"]).unwrap().screen, Some(ScreenSelection::All));
+    }
+
+    #[test]
+    fn invalid_numbers_are_rejected() {
+        assert!(parse(&["--screen", "first"]).is_err());
+        assert!(parse(&["--screen", "0"]).is_err());
+        assert!(parse(&["--screen", "-1"]).is_err());
+        assert!(parse(&["--delay", "1.5"]).is_err());
+        assert!(parse(&["--delay", "-3"]).is_err());
+    }
+
+    #[test]
+    fn missing_values_are_rejected() {
+        for option in ["--screen", "--region", "--delay", "--format", "--out"] {
+            let e = parse(&[option]).unwrap_err();
+            assert_eq!(e.to_string(), format!("Missing value for {option}"));
+        }
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert!(parse(&["--screens", "1"]).unwrap_err().to_string().starts_with("Unknown option --screens"));
+        assert!(parse(&["shot.png"]).is_err());
+        assert!(parse(&["--format", "svg"]).is_err());
+    }
+
+    #[test]
+    fn regions_need_four_values_and_a_positive_size() {
+        assert_eq!(parse_region(" 0, 0, 1, 1").unwrap(), Rect::from_min_size(pos2(0., 0.), vec2(1., 1.)));
+        assert!(parse_region("10,10,-800,600").is_err());
+        assert!(parse_region("10,10,800,0").is_err());
+        assert!(parse_region("-10,10,800,600").is_err());
+        assert!(parse_region("10,10,800").is_err());
+        assert!(parse_region("10,10,800,600,1").is_err());
+        assert!(parse_region("10,10,wide,600").is_err());
+        assert!(parse_region("").is_err());
+    }
+}
@@ -1,34 +1,85 @@
-use std::collections::BTreeMap;
+use std::{collections::{BTreeMap, HashMap}, str::FromStr, sync::Arc};
 #[allow(unused)]
 use std::{path::{PathBuf, Path}, time::Instant, io::Write};
 
-use crate::{painting::{icons::{icon_img, ICON_SIZE}, drawing::{DrawingType, RedoList}}, screenshot::screen_capture::save_image};
+use crate::{painting::{icons::{icon_img, ICON_SIZE}, document::AnnotationDocument, history::DEFAULT_HISTORY_BUDGET_MB, palette::ToolSettings, redaction::RedactionKey, highlight::HighlightKey}, screenshot::{capture_backend::{CaptureBackend, ScreenSelection, default_backend}, capture_worker::CaptureWorker, screen_capture::{EncoderSettings, PngCompression, PngFilter}, naming::{render_template, CollisionPolicy, NamingContext, NamingSettings, TEMPLATE_HELP}}, windows::{file_error_window::PendingFileError, overwrite_window::PendingOverwrite}};
 use eframe::{App, CreationContext};
 use egui::{
-    Button, ColorImage, Context, FontId, Grid, Layout, Rect,
+    Button, ColorImage, Context, DragValue, TextureHandle, FontId, Grid, Layout, Rect, Vec2,
     RichText, TextStyle, Visuals,
     Widget, Window, TextEdit,
-    Key, Modifiers, KeyboardShortcut, popup_below_widget, Id, pos2,
+    Key, Modifiers, KeyboardShortcut, popup_below_widget,
 };
 use native_dialog::FileDialog;
 use serde::{Deserialize, Serialize};
 use directories::UserDirs;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Format {
     Jpeg,
     Png,
     Gif,
+    WebP,
+    Bmp,
+    Tiff,
+    Qoi,
 }
 
-impl ToString for Format {
-    fn to_string(&self) -> String {
+impl Format {
+    ///Every supported format, in the order shown to the user
+    pub const ALL: [Format; 7] = [Format::Png, Format::Jpeg, Format::Gif, Format::WebP, Format::Bmp, Format::Tiff, Format::Qoi];
+
+    ///Name shown to the user
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Jpeg => "Jpeg",
+            Format::Png => "Png",
+            Format::Gif => "Gif",
+            Format::WebP => "WebP",
+            Format::Bmp => "Bmp",
+            Format::Tiff => "Tiff",
+            Format::Qoi => "Qoi",
+        }
+    }
+
+    ///File extensions of the format, the first one is used when saving
+    pub fn extensions(&self) -> &'static [&'static str] {
         match self {
-            Format::Jpeg => "jpg".to_string(),
-            Format::Png => "png".to_string(),
-            Format::Gif => "gif".to_string(),
+            Format::Jpeg => &["jpg", "jpeg"],
+            Format::Png => &["png"],
+            Format::Gif => &["gif"],
+            Format::WebP => &["webp"],
+            Format::Bmp => &["bmp"],
+            Format::Tiff => &["tiff", "tif"],
+            Format::Qoi => &["qoi"],
         }
     }
+
+    ///File dialog with a filter for every supported format
+    pub fn file_dialog() -> FileDialog<'static> {
+        Format::ALL
+            .iter()
+            .fold(FileDialog::new(), |dialog, f| dialog.add_filter(f.name(), f.extensions()))
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.extensions()[0])
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    ///Parse a format from its name or file extension
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        Format::ALL
+            .into_iter()
+            .find(|f| f.name().to_lowercase() == s || f.extensions().contains(&s.as_str()))
+            .ok_or_else(|| anyhow::anyhow!("Unsupported format {s:?}"))
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -52,19 +103,19 @@ impl MyHotKey{
             return format!("{:?}", self.key.unwrap()).to_string();
         }
         let mut m = "";
-        if self.modifier.alt == true {
+        if self.modifier.alt {
             m = "ALT";
         }
-        if self.modifier.ctrl == true {
+        if self.modifier.ctrl {
             m = "CTRL";
         }
-        if self.modifier.command == true {
+        if self.modifier.command {
             m = "CMD";
         }
-        if self.modifier.mac_cmd == true {
+        if self.modifier.mac_cmd {
             m = "CMD";
         }
-        if self.modifier.shift == true {
+        if self.modifier.shift {
             m = "SHIFT";
         }
         format!("{:?} + {:?}", m , self.key.unwrap()).to_string()
@@ -78,6 +129,20 @@ pub struct KrustyGrabConfig {
     pub save_format: Format,
     pub screenshot_delay: usize,
     pub myhotkeys: BTreeMap<String, MyHotKey>,
+    #[serde(default)] //Missing in the configurations saved by older versions
+    pub encoder: EncoderSettings,
+    #[serde(default)]
+    pub naming: NamingSettings,
+    ///Memory that the undo history can use, in megabytes
+    #[serde(default = "default_history_budget")]
+    pub history_budget_mb: usize,
+    ///Palette and last tool settings, kept between the sessions
+    #[serde(default)]
+    pub tools: ToolSettings,
+}
+
+fn default_history_budget() -> usize {
+    DEFAULT_HISTORY_BUDGET_MB
 }
 
 impl Default for KrustyGrabConfig {
@@ -94,10 +159,16 @@ impl Default for KrustyGrabConfig {
 
         Self {
             dark_mode: true,
-            save_folder: UserDirs::new().expect("Unable to load user dirs").desktop_dir().expect("Unable to find desktop dir").to_path_buf(),
+            save_folder: UserDirs::new()
+                .and_then(|dirs| dirs.desktop_dir().map(Path::to_path_buf))
+                .unwrap_or_else(|| PathBuf::from(".")), //Headless machines may have no desktop folder
             save_format: Format::Png,
             screenshot_delay: 0,
             myhotkeys,
+            encoder: EncoderSettings::default(),
+            naming: NamingSettings::default(),
+            history_budget_mb: default_history_budget(),
+            tools: ToolSettings::default(),
         }
     }
 }
@@ -106,6 +177,31 @@ impl KrustyGrabConfig {
     fn _new() -> Self {
         Default::default()
     }
+
+    ///Store the counter of the file names, leaving untouched the other settings not yet applied in the preferences.
+    ///Skipped in the tests, so that they do not change the configuration of the user.
+    pub fn store_name_counter(counter: u64) {
+        if cfg!(test) {
+            return;
+        }
+
+        let mut stored: KrustyGrabConfig = confy::load("krustygrab", None).unwrap_or_default();
+        stored.naming.counter = counter;
+
+        if let Err(e) = confy::store("krustygrab", None, stored) {
+            tracing::error!("Failed saving the file name counter: {}", e);
+        }
+    }
+
+    ///Store the palette and the last tool settings, leaving untouched the other settings not yet applied in the preferences
+    pub fn store_tools(tools: &ToolSettings) {
+        let mut stored: KrustyGrabConfig = confy::load("krustygrab", None).unwrap_or_default();
+        stored.tools = tools.clone();
+
+        if let Err(e) = confy::store("krustygrab", None, stored) {
+            tracing::error!("Failed saving the tool settings: {}", e);
+        }
+    }
 }
 
 ///Used to track the current area manipulation.
@@ -122,27 +218,38 @@ pub enum GrabStatus {
     BotMid,
     BotRight,
     Move,
+    ///Control point of a curved drawing
+    Control,
 }
 
 ///Used to select the window to be shown
 pub enum WindowStatus {
     Main,
     Crop,
-    Save,
 }
 
 pub struct KrustyGrab {
     pub config: KrustyGrabConfig,
     pub config_window: bool,
+    pub history_window: bool,
     pub settingkey: bool, 
     pub screen: Option<ColorImage>,
-    pub screenshot_requested: bool,
+    pub capture_worker: Option<CaptureWorker>,
+    pub file_error: Option<PendingFileError>,
+    pub pending_overwrite: Option<PendingOverwrite>,
+    pub document: AnnotationDocument,
+    pub redaction_textures: HashMap<RedactionKey, TextureHandle>,
+    pub highlight_textures: HashMap<HighlightKey, TextureHandle>,
     grab_status: GrabStatus,
     window_status: WindowStatus,
     select: Option<Rect>,
+    ///Selected area when the crop window was opened, restored if the crop is cancelled
+    prev_area: Option<Rect>,
+    ///Distance of the pointer from the center of the selected area while it is moved
+    center_distance: Option<Vec2>,
     temp_image: Option<ColorImage>,
-    selected_screen: usize,
-    pub save_path_request: Option<PathBuf>,
+    selected_screen: ScreenSelection,
+    pub capture_backend: Arc<dyn CaptureBackend>,
 }
 
 impl Default for KrustyGrab {
@@ -150,15 +257,23 @@ impl Default for KrustyGrab {
         Self {
             config: KrustyGrabConfig::_new(),
             config_window: false,
+            history_window: false,
             settingkey: false,
             screen: None,
             grab_status: GrabStatus::None,
             window_status: WindowStatus::Main,
             select: None,
+            prev_area: None,
+            center_distance: None,
             temp_image: None,
-            selected_screen: 0,
-            screenshot_requested: false,
-            save_path_request: None,
+            selected_screen: ScreenSelection::default(),
+            capture_backend: default_backend(),
+            capture_worker: None,
+            file_error: None,
+            pending_overwrite: None,
+            document: AnnotationDocument::new(),
+            redaction_textures: HashMap::new(),
+            highlight_textures: HashMap::new(),
         }
     }
 }
@@ -205,23 +320,45 @@ impl KrustyGrab {
         ctx.egui_ctx.set_style(style);
 
         let config: KrustyGrabConfig = confy::load("krustygrab", None).unwrap_or_default();
+        let mut document = AnnotationDocument::new();
+        document.set_history_budget(config.history_budget_mb);
+        config.tools.restore(&mut document.tool);
 
         Self {
             config,
+            document,
             ..Default::default()
         }
     }
 
+    ///Replace the source of the screenshots, e.g. with a [`crate::screenshot::capture_backend::FakeBackend`]
+    pub fn with_capture_backend(mut self, backend: Arc<dyn CaptureBackend>) -> Self {
+        self.capture_backend = backend;
+        self
+    }
+
     pub fn get_grab_status(&self) -> GrabStatus {
         self.grab_status
     }
     pub fn get_selected_area(&self) -> Option<Rect> {
         self.select
     } 
+    pub fn get_prev_area(&self) -> Option<Rect> {
+        self.prev_area
+    }
+    pub fn get_center_distance(&self) -> Option<Vec2> {
+        self.center_distance
+    }
     pub fn get_temp_image(&self) -> Option<ColorImage> {
         self.temp_image.clone()
     }
-    pub fn get_selected_screen(&self) -> usize {
+    pub fn get_temp_image_ref(&self) -> Option<&ColorImage> {
+        self.temp_image.as_ref()
+    }
+    pub fn get_temp_image_size(&self) -> Option<[usize; 2]> {
+        self.temp_image.as_ref().map(|im| im.size)
+    }
+    pub fn get_selected_screen(&self) -> ScreenSelection {
         self.selected_screen
     }
     pub fn set_grab_status(&mut self, new_status: GrabStatus) {
@@ -233,31 +370,125 @@ impl KrustyGrab {
     pub fn set_select_area(&mut self, new_area: Option<Rect>) {
         self.select = new_area;
     }
+    pub fn set_prev_area(&mut self, area: Option<Rect>) {
+        self.prev_area = area;
+    }
+    pub fn set_center_distance(&mut self, distance: Option<Vec2>) {
+        self.center_distance = distance;
+    }
     pub fn set_temp_image(&mut self, new_image: Option<ColorImage>) {
         self.screen = new_image.clone();
         self.temp_image = new_image.clone();
+        //The redaction and highlight textures show pixels of the previous screenshot
+        self.redaction_textures.clear();
+        self.highlight_textures.clear();
     }
     pub fn set_definitive_image(&mut self, new_image: Option<ColorImage>) {
         self.screen = new_image.clone();
     }
-    pub fn set_selected_screen(&mut self, new_screen: usize){
+    pub fn set_selected_screen(&mut self, new_screen: ScreenSelection){
         self.selected_screen = new_screen;
     }
 
     pub fn is_window_status_crop(&self) -> bool {
-        match self.window_status {
-            WindowStatus::Crop => true,
-            _ => false
-        }
+        matches!(self.window_status, WindowStatus::Crop)
     }
 
-    pub fn is_window_status_save(&self) -> bool {
-        match self.window_status {
-            WindowStatus::Save => true,
-            _ => false
+    ///Render the settings of the encoder of the selected format, as rows of the config grid
+    fn render_encoder_settings(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.config.encoder;
+
+        match self.config.save_format {
+            Format::Jpeg => {
+                ui.label("Quality:");
+                ui.add(egui::Slider::new(&mut settings.jpeg_quality, 1..=100));
+                ui.end_row();
+            },
+            Format::Png => {
+                ui.label("Compression:");
+                egui::ComboBox::from_id_source("Png compression")
+                    .selected_text(RichText::new(format!("{:?}", settings.png_compression)).text_style(TextStyle::Body))
+                    .show_ui(ui, |ui| {
+                        for compression in PngCompression::ALL {
+                            ui.selectable_value(
+                                &mut settings.png_compression,
+                                compression,
+                                RichText::new(format!("{:?}", compression)).text_style(TextStyle::Body),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Filter:");
+                egui::ComboBox::from_id_source("Png filter")
+                    .selected_text(RichText::new(format!("{:?}", settings.png_filter)).text_style(TextStyle::Body))
+                    .show_ui(ui, |ui| {
+                        for filter in PngFilter::ALL {
+                            ui.selectable_value(
+                                &mut settings.png_filter,
+                                filter,
+                                RichText::new(format!("{:?}", filter)).text_style(TextStyle::Body),
+                            );
+                        }
+                    });
+                ui.end_row();
+            },
+            Format::Gif => {
+                ui.label("Colors:");
+                ui.add(egui::Slider::new(&mut settings.gif_colors, 2..=256));
+                ui.end_row();
+
+                ui.label("Dithering:");
+                ui.checkbox(&mut settings.gif_dithering, "");
+                ui.end_row();
+            },
+            //No settings for the other formats
+            _ => {},
         }
     }
 
+    ///Render the template of the file names with its preview and the collision policy, as rows of the config grid
+    fn render_naming_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("File name:");
+        ui.text_edit_singleline(&mut self.config.naming.template)
+            .on_hover_text(TEMPLATE_HELP);
+        ui.end_row();
+
+        //Preview with the image currently shown, or with the size of the first screen
+        let size = match (self.get_selected_area(), &self.screen) {
+            (Some(area), _) => [area.width() as usize, area.height() as usize],
+            (None, Some(screen)) => screen.size,
+            (None, None) => self
+                .capture_backend
+                .displays()
+                .ok()
+                .and_then(|d| d.first().map(|d| [d.width as usize, d.height as usize]))
+                .unwrap_or_default(),
+        };
+        let context = NamingContext::now(self.get_selected_screen(), size, self.config.naming.counter);
+
+        ui.label("Preview:");
+        match render_template(&self.config.naming.template, &context) {
+            Ok(name) => ui.label(format!("{}.{}", name, self.config.save_format)),
+            Err(e) => ui.colored_label(ui.visuals().error_fg_color, e.to_string()),
+        };
+        ui.end_row();
+
+        ui.label("If the file exists:");
+        egui::ComboBox::from_id_source("Collision policy")
+            .selected_text(RichText::new(self.config.naming.collision.name()).text_style(TextStyle::Body))
+            .show_ui(ui, |ui| {
+                for policy in CollisionPolicy::ALL {
+                    ui.selectable_value(
+                        &mut self.config.naming.collision,
+                        policy,
+                        RichText::new(policy.name()).text_style(TextStyle::Body),
+                    );
+                }
+            });
+        ui.end_row();
+    }
+
     // Render the config panel
     fn render_config(&mut self, ctx: &Context) {
         Window::new(RichText::new("Configuration").text_style(TextStyle::Body)).show(ctx, |ui| {
@@ -270,11 +501,12 @@ impl KrustyGrab {
                     if Button::image_and_text(icon_img("folder", ctx), ICON_SIZE, "")
                         .ui(ui)
                         .clicked() {
-                            if let Some(path) = FileDialog::new()
+                            match FileDialog::new()
                                 .set_location(&self.config.save_folder)
-                                .show_open_single_dir()
-                                .expect("Unable to visualize the folder selector") {
-                                    self.config.save_folder = path.clone();
+                                .show_open_single_dir() {
+                                    Ok(Some(path)) => self.config.save_folder = path,
+                                    Ok(None) => {},
+                                    Err(e) => self.notify_file_error("show the folder selector", None, e.to_string()),
                                 }
                         }
                     ui.shrink_width_to_current();
@@ -286,29 +518,30 @@ impl KrustyGrab {
                     ui.label("Save format:");
                     egui::ComboBox::from_label("Format")
                         .selected_text(
-                            RichText::new(format!("{:?}", self.config.save_format))
+                            RichText::new(self.config.save_format.name())
                                 .text_style(TextStyle::Body),
                         )
                         .show_ui(ui, |ui| {
                             ui.style_mut().wrap = Some(false);
                             ui.set_min_width(60.0);
-                            ui.selectable_value(
-                                &mut self.config.save_format,
-                                Format::Png,
-                                RichText::new("Png").text_style(TextStyle::Body),
-                            );
-                            ui.selectable_value(
-                                &mut self.config.save_format,
-                                Format::Jpeg,
-                                RichText::new("Jpeg").text_style(TextStyle::Body),
-                            );
-                            ui.selectable_value(
-                                &mut self.config.save_format,
-                                Format::Gif,
-                                RichText::new("Gif").text_style(TextStyle::Body),
-                            );
+                            for format in Format::ALL {
+                                ui.selectable_value(
+                                    &mut self.config.save_format,
+                                    format,
+                                    RichText::new(format.name()).text_style(TextStyle::Body),
+                                );
+                            }
                         });
                     ui.end_row();
+
+                    self.render_encoder_settings(ui);
+                    self.render_naming_settings(ui);
+
+                    ui.label("Undo history memory:");
+                    ui.add(DragValue::new(&mut self.config.history_budget_mb).clamp_range(16..=4096).suffix(" MB"))
+                        .on_hover_text("The oldest edits are forgotten when the history uses more memory");
+                    ui.end_row();
+
                     ui.separator();
                     ui.separator();
                     ui.end_row();
@@ -339,11 +572,11 @@ impl KrustyGrab {
                                 // tracing::info!("Reading input for {}", shortcut_name ); 
                                 let mut flag = false; 
                                 if i.keys_down.iter().nth(0).is_some(){
-                                    let new_hotkey = MyHotKey::new(i.modifiers.clone(), i.keys_down.iter().nth(0).unwrap().clone());
+                                    let new_hotkey = MyHotKey::new(i.modifiers, *i.keys_down.iter().nth(0).unwrap());
                                     flag = ! (new_hotkey.key == my_hotkey.key && new_hotkey.modifier == my_hotkey.modifier);
                                     my_hotkey = new_hotkey;
                                 }
-                                return flag; 
+                                flag
                             });
 
                             if changed {
@@ -396,6 +629,7 @@ impl KrustyGrab {
                             } else {
                                 tracing::info!("App state saved");
                             }
+                            self.document.set_history_budget(self.config.history_budget_mb);
                             self.config_window = false;
                         }
                     });
@@ -406,26 +640,11 @@ impl KrustyGrab {
 }
 
 impl App for KrustyGrab {
-    // Function called after every frame render of the app. Used to complete the save of the screenshot. 
-    fn post_rendering(&mut self, _window_size_px: [u32; 2], frame: &eframe::Frame) {
-        // if we have requested a frame.screenshot
-        if let Some(res) = frame.screenshot() {
-            // and if we have a save path (should be redundant)
-            if let Some(path) = self.save_path_request.clone() { 
-                // save the image 
-                let save_region = &self.get_selected_area().unwrap_or_else(||Rect::from_min_size(pos2(0.0, 0.0), frame.info().window_info.size));
-                save_image(res.region(save_region, None), path).expect("Unable to save");
-            }        
-            self.save_path_request = None;
-            self.set_window_status(WindowStatus::Main);
-        }
-    }
-    
     // Function called at every render of the app 
     fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
         
-        // If we are not saving we go back to not full screen 
-        if self.save_path_request.is_none(){
+        // If we are not cropping we go back to not full screen 
+        if !self.is_window_status_crop(){
             frame.set_fullscreen(false);
         }
         
@@ -441,19 +660,22 @@ impl App for KrustyGrab {
             self.render_config(ctx);
         }
 
-        //Take the screenshot before turning on the visibility of the window
-        if self.screenshot_requested {
-            self.set_screenshot(ctx);
-            frame.set_visible(true);
-            self.screenshot_requested = false;
-        }
+        //Follow the progress of the requested screenshot
+        self.update_capture(frame);
 
         // In base of the status it chooses what to display
         match self.window_status {
-            WindowStatus::Main => self.main_window(ctx, frame),
+            WindowStatus::Main => {
+                self.main_window(ctx, frame);
+                self.overwrite_window(ctx);
+                if self.history_window {
+                    self.history_window(ctx);
+                }
+            },
             WindowStatus::Crop => self.crop_screen_window(ctx, frame),
-            WindowStatus::Save => self.save_window(ctx, frame),
         }
+        //Errors are notified also when raised while cropping
+        self.file_error_window(ctx);
     
         // When we are not setting a shortcut
         if !self.settingkey {
@@ -462,101 +684,36 @@ impl App for KrustyGrab {
                 for s in self.config.myhotkeys.iter().filter(|x|x.1.key.is_some()).clone() {
                     let sh = KeyboardShortcut::new(s.1.modifier, s.1.key.unwrap());
                     if x.consume_shortcut(&sh){
-                        return Some(s.clone());
+                        return Some(s);
                     }
                 }
-                return None;
+                None
             }){
                 tracing::info!("Shortcut pressed: {:?}", hk.0);
                 match hk.0.as_str() {
                     "Undo" => {
-
-                        if self.screen.is_some() {
-                            let do_undo = ctx.memory(|mem| {
-                                match mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing")) {
-                                    Some(d) => !d.is_empty(),
-                                    None => false,
-                                }
-                            });
-                            if do_undo{
-
-                                
-                                tracing::info!("Undoing");
-                            ctx.memory_mut(|mem| {
-                                match mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing")) {
-                                    Some(mut drawings) => {
-                                        let last = drawings.pop().expect("Drawings list should contains at least one element at this point");
-                                        
-                                        //Retrieve and update Redo list
-                                        let redo_list = match mem.data.get_temp::<RedoList>(Id::from("Redo_list")){
-                                            Some(mut redo) => {
-                                                redo.push(last);
-                                                redo
-                                            },
-                                            None => {
-                                                let mut redo = RedoList::new(Self::REDO_LIST_SIZE);
-                                                redo.push(last);
-                                                redo
-                                            },
-                                        };
-                                        
-                                        mem.data.insert_temp(Id::from("Redo_list"), redo_list);
-                                        mem.data.insert_temp(Id::from("Drawing"), drawings);
-                                    },
-                                    None => {},
-                                };
-                            });
-                        }else {
-                                tracing::info!("Nothing to undo");
-                        }
-                        }else {
+                        if self.screen.is_none() {
                             tracing::info!("You should do a screen before");
+                        } else if self.undo() {
+                            tracing::info!("Undoing");
+                        } else {
+                            tracing::info!("Nothing to undo");
                         }
                     }
                     "Redo" => {
-                        if self.screen.is_some() {
-                            let do_redo = ctx.memory(|mem| {
-                                match mem.data.get_temp::<RedoList>(Id::from("Redo_list")) {
-                                    Some(d) => !d.is_empty(),
-                                    None => false,
-                                }
-                            });
-                            if do_redo { 
-                                tracing::info!("Undoing");
-                                ctx.memory_mut(|mem| {
-                                    match mem.data.get_temp::<RedoList>(Id::from("Redo_list")) {
-                                        Some(mut redo) => {            
-                                            let last = redo.pop().expect("Redo list should contains at least one element at this point");
-            
-                                            //Retrieve and update drawings list
-                                            match mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing")) {
-                                                Some(mut d) => {
-                                                    d.push(last);
-                                                    mem.data.insert_temp(Id::from("Drawing"), d);
-                                                },
-                                                None => panic!("Drawings list should exists in memory at this point"),
-                                            }
-                                            
-                                            mem.data.insert_temp(Id::from("Redo_list"), redo);
-                                        },
-                                        None => {},
-                                    };
-                                });                      
-                            } else {
-                                tracing::info!("Nothing to redo");
-                            }
-                        } else {
+                        if self.screen.is_none() {
                             tracing::info!("You should do a screen before");
+                        } else if self.redo() {
+                            tracing::info!("Redoing");
+                        } else {
+                            tracing::info!("Nothing to redo");
                         }
                     }
                     "Screen" => {
-                        frame.set_visible(false);
-                        self.screenshot_requested = true;
+                        self.request_screenshot(ctx, false);
                     }
                     "Screen Area" => {
-                        frame.set_visible(false);
-                        self.set_window_status(self::WindowStatus::Crop);
-                        self.screenshot_requested = true;
+                        self.request_screenshot(ctx, true);
                     },
                     _ => tracing::error!("Unknown shortcut pressed")
                 }
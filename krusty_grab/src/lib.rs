@@ -1,4 +1,5 @@
 pub mod windows;
 pub mod krustygrab;
 pub mod painting;
-pub mod screenshot;
\ No newline at end of file
+pub mod screenshot;
+pub mod cli;
\ No newline at end of file
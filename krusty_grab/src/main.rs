@@ -1,18 +1,25 @@
-mod krustygrab;
-mod painting;
-mod windows;
-mod screenshot;
-
 use eframe::{run_native, NativeOptions};
 use egui::Vec2;
-use krustygrab::KrustyGrab;
+use krusty_grab::{cli, krustygrab::KrustyGrab};
 
 fn main() {
     tracing_subscriber::fmt::init();
-    let mut win_options = NativeOptions::default();
-    
-    win_options.initial_window_size = Some(Vec2::new(960., 540.));
-    win_options.min_window_size = Some(Vec2::new(939.0, 0.0));                  //Avoid toolbar overlapping
+
+    //Headless capture, without creating the window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("capture") {
+        if let Err(e) = cli::run_capture(&args[1..]) {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let win_options = NativeOptions {
+        initial_window_size: Some(Vec2::new(960., 540.)),
+        min_window_size: Some(Vec2::new(939.0, 0.0)),                           //Avoid toolbar overlapping
+        ..Default::default()
+    };
 
     run_native(
             "KrustyGrab", 
@@ -0,0 +1,176 @@
+use egui::{emath::{RectTransform, Rot2}, CursorIcon, DragValue, Pos2, Shape, Stroke, Ui, Vec2, Widget};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    krustygrab::KrustyGrab,
+    painting::{document::DrawingMode, drawing::DrawingType},
+};
+
+///Points sampled along a curved arrow, used to draw and hit it
+const CURVE_SEGMENTS: usize = 32;
+
+///Ends of an arrow with a head
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ArrowHeads {
+    #[default]
+    Single,
+    Double,
+}
+
+///Shape of the heads of an arrow
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum ArrowHead {
+    ///Two lines
+    #[default]
+    Open,
+    ///Triangle filled with the color of the arrow
+    Filled,
+}
+
+///Appearance of an arrow
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ArrowOptions {
+    pub heads: ArrowHeads,
+    pub head: ArrowHead,
+    ///Length of the heads as a fraction of the distance between the ends
+    pub head_size: f32,
+}
+
+impl Default for ArrowOptions {
+    fn default() -> Self {
+        Self { heads: ArrowHeads::default(), head: ArrowHead::default(), head_size: 0.25 }
+    }
+}
+
+///Point of the quadratic Bezier curve from `a` to `b` with control point `c`, for `t` between 0 and 1
+fn bezier(a: Pos2, c: Pos2, b: Pos2, t: f32) -> Pos2 {
+    let u = 1. - t;
+    (a.to_vec2() * u * u + c.to_vec2() * 2. * u * t + b.to_vec2() * t * t).to_pos2()
+}
+
+///Points of the body of the arrow from `p` to `tip`, curved towards the control point if there is one
+pub fn arrow_points(p: Pos2, tip: Pos2, c: Option<Pos2>) -> Vec<Pos2> {
+    match c {
+        Some(c) => (0..=CURVE_SEGMENTS).map(|i| bezier(p, c, tip, i as f32 / CURVE_SEGMENTS as f32)).collect(),
+        None => vec![p, tip],
+    }
+}
+
+///Control point bending a new curved arrow: a quarter of its length away from the middle, on its left
+pub fn default_control(p: Pos2, v: Vec2) -> Pos2 {
+    p + v / 2. - v.rot90() / 4.
+}
+
+///Head with the tip in `tip`, pointing along `dir`
+fn head(tip: Pos2, dir: Vec2, length: f32, style: ArrowHead, stroke: Stroke) -> Vec<Shape> {
+    //Same angle of Painter::arrow
+    let rot = Rot2::from_angle(std::f32::consts::TAU / 10.0);
+    let dir = dir.normalized();
+    let (left, right) = (tip - length * (rot * dir), tip - length * (rot.inverse() * dir));
+
+    match style {
+        ArrowHead::Open => vec![Shape::line_segment([tip, left], stroke), Shape::line_segment([tip, right], stroke)],
+        ArrowHead::Filled => vec![Shape::convex_polygon(vec![tip, left, right], stroke.color, stroke)],
+    }
+}
+
+///Shapes of the arrow from `p` along `v`, placed on the painting area by the transform. The stroke is the one of the painting area.
+pub fn arrow_shapes(p: Pos2, v: Vec2, c: Option<Pos2>, s: Stroke, o: &ArrowOptions, transform: &RectTransform) -> Vec<Shape> {
+    let points: Vec<Pos2> = arrow_points(p, p + v, c).into_iter().map(|p| transform.transform_pos(p)).collect();
+    let length = v.length() * transform.scale().x * o.head_size;
+    let (first, last) = (points[0], points[points.len() - 1]);
+    //The heads follow the direction of the curve at the ends, the one of the arrow if the control point is on an end
+    let along = |dir: Vec2, straight: Vec2| if dir.length_sq() > f32::EPSILON { dir } else { straight };
+    let (start_dir, end_dir) = match c.map(|c| transform.transform_pos(c)) {
+        Some(c) => (along(first - c, first - last), along(last - c, last - first)),
+        None => (first - last, last - first),
+    };
+
+    let mut shapes = vec![Shape::line(points, s)];
+    shapes.extend(head(last, end_dir, length, o.head, s));
+    if o.heads == ArrowHeads::Double {
+        shapes.extend(head(first, start_dir, length, o.head, s));
+    }
+    shapes
+}
+
+impl DrawingType {
+    ///Control point of the curved arrows
+    pub fn control_point(&self) -> Option<Pos2> {
+        match self {
+            DrawingType::Arrow { c, .. } => *c,
+            _ => None,
+        }
+    }
+
+    ///Move the control point of a curved arrow
+    pub fn set_control_point(&mut self, pos: Pos2) {
+        if let DrawingType::Arrow { c: Some(c), .. } = self {
+            *c = pos;
+        }
+    }
+}
+
+impl KrustyGrab {
+    ///Options of the arrows, shown while drawing arrows or when an arrow is selected.
+    ///The options of the selected arrow are shown and changed instead of the tool ones.
+    pub fn arrow_options(&mut self, ui: &mut Ui) {
+        let selected = self.document.selection.as_ref().and_then(|s| match self.document.drawings().get(s.index) {
+            Some(DrawingType::Arrow { o, c, .. }) => Some((s.index, *o, c.is_some())),
+            _ => None,
+        });
+        let (options, mut curved) = match selected {
+            Some((_, options, curved)) => (options, curved),
+            None if self.document.tool.mode == DrawingMode::Arrow => (self.document.tool.arrow, self.document.tool.curved_arrow),
+            None => return,
+        };
+
+        let mut double = options.heads == ArrowHeads::Double;
+        let mut filled = options.head == ArrowHead::Filled;
+        let mut head_size = options.head_size * 100.;
+
+        let mut changed = ui.toggle_value(&mut double, "⬌").on_hover_cursor(CursorIcon::PointingHand).on_hover_text_at_pointer("Double head").changed();
+        changed |= ui.toggle_value(&mut filled, "⏵").on_hover_cursor(CursorIcon::PointingHand).on_hover_text_at_pointer("Filled heads").changed();
+        changed |= ui.toggle_value(&mut curved, "Curved").on_hover_cursor(CursorIcon::PointingHand)
+            .on_hover_text_at_pointer("Curved arrow, bent with its control point in the select tool").changed();
+        changed |= DragValue::new(&mut head_size)
+            .prefix("Head: ")
+            .suffix("%")
+            .speed(0.5)
+            .clamp_range(5.0..=100.0)
+            .ui(ui)
+            .on_hover_text_at_pointer("Size of the heads, compared to the length of the arrow")
+            .changed();
+
+        if changed {
+            let options = ArrowOptions {
+                heads: if double { ArrowHeads::Double } else { ArrowHeads::Single },
+                head: if filled { ArrowHead::Filled } else { ArrowHead::Open },
+                head_size: head_size / 100.,
+            };
+            tracing::info!("Arrow options changed to {:?}, curved {}", options, curved);
+            self.set_arrow_options(options, curved);
+        }
+    }
+
+    ///Change the options of the selected arrow, or of the tool if no arrow is selected
+    pub fn set_arrow_options(&mut self, options: ArrowOptions, curved: bool) {
+        let selected = self.document.selection.as_ref().map(|s| s.index);
+        match selected.and_then(|index| Some((index, self.document.drawings().get(index)?.clone()))) {
+            Some((index, DrawingType::Arrow { p, v, s, c, l, .. })) => {
+                let c = match (c, curved) {
+                    (Some(c), true) => Some(c),
+                    (None, true) => Some(default_control(p, v)),
+                    (_, false) => None,
+                };
+                self.document.modify(index, DrawingType::Arrow { p, v, s, o: options, c, l });
+                self.document.end_edit();
+            },
+            _ => {
+                self.document.tool.arrow = options;
+                self.document.tool.curved_arrow = curved;
+            },
+        }
+    }
+}
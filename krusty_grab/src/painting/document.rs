@@ -0,0 +1,777 @@
+use egui::{Color32, ColorImage, Pos2, Rect, Rgba, Stroke, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    painting::{
+        arrow::{default_control, ArrowOptions},
+        drawing::DrawingType,
+        eraser::EraserMode,
+        history::{Command, History, DEFAULT_HISTORY_BUDGET_MB},
+        selection::Selection,
+        smoothing::BrushOptions,
+        steps::{next_step, renumber_steps, step_radius, StepStyle},
+        style::LineStyle,
+        text::TextOptions,
+    },
+};
+
+///Tool used to draw on the screenshot
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum DrawingMode {
+    Brush,
+    Highlighter,
+    ///Freehand highlight
+    Marker,
+    Rectangle,
+    FilledRectangle,
+    Circle,
+    FilledCircle,
+    Ellipse,
+    FilledEllipse,
+    Arrow,
+    Line,
+    ///Open path of segments, one point for every click
+    Polyline,
+    ///Closed path of segments, one point for every click
+    Polygon,
+    FilledPolygon,
+    Text,
+    ///Remove the drawings, or the parts of the brush strokes, under the pointer
+    Eraser,
+    ///Pick the color of the drawings from the screenshot
+    Eyedropper,
+    Pixelate,
+    Blur,
+    StepMarker,
+    ///Select an existing drawing to move, resize, restyle or delete it
+    Select,
+}
+
+///Settings used for the new drawings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToolState {
+    pub mode: DrawingMode,
+    pub color: Rgba,
+    pub thickness: f32,
+    ///Radius of the corners of the rectangles, in screenshot pixels
+    pub rounding: f32,
+    pub text: TextOptions,
+    pub arrow: ArrowOptions,
+    ///Set to draw curved arrows
+    pub curved_arrow: bool,
+    pub step_style: StepStyle,
+    pub line: LineStyle,
+    pub eraser: EraserMode,
+    pub brush: BrushOptions,
+    ///Set to fit the highlights on the rows of text of the screenshot
+    pub highlight_snap: bool,
+}
+
+impl Default for ToolState {
+    fn default() -> Self {
+        Self {
+            mode: DrawingMode::Brush,
+            color: Rgba::from(Color32::GREEN),
+            thickness: 1.0,
+            rounding: 0.0,
+            text: TextOptions::default(),
+            arrow: ArrowOptions::default(),
+            curved_arrow: false,
+            step_style: StepStyle::default(),
+            line: LineStyle::default(),
+            eraser: EraserMode::default(),
+            brush: BrushOptions::default(),
+            highlight_snap: false,
+        }
+    }
+}
+
+impl ToolState {
+    pub fn stroke(&self) -> Stroke {
+        Stroke::new(self.thickness, self.color)
+    }
+}
+
+///Text being written with the text tool
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub pos: Pos2,
+    pub text: String,
+    ///Index of the text in the drawings once added, so that the following changes replace it
+    pub index: Option<usize>,
+    ///Set when the editor has to take the keyboard focus
+    pub focus: bool,
+}
+
+///Placement of the screenshot on the canva and popups covering it, updated at every frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasState {
+    ///Screenshot pixels for every point of the canva
+    pub ratio: f32,
+    ///Position of the screenshot on the canva
+    pub pos: Pos2,
+    ///The pointer does not draw while the color picker is open
+    pub color_picker_open: bool,
+    ///Set by the settings menu while it is open, cleared once the canva has been drawn
+    pub settings_menu_open: bool,
+}
+
+impl Default for CanvasState {
+    fn default() -> Self {
+        Self {
+            ratio: 1.0,
+            pos: Pos2::ZERO,
+            color_picker_open: false,
+            settings_menu_open: false,
+        }
+    }
+}
+
+///Annotations of the screenshot with the state of the tools used to edit them and the history of the edits.
+///Positions are expressed in screenshot pixels.
+pub struct AnnotationDocument {
+    drawings: Vec<DrawingType>,
+    history: History,
+    pub tool: ToolState,
+    ///Position where the pointer was pressed, while dragging on the canva
+    drag_origin: Option<Pos2>,
+    pub text_edit: Option<TextEdit>,
+    pub canvas: CanvasState,
+    pub selection: Option<Selection>,
+    ///Points of the polyline or polygon being drawn, with the tool used to start it
+    path: Vec<Pos2>,
+    path_mode: DrawingMode,
+}
+
+///Change of the screenshot caused by undoing or redoing a command, applied by the caller
+pub enum ScreenChange {
+    Area(Option<Rect>),
+    Image { image: Option<ColorImage>, selected_area: Option<Rect> },
+}
+
+///Outcome of an undo or redo
+pub enum HistoryStep {
+    ///There was nothing to undo or redo
+    Nothing,
+    ///Only the drawings changed
+    Drawings,
+    ///The screenshot changed too, the caller has to show the new one
+    Screen(ScreenChange),
+}
+
+impl HistoryStep {
+    fn from_change(change: Option<ScreenChange>) -> Self {
+        match change {
+            Some(change) => HistoryStep::Screen(change),
+            None => HistoryStep::Drawings,
+        }
+    }
+}
+
+impl Default for AnnotationDocument {
+    fn default() -> Self {
+        Self {
+            drawings: Vec::new(),
+            history: History::new(DEFAULT_HISTORY_BUDGET_MB * 1024 * 1024),
+            tool: ToolState::default(),
+            drag_origin: None,
+            text_edit: None,
+            canvas: CanvasState::default(),
+            selection: None,
+            path: Vec::new(),
+            path_mode: DrawingMode::Polyline,
+        }
+    }
+}
+
+impl AnnotationDocument {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn drawings(&self) -> &[DrawingType] {
+        &self.drawings
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    ///Set the memory budget of the history, in megabytes
+    pub fn set_history_budget(&mut self, megabytes: usize) {
+        self.history.set_budget(megabytes * 1024 * 1024);
+    }
+
+    ///Replace the drawings, e.g. with the ones loaded from a project, forgetting the history.
+    ///The given drawings can be restored with redo, the next one last.
+    pub fn replace(&mut self, drawings: Vec<DrawingType>, redo_list: Vec<DrawingType>) {
+        self.history.clear();
+        self.drag_origin = None;
+        self.text_edit = None;
+        self.selection = None;
+        self.path.clear();
+
+        let first = drawings.len();
+        self.drawings = drawings;
+        renumber_steps(&mut self.drawings);
+        for (i, drawing) in redo_list.into_iter().enumerate() {
+            self.history.push(Command::AddDrawing { index: first + i, drawing });
+        }
+        //The drawings of the redo list are not applied, so they are only moved in the undone commands
+        while self.history.undo().is_some() {}
+    }
+
+    ///Drawings that the next redos add back, the next one first. Used to save them in the projects.
+    pub fn redo_drawings(&self) -> Vec<DrawingType> {
+        self.history
+            .undone()
+            .map_while(|c| match c {
+                Command::AddDrawing { drawing, .. } => Some(drawing.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    ///Apply a command and record it in the history
+    pub fn execute(&mut self, command: Command) {
+        apply(&mut self.drawings, &command, true);
+        self.history.push(command);
+    }
+
+    ///Apply a step of a continuous edit, recorded together with the previous steps of the same edit
+    pub fn execute_merging(&mut self, command: Command) {
+        apply(&mut self.drawings, &command, true);
+        self.history.push_merging(command);
+    }
+
+    ///Record a change of the screenshot already applied by the caller
+    pub fn record(&mut self, command: Command) {
+        if let Command::ReplaceImage { after, .. } = &command {
+            self.drawings = after.drawings.clone();
+            renumber_steps(&mut self.drawings);
+            self.drag_origin = None;
+            self.text_edit = None;
+            self.selection = None;
+            self.path.clear();
+        }
+        self.history.push(command);
+    }
+
+    ///Add a drawing. The undone edits can no longer be redone.
+    pub fn push(&mut self, drawing: DrawingType) {
+        self.execute(Command::AddDrawing { index: self.drawings.len(), drawing });
+    }
+
+    pub fn delete(&mut self, index: usize) {
+        if let Some(drawing) = self.drawings.get(index).cloned() {
+            self.execute(Command::DeleteDrawing { index, drawing });
+        }
+    }
+
+    ///Move a drawing. Consecutive moves of the same drawing are undone together until `end_edit` is called.
+    pub fn translate(&mut self, index: usize, offset: Vec2) {
+        if index < self.drawings.len() && offset != Vec2::ZERO {
+            self.execute_merging(Command::MoveDrawing { index, offset });
+        }
+    }
+
+    ///Change the stroke of a drawing. Consecutive changes of the same drawing are undone together until `end_edit` is called.
+    pub fn restyle(&mut self, index: usize, stroke: Stroke) {
+        if let Some(before) = self.drawings.get(index).map(DrawingType::stroke) {
+            if before != stroke {
+                self.execute_merging(Command::RestyleDrawing { index, before, after: stroke });
+            }
+        }
+    }
+
+    ///Replace a drawing with a modified version. Consecutive changes of the same drawing are undone together until `end_edit` is called.
+    pub fn modify(&mut self, index: usize, drawing: DrawingType) {
+        if let Some(before) = self.drawings.get(index).cloned() {
+            self.execute_merging(Command::ReplaceDrawing { index, before, after: drawing });
+        }
+    }
+
+    ///Complete the continuous edit in progress
+    pub fn end_edit(&mut self) {
+        self.history.seal();
+    }
+
+    ///Check if a brush stroke is being drawn. It is not in the history yet, so undo and redo wait for it to be completed.
+    pub fn stroke_in_progress(&self) -> bool {
+        matches!(self.drawings.last(), Some(DrawingType::Brush { end: false, .. }))
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.stroke_in_progress() && self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.stroke_in_progress() && self.history.can_redo()
+    }
+
+    ///Revert the last command
+    pub fn undo(&mut self) -> HistoryStep {
+        //The indexes of the drawings may change, so the selected one is forgotten
+        self.selection = None;
+        if self.stroke_in_progress() {
+            return HistoryStep::Nothing;
+        }
+        match self.history.undo() {
+            Some(command) => HistoryStep::from_change(apply(&mut self.drawings, command, false)),
+            None => HistoryStep::Nothing,
+        }
+    }
+
+    ///Apply again the last undone command
+    pub fn redo(&mut self) -> HistoryStep {
+        self.selection = None;
+        if self.stroke_in_progress() {
+            return HistoryStep::Nothing;
+        }
+        match self.history.redo() {
+            Some(command) => HistoryStep::from_change(apply(&mut self.drawings, command, true)),
+            None => HistoryStep::Nothing,
+        }
+    }
+
+    ///Add a point to the brush or marker stroke in progress, starting a new stroke if there is none
+    pub fn brush_to(&mut self, pos: Pos2) {
+        match self.drawings.last_mut() {
+            Some(DrawingType::Brush { points, end: false, .. } | DrawingType::Marker { points, end: false, .. }) => points.push(pos),
+            //The stroke is recorded in the history once completed
+            _ => {
+                let (points, s, l) = (vec![pos], self.tool.stroke(), self.tool.line);
+                self.drawings.push(match self.tool.mode {
+                    DrawingMode::Marker => DrawingType::Marker { points, s, end: false, l },
+                    _ => DrawingType::Brush { points, s, end: false, l },
+                });
+            },
+        }
+    }
+
+    ///Complete the brush or marker stroke in progress, adding its last point if given. The points are simplified and smoothed with the brush options.
+    pub fn end_brush(&mut self, last: Option<Pos2>) {
+        if let Some(DrawingType::Brush { points, end: end @ false, .. } | DrawingType::Marker { points, end: end @ false, .. }) = self.drawings.last_mut() {
+            if let Some(pos) = last {
+                points.push(pos);
+            }
+            *points = self.tool.brush.apply(points);
+            *end = true;
+
+            let drawing = self.drawings.pop().expect("The stroke is the last drawing");
+            self.push(drawing);
+        }
+    }
+
+    ///Position where the current drag started, set on the first call
+    pub fn drag_origin(&mut self, start: impl FnOnce() -> Pos2) -> Pos2 {
+        *self.drag_origin.get_or_insert_with(start)
+    }
+
+    ///Complete the current drag, returning its origin
+    pub fn end_drag(&mut self) -> Option<Pos2> {
+        self.drag_origin.take()
+    }
+
+    ///Drawing obtained dragging from `start` to `end` with the current tool. None for the tools that are not used dragging.
+    pub fn drag_drawing(&self, start: Pos2, end: Pos2) -> Option<DrawingType> {
+        let (s, l) = (self.tool.stroke(), self.tool.line);
+
+        match self.tool.mode {
+            DrawingMode::Rectangle => Some(DrawingType::Rectangle { r: Rect::from_two_pos(start, end), s, rounding: self.tool.rounding, l }),
+            DrawingMode::FilledRectangle => Some(DrawingType::FilledRectangle { r: Rect::from_two_pos(start, end), s, rounding: self.tool.rounding, l }),
+            DrawingMode::Ellipse => Some(DrawingType::Ellipse { r: Rect::from_two_pos(start, end), s, l }),
+            DrawingMode::FilledEllipse => Some(DrawingType::FilledEllipse { r: Rect::from_two_pos(start, end), s, l }),
+            DrawingMode::Pixelate => Some(DrawingType::Pixelate { r: Rect::from_two_pos(start, end), s }),
+            DrawingMode::Blur => Some(DrawingType::Blur { r: Rect::from_two_pos(start, end), s }),
+            DrawingMode::Highlighter => {
+                //Horizontal band as high as ten times the thickness, centered on the starting point
+                let from_here = Pos2::new(start.x.min(end.x), start.y - s.width * 5.);
+                let to_there = Pos2::new(start.x.max(end.x), start.y + s.width * 5.);
+                Some(DrawingType::Highlighter { r: Rect::from_min_max(from_here, to_there), s, l })
+            },
+            //Constructed with one side on the starting point and the opposite on the cursor
+            DrawingMode::Circle => Some(DrawingType::Circle { c: start + (end - start) / 2.0, r: end.distance(start) / 2.0, s, l }),
+            DrawingMode::FilledCircle => Some(DrawingType::FilledCircle { c: start + (end - start) / 2.0, r: end.distance(start) / 2.0, s, l }),
+            DrawingMode::Arrow => {
+                let v = end - start;
+                let c = self.tool.curved_arrow.then(|| default_control(start, v));
+                Some(DrawingType::Arrow { p: start, v, s, o: self.tool.arrow, c, l })
+            },
+            DrawingMode::Line => Some(DrawingType::Line { points: [start, end], s, l }),
+            //Placed where the pointer is released, with the number following the existing markers
+            DrawingMode::StepMarker => Some(DrawingType::StepMarker {
+                c: end,
+                r: step_radius(s.width),
+                n: next_step(&self.drawings),
+                style: self.tool.step_style,
+                s,
+            }),
+            DrawingMode::Brush
+            | DrawingMode::Marker
+            | DrawingMode::Polyline
+            | DrawingMode::Polygon
+            | DrawingMode::FilledPolygon
+            | DrawingMode::Text
+            | DrawingMode::Eraser
+            | DrawingMode::Eyedropper
+            | DrawingMode::Select => None,
+        }
+    }
+
+    pub fn path_points(&self) -> &[Pos2] {
+        &self.path
+    }
+
+    ///Add a point to the polyline or polygon being drawn, starting a new one if there is none
+    pub fn add_path_point(&mut self, pos: Pos2) {
+        if self.path.is_empty() {
+            self.path_mode = self.tool.mode;
+        }
+        if self.path.last() != Some(&pos) {
+            self.path.push(pos);
+        }
+    }
+
+    ///Polyline or polygon made of the points added so far and the given last point, if any.
+    ///None when there are less than two points or the tool does not use them.
+    pub fn path_drawing(&self, last: Option<Pos2>) -> Option<DrawingType> {
+        let mode = if self.path.is_empty() { self.tool.mode } else { self.path_mode };
+        let mut points = self.path.clone();
+        points.extend(last.filter(|pos| points.last() != Some(pos)));
+        if points.len() < 2 {
+            return None;
+        }
+
+        let (s, l) = (self.tool.stroke(), self.tool.line);
+        match mode {
+            DrawingMode::Polyline => Some(DrawingType::Polyline { points, s, l }),
+            DrawingMode::Polygon => Some(DrawingType::Polygon { points, s, l }),
+            DrawingMode::FilledPolygon => Some(DrawingType::FilledPolygon { points, s, l }),
+            _ => None,
+        }
+    }
+
+    ///Complete the polyline or polygon being drawn, also when another tool has been chosen in the meantime.
+    ///A polygon needs three points, a polyline two, otherwise it is discarded.
+    pub fn end_path(&mut self) {
+        let drawing = self.path_drawing(None);
+        let required = if self.path_mode == DrawingMode::Polyline { 2 } else { 3 };
+
+        if self.path.len() >= required {
+            if let Some(drawing) = drawing {
+                tracing::info!("Added {:?}", drawing);
+                self.push(drawing);
+            }
+        }
+        self.path.clear();
+    }
+
+    ///Discard the polyline or polygon being drawn
+    pub fn cancel_path(&mut self) {
+        self.path.clear();
+    }
+
+    ///Start writing a text in the given position
+    pub fn start_text(&mut self, pos: Pos2) {
+        self.text_edit = Some(TextEdit { pos, text: String::new(), index: None, focus: true });
+    }
+
+    ///Update the text being written, replacing the previous version in the drawings. The text is added once it is not empty.
+    pub fn set_text(&mut self, text: String) {
+        let Some(edit) = &mut self.text_edit else {
+            return;
+        };
+        edit.text = text.clone();
+
+        let drawing = DrawingType::Text { p: edit.pos, t: text, s: self.tool.stroke(), o: self.tool.text };
+        match edit.index {
+            Some(index) => self.modify(index, drawing),
+            None if edit.text.is_empty() => {},
+            None => {
+                edit.index = Some(self.drawings.len());
+                self.execute_merging(Command::AddDrawing { index: self.drawings.len(), drawing });
+            },
+        }
+    }
+
+    ///Stop writing the text, keeping what has been written. A text left empty is removed.
+    pub fn end_text(&mut self) {
+        let Some(edit) = self.text_edit.take() else {
+            return;
+        };
+        self.end_edit();
+
+        if let (Some(index), true) = (edit.index, edit.text.trim().is_empty()) {
+            //The addition is forgotten when it is the last edit, so that it can not be redone
+            match self.history.done().last() {
+                Some(Command::AddDrawing { index: added, .. }) if *added == index => {
+                    self.undo();
+                    self.history.forget_redo();
+                },
+                _ => self.delete(index),
+            }
+        }
+    }
+}
+
+///Apply a command to the drawings, forward to do it and backward to undo it.
+///The step markers are numbered again, so that adding or removing one updates the following ones.
+fn apply(drawings: &mut Vec<DrawingType>, command: &Command, forward: bool) -> Option<ScreenChange> {
+    let change = match command {
+        Command::AddDrawing { index, drawing } | Command::DeleteDrawing { index, drawing } => {
+            let adding = matches!(command, Command::AddDrawing { .. }) == forward;
+            if adding {
+                drawings.insert((*index).min(drawings.len()), drawing.clone());
+            } else if *index < drawings.len() {
+                drawings.remove(*index);
+            }
+            None
+        },
+        Command::MoveDrawing { index, offset } => {
+            if let Some(drawing) = drawings.get_mut(*index) {
+                drawing.translate(if forward { *offset } else { -*offset });
+            }
+            None
+        },
+        Command::RestyleDrawing { index, before, after } => {
+            if let Some(drawing) = drawings.get_mut(*index) {
+                drawing.set_stroke(if forward { *after } else { *before });
+            }
+            None
+        },
+        Command::ReplaceDrawing { index, before, after } => {
+            if let Some(drawing) = drawings.get_mut(*index) {
+                *drawing = if forward { after.clone() } else { before.clone() };
+            }
+            None
+        },
+        Command::Crop { before, after } => Some(ScreenChange::Area(if forward { *after } else { *before })),
+        Command::ReplaceImage { before, after } => {
+            let state = if forward { after } else { before };
+            *drawings = state.drawings.clone();
+            Some(ScreenChange::Image { image: state.image.clone(), selected_area: state.selected_area })
+        },
+        Command::Erase { steps } => {
+            if forward {
+                steps.iter().for_each(|step| _ = apply(drawings, step, true));
+            } else {
+                steps.iter().rev().for_each(|step| _ = apply(drawings, step, false));
+            }
+            None
+        },
+    };
+    renumber_steps(drawings);
+    change
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, vec2};
+
+    use super::*;
+    use crate::painting::{smoothing::Smoothing, steps::{next_step, StepStyle}};
+
+    fn rect(x: f32) -> DrawingType {
+        DrawingType::Rectangle { r: Rect::from_min_size(pos2(x, 0.), vec2(10., 10.)), s: Stroke::new(1., Color32::RED), rounding: 0., l: Default::default() }
+    }
+
+    ///Left side of the rectangles in the document
+    fn positions(document: &AnnotationDocument) -> Vec<f32> {
+        document
+            .drawings()
+            .iter()
+            .map(|d| match d {
+                DrawingType::Rectangle { r, .. } => r.min.x,
+                d => panic!("Unexpected drawing {:?}", d),
+            })
+            .collect()
+    }
+
+    fn texts(document: &AnnotationDocument) -> Vec<String> {
+        document
+            .drawings()
+            .iter()
+            .filter_map(|d| match d {
+                DrawingType::Text { t, .. } => Some(t.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn push_undo_redo() {
+        let mut document = AnnotationDocument::new();
+        assert!(!document.can_undo());
+        document.push(rect(0.));
+        document.push(rect(20.));
+        assert_eq!(positions(&document), [0., 20.]);
+
+        assert!(matches!(document.undo(), HistoryStep::Drawings));
+        assert_eq!(positions(&document), [0.]);
+        assert!(document.can_redo());
+        assert!(matches!(document.redo(), HistoryStep::Drawings));
+        assert_eq!(positions(&document), [0., 20.]);
+
+        //A new drawing drops the undone ones
+        document.undo();
+        document.push(rect(40.));
+        assert!(!document.can_redo());
+        assert!(matches!(document.redo(), HistoryStep::Nothing));
+        assert_eq!(positions(&document), [0., 40.]);
+
+        document.undo();
+        document.undo();
+        assert!(matches!(document.undo(), HistoryStep::Nothing));
+        assert!(document.drawings().is_empty());
+    }
+
+    #[test]
+    fn crop_is_returned_to_the_caller() {
+        let mut document = AnnotationDocument::new();
+        let area = Rect::from_min_size(pos2(5., 5.), vec2(10., 10.));
+        document.record(Command::Crop { before: None, after: Some(area) });
+
+        assert!(matches!(document.undo(), HistoryStep::Screen(ScreenChange::Area(None))));
+        assert!(matches!(document.redo(), HistoryStep::Screen(ScreenChange::Area(Some(a))) if a == area));
+    }
+
+    #[test]
+    fn delete_is_undone_in_place() {
+        let mut document = AnnotationDocument::new();
+        for x in [0., 20., 40.] {
+            document.push(rect(x));
+        }
+        document.delete(1);
+        assert_eq!(positions(&document), [0., 40.]);
+        document.undo();
+        assert_eq!(positions(&document), [0., 20., 40.]);
+    }
+
+    #[test]
+    fn replace_keeps_the_redo_list() {
+        let mut document = AnnotationDocument::new();
+        document.push(rect(100.));
+        document.replace(vec![rect(0.)], vec![rect(20.), rect(40.)]);
+
+        assert_eq!(positions(&document), [0.]);
+        assert!(!document.can_undo());
+        assert_eq!(document.redo_drawings().len(), 2);
+
+        document.redo();
+        assert_eq!(positions(&document), [0., 20.]);
+        document.redo();
+        assert_eq!(positions(&document), [0., 20., 40.]);
+        assert!(matches!(document.redo(), HistoryStep::Nothing));
+        assert!(document.redo_drawings().is_empty());
+    }
+
+    #[test]
+    fn merged_moves_are_undone_together() {
+        let mut document = AnnotationDocument::new();
+        document.push(rect(0.));
+        document.push(rect(20.));
+
+        document.translate(0, vec2(5., 0.));
+        document.translate(0, vec2(5., 0.));
+        //Another drawing is a separate edit
+        document.translate(1, vec2(1., 0.));
+        document.end_edit();
+        document.translate(1, vec2(1., 0.));
+        assert_eq!(positions(&document), [10., 22.]);
+
+        document.undo();
+        assert_eq!(positions(&document), [10., 21.]);
+        document.undo();
+        assert_eq!(positions(&document), [10., 20.]);
+        document.undo();
+        assert_eq!(positions(&document), [0., 20.]);
+        document.redo();
+        assert_eq!(positions(&document), [10., 20.]);
+    }
+
+    #[test]
+    fn brush_stroke_is_a_single_edit() {
+        let mut document = AnnotationDocument::new();
+        //Every point is kept, the processing of the strokes is tested on its own
+        document.tool.brush = BrushOptions { smoothing: Smoothing::None, tolerance: 0. };
+        document.push(rect(0.));
+
+        document.brush_to(pos2(1., 1.));
+        document.brush_to(pos2(2., 5.));
+        assert!(document.stroke_in_progress());
+        assert!(!document.can_undo());
+        assert!(matches!(document.undo(), HistoryStep::Nothing));
+        assert_eq!(document.drawings().len(), 2);
+
+        document.end_brush(Some(pos2(3., 9.)));
+        assert!(!document.stroke_in_progress());
+        document.undo();
+        assert_eq!(positions(&document), [0.]);
+        document.redo();
+        assert!(matches!(document.drawings().last(), Some(DrawingType::Brush { points, end: true, .. }) if points.len() == 3));
+    }
+
+    #[test]
+    fn drag_keeps_its_origin() {
+        let mut document = AnnotationDocument::new();
+        assert_eq!(document.drag_origin(|| pos2(1., 2.)), pos2(1., 2.));
+        assert_eq!(document.drag_origin(|| pos2(9., 9.)), pos2(1., 2.));
+        assert_eq!(document.end_drag(), Some(pos2(1., 2.)));
+        assert_eq!(document.end_drag(), None);
+
+        document.tool.mode = DrawingMode::Circle;
+        match document.drag_drawing(pos2(0., 0.), pos2(10., 0.)) {
+            Some(DrawingType::Circle { c, r, .. }) => assert_eq!((c, r), (pos2(5., 0.), 5.)),
+            d => panic!("Unexpected drawing {:?}", d),
+        }
+        document.tool.mode = DrawingMode::Brush;
+        assert!(document.drag_drawing(pos2(0., 0.), pos2(10., 0.)).is_none());
+    }
+
+    #[test]
+    fn step_markers_are_renumbered() {
+        let mut document = AnnotationDocument::new();
+        for x in [0., 20., 40.] {
+            let n = next_step(document.drawings());
+            document.push(DrawingType::StepMarker { c: pos2(x, 0.), r: 10., n, style: StepStyle::Number, s: Stroke::new(1., Color32::RED) });
+        }
+        let labels = |document: &AnnotationDocument| -> Vec<(f32, String)> {
+            document
+                .drawings()
+                .iter()
+                .map(|d| match d {
+                    DrawingType::StepMarker { c, n, style, .. } => (c.x, style.label(*n)),
+                    d => panic!("Unexpected drawing {:?}", d),
+                })
+                .collect()
+        };
+        assert_eq!(labels(&document), [(0., "1".to_string()), (20., "2".to_string()), (40., "3".to_string())]);
+
+        //The following markers move up to fill the gap
+        document.delete(1);
+        assert_eq!(labels(&document), [(0., "1".to_string()), (40., "2".to_string())]);
+
+        document.undo();
+        assert_eq!(labels(&document), [(0., "1".to_string()), (20., "2".to_string()), (40., "3".to_string())]);
+    }
+
+    #[test]
+    fn typed_text_is_a_single_edit() {
+        let mut document = AnnotationDocument::new();
+        document.push(rect(0.));
+
+        document.start_text(pos2(5., 5.));
+        for text in ["H", "He", "Hello"] {
+            document.set_text(text.to_string());
+        }
+        document.end_text();
+        assert_eq!(texts(&document), ["Hello"]);
+
+        document.undo();
+        assert!(texts(&document).is_empty());
+        assert_eq!(document.drawings().len(), 1);
+        document.redo();
+        assert_eq!(texts(&document), ["Hello"]);
+    }
+}
@@ -1,94 +1,235 @@
-use std::collections::VecDeque;
+use std::collections::HashSet;
 
-use egui::{Context, Pos2, Stroke, Rect, Vec2, Rgba, Color32, Layout, Align, Button, Id, color_picker::{color_edit_button_rgba, Alpha}, DragValue, Ui, LayerId, Order, pos2, Align2, FontId, Widget, Window, Painter, CursorIcon, RichText, TextStyle};
+use egui::{emath::RectTransform, epaint::text::Fonts, Shape, Context, Pos2, Stroke, Rect, Vec2, Color32, Layout, Align, Button, Id, color_picker::{color_edit_button_rgba, Alpha}, DragValue, Ui, LayerId, Order, pos2, Widget, Painter, CursorIcon, RichText, TextStyle};
 use egui_extras::RetainedImage;
-use native_dialog::FileDialog;
 use serde::{Serialize, Deserialize};
-use crate::krustygrab::{self, KrustyGrab };
-use crate::painting::icons::{icon_img, ICON_SIZE};
-
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-enum DrawingMode {
-    Brush,
-    Highlighter, 
-    Rectangle,
-    FilledRectangle,
-    Circle,
-    FilledCircle, 
-    Arrow,
-    Text, // BUGGED
-}
+use crate::krustygrab::{Format, KrustyGrab, KrustyGrabConfig};
+use crate::painting::{arrow::{arrow_shapes, ArrowOptions}, document::DrawingMode, eraser::EraserMode, icons::{icon_img, ICON_SIZE}, path::filled_polygon, redaction::Redaction, style::{Dash, LineStyle}, shapes::{ellipse_filled, ellipse_stroke}, highlight::{highlight_color, MARKER_WIDTH}, smoothing::stroke_shapes, steps::{step_shapes, StepStyle}, text::TextOptions};
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum DrawingType {
-    Brush {points: Vec<Pos2>, s: Stroke, end: bool},
-    Rectangle {r: Rect, s: Stroke},
-    FilledRectangle {r: Rect, s: Stroke},
-    Highlighter {r: Rect, s: Stroke},
-    Circle {c: Pos2, r: f32, s: Stroke},
-    FilledCircle {c: Pos2, r: f32, s: Stroke},
-    Arrow {p: Pos2, v: Vec2, s: Stroke},
-    Text {p: Pos2, t: String, s: Stroke}, // BUGGED
+    Brush {points: Vec<Pos2>, s: Stroke, end: bool, #[serde(default)] l: LineStyle},
+    ///Rectangle with the corners rounded with the given radius
+    Rectangle {r: Rect, s: Stroke, #[serde(default)] rounding: f32, #[serde(default)] l: LineStyle},
+    FilledRectangle {r: Rect, s: Stroke, #[serde(default)] rounding: f32, #[serde(default)] l: LineStyle},
+    Highlighter {r: Rect, s: Stroke, #[serde(default)] l: LineStyle},
+    ///Freehand highlight, as wide as [`MARKER_WIDTH`] times the thickness
+    Marker {points: Vec<Pos2>, s: Stroke, end: bool, #[serde(default)] l: LineStyle},
+    Circle {c: Pos2, r: f32, s: Stroke, #[serde(default)] l: LineStyle},
+    FilledCircle {c: Pos2, r: f32, s: Stroke, #[serde(default)] l: LineStyle},
+    ///Ellipse inscribed in the rectangle
+    Ellipse {r: Rect, s: Stroke, #[serde(default)] l: LineStyle},
+    FilledEllipse {r: Rect, s: Stroke, #[serde(default)] l: LineStyle},
+    ///Arrow from `p` along `v`, curved towards the control point `c` if there is one
+    Arrow {p: Pos2, v: Vec2, s: Stroke, #[serde(default)] o: ArrowOptions, #[serde(default)] c: Option<Pos2>, #[serde(default)] l: LineStyle},
+    Line {points: [Pos2; 2], s: Stroke, #[serde(default)] l: LineStyle},
+    Polyline {points: Vec<Pos2>, s: Stroke, #[serde(default)] l: LineStyle},
+    Polygon {points: Vec<Pos2>, s: Stroke, #[serde(default)] l: LineStyle},
+    FilledPolygon {points: Vec<Pos2>, s: Stroke, #[serde(default)] l: LineStyle},
+    ///Text with its position, as given by the alignment. The thickness of the stroke is used for the outline box.
+    Text {p: Pos2, t: String, s: Stroke, #[serde(default)] o: TextOptions},
+    ///Area whose pixels are replaced, with the thickness of the stroke as strength
+    Pixelate {r: Rect, s: Stroke},
+    Blur {r: Rect, s: Stroke},
+    ///Numbered badge. The number follows the order of the markers in the drawings and is kept updated by the document.
+    StepMarker {c: Pos2, r: f32, n: usize, style: StepStyle, s: Stroke},
 }
 
-#[derive(Clone)]
-pub struct RedoList {
-    drawings: VecDeque<DrawingType>,
-    capacity: usize,
-}
+impl DrawingType {
+    ///Build the shapes representing the drawing. The transform maps the drawing coordinates on the painting area,
+    ///so the same shapes are used for the canva, the select mode and the exported image.
+    pub fn to_shapes(&self, transform: &RectTransform, fonts: &Fonts) -> Vec<Shape> {
+        let scale = transform.scale().x;
+        let scaled = |s: &Stroke| Stroke::new(s.width * scale, s.color);
+
+        let shapes = match self {
+            DrawingType::Brush { points, s, l, .. } => {
+                let points = points.iter().map(|p| transform.transform_pos(*p)).collect();
+                //The round corners are only added to the solid strokes, the patterns have their own ends
+                if l.dash == Dash::Solid && l.opacity >= 1. { stroke_shapes(points, scaled(s)) } else { vec![Shape::line(points, scaled(s))] }
+            },
+            DrawingType::Highlighter { r, s, .. } => vec![Shape::rect_filled(transform.transform_rect(*r), 0.0, highlight_color(s.color))],
+            DrawingType::Marker { points, s, .. } => {
+                let stroke = Stroke::new(s.width * MARKER_WIDTH * scale, highlight_color(s.color));
+                vec![Shape::line(points.iter().map(|p| transform.transform_pos(*p)).collect(), stroke)]
+            },
+            DrawingType::Rectangle { r, s, rounding, .. } => vec![Shape::rect_stroke(transform.transform_rect(*r), rounding * scale, scaled(s))],
+            DrawingType::FilledRectangle { r, s, rounding, .. } => vec![Shape::rect_filled(transform.transform_rect(*r), rounding * scale, s.color)],
+            DrawingType::Circle { c, r, s, .. } => vec![Shape::circle_stroke(transform.transform_pos(*c), r * scale, scaled(s))],
+            DrawingType::FilledCircle { c, r, s, .. } => vec![Shape::circle_filled(transform.transform_pos(*c), r * scale, s.color)],
+            DrawingType::Ellipse { r, s, .. } => vec![ellipse_stroke(transform.transform_rect(*r), scaled(s))],
+            DrawingType::FilledEllipse { r, s, .. } => vec![ellipse_filled(transform.transform_rect(*r), s.color)],
+            DrawingType::Arrow { p, v, s, o, c, .. } => arrow_shapes(*p, *v, *c, scaled(s), o, transform),
+            DrawingType::Line { points, s, .. } => vec![Shape::line_segment(points.map(|p| transform.transform_pos(p)), scaled(s))],
+            DrawingType::Polyline { points, s, .. } => vec![Shape::line(points.iter().map(|p| transform.transform_pos(*p)).collect(), scaled(s))],
+            DrawingType::Polygon { points, s, .. } => vec![Shape::closed_line(points.iter().map(|p| transform.transform_pos(*p)).collect(), scaled(s))],
+            DrawingType::FilledPolygon { points, s, .. } => vec![filled_polygon(points.iter().map(|p| transform.transform_pos(*p)).collect(), s.color)],
+            //Font resized according to the dimension of the painting area
+            DrawingType::Text { p, t, s, o } => o.shapes(t, s, transform.transform_pos(*p), scale, fonts),
+            //The redactions change the pixels of the image, so they have no shape
+            DrawingType::Pixelate { .. } | DrawingType::Blur { .. } => Vec::new(),
+            DrawingType::StepMarker { c, r, n, style, s } => step_shapes(transform.transform_pos(*c), r * scale, style.label(*n), s, fonts),
+        };
 
-#[allow(unused)]
-impl RedoList {
-    pub fn new(capacity: usize) -> Self {
-        RedoList { drawings: VecDeque::<DrawingType>::with_capacity(capacity), capacity }
+        let Some(l) = self.line_style() else {
+            return shapes;
+        };
+        //The heads of the arrows stay solid, only their body follows the pattern
+        let dashed = if let DrawingType::Arrow { .. } = self { 1 } else { shapes.len() };
+        shapes
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, shape)| if i < dashed { l.dash(shape, scale) } else { vec![shape] })
+            .map(|shape| l.fade(shape))
+            .collect()
     }
 
-    pub fn push(&mut self, d: DrawingType) {
-        if self.drawings.len() >= self.capacity {
-           self.drawings.pop_front(); 
+    ///Kind, area and strength of the redactions
+    pub fn redaction(&self) -> Option<(Redaction, Rect, f32)> {
+        match self {
+            DrawingType::Pixelate { r, s } => Some((Redaction::Pixelate, *r, s.width)),
+            DrawingType::Blur { r, s } => Some((Redaction::Blur, *r, s.width)),
+            _ => None,
         }
-        self.drawings.push_back(d);
     }
 
-    pub fn pop(&mut self) -> Option<DrawingType> {
-        self.drawings.pop_back()
+    ///Name of the kind of drawing, shown to the user
+    pub fn name(&self) -> &'static str {
+        match self {
+            DrawingType::Brush { .. } => "brush stroke",
+            DrawingType::Rectangle { .. } => "rectangle",
+            DrawingType::FilledRectangle { .. } => "filled rectangle",
+            DrawingType::Highlighter { .. } => "highlight",
+            DrawingType::Marker { .. } => "marker stroke",
+            DrawingType::Circle { .. } => "circle",
+            DrawingType::FilledCircle { .. } => "filled circle",
+            DrawingType::Ellipse { .. } => "ellipse",
+            DrawingType::FilledEllipse { .. } => "filled ellipse",
+            DrawingType::Arrow { .. } => "arrow",
+            DrawingType::Line { .. } => "line",
+            DrawingType::Polyline { .. } => "polyline",
+            DrawingType::Polygon { .. } => "polygon",
+            DrawingType::FilledPolygon { .. } => "filled polygon",
+            DrawingType::Text { .. } => "text",
+            DrawingType::Pixelate { .. } => "pixelated area",
+            DrawingType::Blur { .. } => "blurred area",
+            DrawingType::StepMarker { .. } => "step marker",
+        }
     }
 
-    pub fn capacity(&self) -> usize { 
-        self.capacity
+    ///Approximate memory used by the drawing, in bytes
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + match self {
+                DrawingType::Brush { points, .. }
+                | DrawingType::Marker { points, .. }
+                | DrawingType::Polyline { points, .. }
+                | DrawingType::Polygon { points, .. }
+                | DrawingType::FilledPolygon { points, .. } => points.len() * std::mem::size_of::<Pos2>(),
+                DrawingType::Text { t, .. } => t.len(),
+                _ => 0,
+            }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.drawings.is_empty()
+    pub fn stroke(&self) -> Stroke {
+        match self {
+            DrawingType::Brush { s, .. }
+            | DrawingType::Rectangle { s, .. }
+            | DrawingType::FilledRectangle { s, .. }
+            | DrawingType::Highlighter { s, .. }
+            | DrawingType::Marker { s, .. }
+            | DrawingType::Circle { s, .. }
+            | DrawingType::FilledCircle { s, .. }
+            | DrawingType::Ellipse { s, .. }
+            | DrawingType::FilledEllipse { s, .. }
+            | DrawingType::Arrow { s, .. }
+            | DrawingType::Line { s, .. }
+            | DrawingType::Polyline { s, .. }
+            | DrawingType::Polygon { s, .. }
+            | DrawingType::FilledPolygon { s, .. }
+            | DrawingType::Text { s, .. }
+            | DrawingType::Pixelate { s, .. }
+            | DrawingType::Blur { s, .. }
+            | DrawingType::StepMarker { s, .. } => *s,
+        }
     }
-}
 
+    pub fn set_stroke(&mut self, stroke: Stroke) {
+        match self {
+            DrawingType::Brush { s, .. }
+            | DrawingType::Rectangle { s, .. }
+            | DrawingType::FilledRectangle { s, .. }
+            | DrawingType::Highlighter { s, .. }
+            | DrawingType::Marker { s, .. }
+            | DrawingType::Circle { s, .. }
+            | DrawingType::FilledCircle { s, .. }
+            | DrawingType::Ellipse { s, .. }
+            | DrawingType::FilledEllipse { s, .. }
+            | DrawingType::Arrow { s, .. }
+            | DrawingType::Line { s, .. }
+            | DrawingType::Polyline { s, .. }
+            | DrawingType::Polygon { s, .. }
+            | DrawingType::FilledPolygon { s, .. }
+            | DrawingType::Text { s, .. }
+            | DrawingType::Pixelate { s, .. }
+            | DrawingType::Blur { s, .. }
+            | DrawingType::StepMarker { s, .. } => *s = stroke,
+        }
+    }
+
+    ///Move the drawing by the given offset
+    pub fn translate(&mut self, offset: Vec2) {
+        match self {
+            DrawingType::Brush { points, .. }
+            | DrawingType::Marker { points, .. }
+            | DrawingType::Polyline { points, .. }
+            | DrawingType::Polygon { points, .. }
+            | DrawingType::FilledPolygon { points, .. } => points.iter_mut().for_each(|p| *p += offset),
+            DrawingType::Line { points, .. } => points.iter_mut().for_each(|p| *p += offset),
+            DrawingType::Rectangle { r, .. }
+            | DrawingType::FilledRectangle { r, .. }
+            | DrawingType::Highlighter { r, .. }
+            | DrawingType::Ellipse { r, .. }
+            | DrawingType::FilledEllipse { r, .. }
+            | DrawingType::Pixelate { r, .. }
+            | DrawingType::Blur { r, .. } => *r = r.translate(offset),
+            DrawingType::Circle { c, .. } | DrawingType::FilledCircle { c, .. } | DrawingType::StepMarker { c, .. } => *c += offset,
+            DrawingType::Arrow { p, c, .. } => {
+                *p += offset;
+                if let Some(c) = c {
+                    *c += offset;
+                }
+            },
+            DrawingType::Text { p, .. } => *p += offset,
+        }
+    }
+}
 
 impl KrustyGrab {
-    pub const REDO_LIST_SIZE: usize = 10;
     pub const BASE_TEXT_SIZE: f32 = 30.0;
-    pub const HIGHLIGTHER_FACTOR: u8 = 70;
 
     // Render the part of head toolbar for the drawing 
-    pub fn render_drawing_toolbar(&mut self, ctx: &Context, ui: &mut Ui, frame: &mut eframe::Frame) {
-        //setting color and thickness
-        let mut color = match ctx.memory(|mem| mem.data.get_temp::<Rgba>(Id::from("Color"))){
-            Some(c) => c,
-            None => Rgba::from(Color32::GREEN)
-        };
-
-        let mut thickness: f32 = match ctx.memory(|mem| mem.data.get_temp(Id::from("Thickness"))) {
-            Some(t) => t,
-            None => 1.0,
-        };
-        
-        //setting drawing mode
-        let drawing_mode = match ctx.memory_mut(|mem| mem.data.get_temp(Id::from("DrawingMode"))) {
-            Some(dm) => dm,
-            None => DrawingMode::Brush,    
-        };
+    pub fn render_drawing_toolbar(&mut self, ctx: &Context, ui: &mut Ui) {
+        let drawing_mode = self.document.tool.mode;
 
         ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+            //Select button
+            let mut select_button = Button::image_and_text(icon_img("pointer", ctx), ICON_SIZE, "")
+                .stroke(Stroke::new(1.0,
+                Color32::from_rgb(128, 106, 0)))
+                .ui(ui)
+                .on_hover_cursor(CursorIcon::PointingHand)
+                .on_hover_text_at_pointer("Select (Delete removes the selected drawing)");
+
+            if drawing_mode == DrawingMode::Select {
+                select_button = select_button.highlight();
+            }
+
+            if select_button.clicked() {
+                self.document.tool.mode = DrawingMode::Select;
+                tracing::info!("Select selected");
+            }
+
             //Brush button
             let mut brush_button = Button::image_and_text(icon_img("pencil", ctx), ICON_SIZE, "")
                 .stroke(Stroke::new(1.0,
@@ -102,39 +243,49 @@ impl KrustyGrab {
             }
 
             if brush_button.clicked() {
-                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("DrawingMode"), DrawingMode::Brush));
+                self.document.tool.mode = DrawingMode::Brush;
                 tracing::info!("Pencil selected");
             }
             
-            // Highlighter button
-            let mut highlighter_button = Button::image_and_text(icon_img("highlighter", ctx), ICON_SIZE, "")
-            .stroke(Stroke::new(1.0,
-            Color32::from_rgb(128, 106, 0)))
-            .ui(ui)
-            .on_hover_cursor(CursorIcon::PointingHand)
-            .on_hover_text_at_pointer("Highlighter");
-
-            if drawing_mode == DrawingMode::Highlighter {
-                highlighter_button = highlighter_button.highlight();
-            }
+            //[Highlighter|Text lines|Marker] button
+            let name_icon = if drawing_mode == DrawingMode::Marker { "marker" } else { "highlighter" };
+            let highlighter_button = ui.menu_image_button(icon_img(name_icon, ctx), ICON_SIZE, |ui| {
+                for (mode, snap, label) in [
+                    (DrawingMode::Highlighter, false, "Highlight area"),
+                    (DrawingMode::Highlighter, true, "Highlight text lines"),
+                    (DrawingMode::Marker, false, "Marker"),
+                ] {
+                    if ui
+                        .button(RichText::new(label).text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.document.tool.mode = mode;
+                        self.document.tool.highlight_snap = snap;
+                        tracing::info!("{} selected", label);
+                        ui.close_menu();
+                    }
+                }
+            }).response.on_hover_cursor(CursorIcon::PointingHand).on_hover_text_at_pointer("Highlighter");
 
-            if highlighter_button.clicked() {
-                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("DrawingMode"), DrawingMode::Highlighter));
-                tracing::info!("Highlighter selected");
+            if drawing_mode == DrawingMode::Highlighter || drawing_mode == DrawingMode::Marker {
+                highlighter_button.highlight();
             }
                         
-            //[Circle|Filled Circle] button 
-            let mut name_icon =  "circle";
-            if drawing_mode == DrawingMode::FilledCircle {
-                name_icon = "circle_full";
-            }
+            //[Circle|Filled Circle|Ellipse|Filled Ellipse] button 
+            let name_icon = match drawing_mode {
+                DrawingMode::FilledCircle => "circle_full",
+                DrawingMode::Ellipse => "ellipse",
+                DrawingMode::FilledEllipse => "ellipse_full",
+                _ => "circle",
+            };
             let circle_button = ui.menu_image_button(icon_img(name_icon, ctx), ICON_SIZE, |ui| {
                 if ui
                     .button(RichText::new("Circle").text_style(TextStyle::Body))
                     .on_hover_cursor(CursorIcon::PointingHand)
                     .clicked()
                 {
-                    ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("DrawingMode"), DrawingMode::Circle));
+                    self.document.tool.mode = DrawingMode::Circle;
                     tracing::info!("Circle selected");
                     ui.close_menu();
                 }
@@ -143,17 +294,35 @@ impl KrustyGrab {
                     .on_hover_cursor(CursorIcon::PointingHand)
                     .clicked()
                 {
-                    ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("DrawingMode"), DrawingMode::FilledCircle));
+                    self.document.tool.mode = DrawingMode::FilledCircle;
                     tracing::info!("Circle Filled selected");
                     ui.close_menu();
                 }
+                if ui
+                    .button(RichText::new("Ellipse").text_style(TextStyle::Body))
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .clicked()
+                {
+                    self.document.tool.mode = DrawingMode::Ellipse;
+                    tracing::info!("Ellipse selected");
+                    ui.close_menu();
+                }
+                if ui
+                    .button(RichText::new("Ellipse Filled").text_style(TextStyle::Body))
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .clicked()
+                {
+                    self.document.tool.mode = DrawingMode::FilledEllipse;
+                    tracing::info!("Ellipse Filled selected");
+                    ui.close_menu();
+                }
             
             }).response
             .on_hover_cursor(CursorIcon::PointingHand)
-            .on_hover_text_at_pointer("Circles");
+            .on_hover_text_at_pointer("Circles and ellipses (Shift draws a circle, Alt draws from the center)");
 
 
-            if drawing_mode == DrawingMode::Circle || drawing_mode == DrawingMode::FilledCircle {
+            if matches!(drawing_mode, DrawingMode::Circle | DrawingMode::FilledCircle | DrawingMode::Ellipse | DrawingMode::FilledEllipse) {
                 circle_button.highlight();
             }
             
@@ -169,7 +338,7 @@ impl KrustyGrab {
                     .on_hover_cursor(CursorIcon::PointingHand)
                     .clicked()
                 {
-                    ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("DrawingMode"), DrawingMode::Rectangle));
+                    self.document.tool.mode = DrawingMode::Rectangle;
                     tracing::info!("Rectangle selected");
                     ui.close_menu();
                 }
@@ -178,17 +347,44 @@ impl KrustyGrab {
                     .on_hover_cursor(CursorIcon::PointingHand)
                     .clicked()
                 {
-                    ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("DrawingMode"), DrawingMode::FilledRectangle));
+                    self.document.tool.mode = DrawingMode::FilledRectangle;
                     tracing::info!("Rectangle Filled selected");
                     ui.close_menu();
                 }
 
-            }).response.on_hover_cursor(CursorIcon::PointingHand).on_hover_text_at_pointer("Rectangles");
+            }).response.on_hover_cursor(CursorIcon::PointingHand).on_hover_text_at_pointer("Rectangles (Shift draws a square, Alt draws from the center)");
 
             if drawing_mode == DrawingMode::Rectangle || drawing_mode == DrawingMode::FilledRectangle {
                 rectangle_button.highlight();
             }
 
+            //[Pixelate|Blur] button
+            let redact_button = ui.menu_image_button(icon_img("redact", ctx), ICON_SIZE, |ui| {
+                if ui
+                    .button(RichText::new("Pixelate").text_style(TextStyle::Body))
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .clicked()
+                {
+                    self.document.tool.mode = DrawingMode::Pixelate;
+                    tracing::info!("Pixelate selected");
+                    ui.close_menu();
+                }
+                if ui
+                    .button(RichText::new("Blur").text_style(TextStyle::Body))
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .clicked()
+                {
+                    self.document.tool.mode = DrawingMode::Blur;
+                    tracing::info!("Blur selected");
+                    ui.close_menu();
+                }
+
+            }).response.on_hover_cursor(CursorIcon::PointingHand).on_hover_text_at_pointer("Redact (the thickness sets the strength)");
+
+            if drawing_mode == DrawingMode::Pixelate || drawing_mode == DrawingMode::Blur {
+                redact_button.highlight();
+            }
+
             //Arrow button
             let mut arrow_button = Button::image_and_text(icon_img("arrow", ctx), ICON_SIZE, "")
                 .stroke(Stroke::new(1.0,
@@ -202,43 +398,161 @@ impl KrustyGrab {
             }
 
             if arrow_button.clicked() {
-                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("DrawingMode"), DrawingMode::Arrow));
+                self.document.tool.mode = DrawingMode::Arrow;
                 tracing::info!("Arrow selected");
             }
 
-            //Text button - TODO bugged ATM
-            // let mut text_button = Button::image_and_text(icon_img("text", ctx), ICON_SIZE, "")
-            //     .stroke(Stroke::new(1.0,
-            //     Color32::from_rgb(128, 106, 0)))
-            //     .ui(ui)
-            //     .on_hover_cursor(CursorIcon::PointingHand)
-            //     .on_hover_text_at_pointer("Text");
+            //Line button
+            let mut line_button = Button::image_and_text(icon_img("line", ctx), ICON_SIZE, "")
+                .stroke(Stroke::new(1.0,
+                Color32::from_rgb(128, 106, 0)))
+                .ui(ui)
+                .on_hover_cursor(CursorIcon::PointingHand)
+                .on_hover_text_at_pointer("Line (Shift snaps the angle)");
+
+            if drawing_mode == DrawingMode::Line {
+                line_button = line_button.highlight();
+            }
+
+            if line_button.clicked() {
+                self.document.tool.mode = DrawingMode::Line;
+                tracing::info!("Line selected");
+            }
 
-            // if drawing_mode == DrawingMode::Text {
-            //     text_button = text_button.highlight();
-            // }
+            //[Polyline|Polygon|Filled Polygon] button
+            let name_icon = match drawing_mode {
+                DrawingMode::Polygon => "polygon",
+                DrawingMode::FilledPolygon => "polygon_full",
+                _ => "polyline",
+            };
 
-            // if text_button.clicked() {
-            //     ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("DrawingMode"), DrawingMode::Text));
-            //     tracing::info!("Text selected");
-            // }
+            let path_button = ui.menu_image_button(icon_img(name_icon, ctx), ICON_SIZE, |ui| {
+                if ui
+                    .button(RichText::new("Polyline").text_style(TextStyle::Body))
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .clicked()
+                {
+                    self.document.tool.mode = DrawingMode::Polyline;
+                    tracing::info!("Polyline selected");
+                    ui.close_menu();
+                }
+                if ui
+                    .button(RichText::new("Polygon").text_style(TextStyle::Body))
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .clicked()
+                {
+                    self.document.tool.mode = DrawingMode::Polygon;
+                    tracing::info!("Polygon selected");
+                    ui.close_menu();
+                }
+                if ui
+                    .button(RichText::new("Polygon Filled").text_style(TextStyle::Body))
+                    .on_hover_cursor(CursorIcon::PointingHand)
+                    .clicked()
+                {
+                    self.document.tool.mode = DrawingMode::FilledPolygon;
+                    tracing::info!("Polygon Filled selected");
+                    ui.close_menu();
+                }
+
+            }).response.on_hover_cursor(CursorIcon::PointingHand)
+            .on_hover_text_at_pointer("Polylines and polygons (click to add points, double click or Enter to finish, Shift snaps the angle)");
+
+            if matches!(drawing_mode, DrawingMode::Polyline | DrawingMode::Polygon | DrawingMode::FilledPolygon) {
+                path_button.highlight();
+            }
+
+            //Text button
+            let mut text_button = Button::image_and_text(icon_img("text", ctx), ICON_SIZE, "")
+                .stroke(Stroke::new(1.0,
+                Color32::from_rgb(128, 106, 0)))
+                .ui(ui)
+                .on_hover_cursor(CursorIcon::PointingHand)
+                .on_hover_text_at_pointer("Text");
+
+            if drawing_mode == DrawingMode::Text {
+                text_button = text_button.highlight();
+            }
+
+            if text_button.clicked() {
+                self.document.tool.mode = DrawingMode::Text;
+                tracing::info!("Text selected");
+            }
+
+            //Step marker button, with the style of the labels
+            let step_button = ui.menu_image_button(icon_img("step", ctx), ICON_SIZE, |ui| {
+                for style in StepStyle::ALL {
+                    if ui
+                        .button(RichText::new(style.name()).text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.document.tool.mode = DrawingMode::StepMarker;
+                        self.document.tool.step_style = style;
+                        tracing::info!("Step marker selected with style {:?}", style);
+                        ui.close_menu();
+                    }
+                }
+            }).response.on_hover_cursor(CursorIcon::PointingHand).on_hover_text_at_pointer("Step markers");
+
+            if drawing_mode == DrawingMode::StepMarker {
+                step_button.highlight();
+            }
+
+            //Eraser button, removing whole drawings or parts of the brush strokes
+            let eraser_button = ui.menu_image_button(icon_img("eraser", ctx), ICON_SIZE, |ui| {
+                for (mode, label) in [(EraserMode::Object, "Erase drawings"), (EraserMode::Stroke, "Erase strokes")] {
+                    if ui
+                        .button(RichText::new(label).text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.document.tool.mode = DrawingMode::Eraser;
+                        self.document.tool.eraser = mode;
+                        tracing::info!("Eraser selected in mode {:?}", mode);
+                        ui.close_menu();
+                    }
+                }
+            }).response.on_hover_cursor(CursorIcon::PointingHand).on_hover_text_at_pointer("Eraser");
+
+            if drawing_mode == DrawingMode::Eraser {
+                eraser_button.highlight();
+            }
+
+            //The style of the selected drawing is shown and changed instead of the tool one
+            let (mut color, mut thickness) = self.shown_style();
 
             //Color picker rendering
             let color_picker = color_edit_button_rgba(ui, &mut color, Alpha::BlendOrAdditive)
                                             .on_hover_cursor(CursorIcon::PointingHand)
                                             .on_hover_text_at_pointer("Change color");
                         
-            if ctx.memory(|mem| mem.any_popup_open()) {
-                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("CP_open"), true));
-            }
-            else {
-                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("CP_open"), false));
-            }
+            self.document.canvas.color_picker_open = ctx.memory(|mem| mem.any_popup_open());
             if color_picker.changed() {
-                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("Color"), color));
                 tracing::info!("Color changed to {:?}", color);
+                self.set_style(color, thickness);
             }
 
+            //Eyedropper button, picking the color from the screenshot
+            let mut eyedropper_button = Button::image_and_text(icon_img("eyedropper", ctx), ICON_SIZE, "")
+            .stroke(Stroke::new(1.0,
+            Color32::from_rgb(128, 106, 0)))
+            .ui(ui)
+            .on_hover_cursor(CursorIcon::PointingHand)
+            .on_hover_text_at_pointer("Pick a color from the screenshot, right click to copy its value");
+
+            if drawing_mode == DrawingMode::Eyedropper {
+                eyedropper_button = eyedropper_button.highlight();
+            }
+
+            if eyedropper_button.clicked() {
+                self.document.tool.mode = DrawingMode::Eyedropper;
+                tracing::info!("Eyedropper selected");
+            }
+
+            //Palette and recent colors
+            self.palette_options(ui);
+
             //Thickness of the tools
             if DragValue::new(&mut thickness)
                 .prefix("Thickness: ")
@@ -247,89 +561,48 @@ impl KrustyGrab {
                 .ui(ui)
                 .on_hover_text_at_pointer("Change thickness")
                 .changed() {
-                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("Thickness"), thickness));
                 tracing::info!("Thickness changed to {:?}", thickness);
+                self.set_style(color, thickness);
             }
 
-            //Undo button
-            let render_undo = ctx.memory(|mem| {
-                match mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing")) {
-                    Some(d) => !d.is_empty(),
-                    None => false,
-                }
-            });
+            self.line_style_options(ui);
+            self.arrow_options(ui);
+            self.brush_options(ui);
+
+            //Corner radius of the rectangles
+            if drawing_mode == DrawingMode::Rectangle || drawing_mode == DrawingMode::FilledRectangle {
+                DragValue::new(&mut self.document.tool.rounding)
+                    .prefix("Corners: ")
+                    .speed(0.5)
+                    .clamp_range(0.0..=100.0)
+                    .ui(ui)
+                    .on_hover_text_at_pointer("Change the radius of the corners");
+            }
 
-            if ui.add_enabled(render_undo, 
+            //Undo button
+            if ui.add_enabled(self.document.can_undo(), 
                 Button::image_and_text(icon_img("undo", ctx), ICON_SIZE, "")
                     .stroke(Stroke::new(1.0, Color32::from_rgb(128, 106, 0)))
             )
                 .on_hover_cursor(CursorIcon::PointingHand)
                 .on_hover_text_at_pointer("Undo")
-                .on_disabled_hover_text("No more drawings to undo")
+                .on_disabled_hover_text("No more edits to undo")
                 .clicked() {
                     tracing::info!("Undo selected");
-                    ctx.memory_mut(|mem| {
-                        match mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing")) {
-                            Some(mut drawings) => {
-                                let last = drawings.pop().expect("Drawings list should contains at least one element at this point");
-
-                                //Retrieve and update Redo list
-                                let redo_list = match mem.data.get_temp::<RedoList>(Id::from("Redo_list")){
-                                    Some(mut redo) => {
-                                        redo.push(last);
-                                        redo
-                                    },
-                                    None => {
-                                        let mut redo = RedoList::new(KrustyGrab::REDO_LIST_SIZE);
-                                        redo.push(last);
-                                        redo
-                                    },
-                                };
-                                
-                                mem.data.insert_temp(Id::from("Redo_list"), redo_list);
-                                mem.data.insert_temp(Id::from("Drawing"), drawings);
-                            },
-                            None => {},
-                        };
-                    });
+                    self.undo();
                 }
 
             //Redo button
-            let render_redo = ctx.memory(|mem| {
-                match mem.data.get_temp::<RedoList>(Id::from("Redo_list")) {
-                    Some(d) => !d.is_empty(),
-                    None => false,
-                }
-            });
-
-            if ui.add_enabled(render_redo, 
+            if ui.add_enabled(self.document.can_redo(), 
                 Button::image_and_text(icon_img("redo", ctx), ICON_SIZE, "")
                 .stroke(Stroke::new(1.0, Color32::from_rgb(128, 106, 0)))
             )
                 .on_hover_cursor(CursorIcon::PointingHand)
                 .on_hover_text_at_pointer("Redo")
-                .on_disabled_hover_text("No more drawings to redo")
+                .on_disabled_hover_text("No more edits to redo")
                 .clicked() {
                     tracing::info!("Redo selected");
-                    ctx.memory_mut(|mem| {
-                        match mem.data.get_temp::<RedoList>(Id::from("Redo_list")) {
-                            Some(mut redo) => {            
-                                let last = redo.pop().expect("Redo list should contains at least one element at this point");
-
-                                //Retrieve and update drawings list
-                                match mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing")) {
-                                    Some(mut d) => {
-                                        d.push(last);
-                                        mem.data.insert_temp(Id::from("Drawing"), d);
-                                    },
-                                    None => panic!("Drawings list should exists in memory at this point"),
-                                }
-                                
-                                mem.data.insert_temp(Id::from("Redo_list"), redo);
-                            },
-                            None => {},
-                        };
-                    });
+                    self.redo();
                 }
 
             //Cut button
@@ -339,7 +612,7 @@ impl KrustyGrab {
                 .on_hover_cursor(CursorIcon::PointingHand)
                 .on_hover_text_at_pointer("Cut screenshot")
                 .clicked() {
-                    self.set_window_status(krustygrab::WindowStatus::Crop);
+                    self.start_crop();
                     tracing::info!("Cut screenshot button selected");
             }
 
@@ -350,15 +623,9 @@ impl KrustyGrab {
                 .on_hover_cursor(CursorIcon::PointingHand)
                 .on_hover_text_at_pointer("Save")
                 .clicked() {
-                    let mut save_path = self.config.save_folder.clone();
-                    save_path.push(format!("{}", chrono::Utc::now().format("%Y_%m_%d-%H_%M_%S")));
-                    save_path.set_extension(self.config.save_format.to_string());
-
-                    self.save_path_request = Some(save_path);
-                    
-                    frame.set_visible(false);
-                    frame.set_fullscreen(true);
-                    self.set_window_status(krustygrab::WindowStatus::Save); 
+                    if let Some(image) = self.annotated_image_or_notify() {
+                        self.quick_save(image);
+                    }
 
                     tracing::info!("Save button selected");
                 }
@@ -370,21 +637,24 @@ impl KrustyGrab {
                 .on_hover_cursor(CursorIcon::PointingHand)
                 .on_hover_text_at_pointer("Save as")
                 .clicked() {
-                    if let Some(path) = FileDialog::new()
-                        .add_filter("PNG", &["png"])
-                        .add_filter("JPG", &["jpg"])
-                        .add_filter("GIF", &["gif"])
-                        .show_save_single_file()
-                        .expect("Unable to visualize the file selection window") {
-                            self.save_path_request = Some(path);
-
-                            frame.set_visible(false);
-                            frame.set_fullscreen(true);
-                            self.set_window_status(krustygrab::WindowStatus::Save); 
+                    match Format::file_dialog()
+                        .show_save_single_file() {
+                            Ok(Some(path)) => {
+                                if let Some(image) = self.annotated_image_or_notify() {
+                                    self.save_or_notify(image, path);
+                                }
+                            },
+                            Ok(None) => {},
+                            Err(e) => self.notify_file_error("show the file selection window", None, e.to_string()),
                         }
                     tracing::info!("Save as button selected");
                 }
         });
+
+        //The color, thickness and tool chosen are kept for the next session
+        if self.remember_tools(ctx) {
+            KrustyGrabConfig::store_tools(&self.config.tools);
+        }
     }
 
     /// Manage the canva
@@ -415,528 +685,192 @@ impl KrustyGrab {
         
         let visualization_ratio = screen.width() as f32 / w;
 
-        ctx.memory_mut(|mem| {
-            mem.data.insert_temp(Id::from("Visualization_ratio"), visualization_ratio);
-            mem.data.insert_temp(Id::from("Visualization_pos"), area.min);
-        });
+        self.document.canvas.ratio = visualization_ratio;
+        self.document.canvas.pos = area.min;
     
         painter.set_clip_rect(area);
         painter.image(screen.texture_id(ctx), area, Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)), Color32::WHITE);
 
-        let mut drawings = match ctx.memory(|mem| mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing"))) {
-            Some(v) => v,
-            None => Vec::<DrawingType>::new(),
-        };
-
-        let color = match ctx.memory(|mem| mem.data.get_temp::<Rgba>(Id::from("Color"))){
-            Some(c) => c,
-            None => Rgba::from(Color32::GREEN)
-        };
-
-        let thickness = match ctx.memory(|mem| mem.data.get_temp::<f32>(Id::from("Thickness"))){
-            Some(t) => t,
-            None => 1.0
-        };
 
-        let stroke = Stroke::new(thickness, color);
+        let transform = self.canvas_transform();
+        self.show_drawings(ctx, &painter, &transform);
 
-        self.show_drawings(ctx, &painter, visualization_ratio);
+        let drawing_mode = self.document.tool.mode;
+        if drawing_mode == DrawingMode::Select {
+            self.show_selection(ctx, &painter, &transform);
+            if !ctx.input(|i| i.pointer.primary_down()) {
+                self.end_select_grab();
+            }
+        }
+        else {
+            self.document.selection = None;
+        }
 
-        let color_picker_open = match ctx.memory(|mem| mem.data.get_temp::<bool>(Id::from("CP_open"))){
-            Some(c) => c,
-            None => false
-        };
-        
-        let settings_menu_open = match ctx.memory(|mem| mem.data.get_temp::<bool>(Id::from("SM_open"))){
-            Some(c) => {
-                ctx.memory_mut(|mem| mem.data.remove::<bool>(Id::from("SM_open")));
-                c
-            },
-            None => false
-        };
+        let color_picker_open = self.document.canvas.color_picker_open;
+        let settings_menu_open = std::mem::take(&mut self.document.canvas.settings_menu_open);
 
-        let drawing_mode = match ctx.memory_mut(|mem| mem.data.get_temp(Id::from("DrawingMode"))) {
-            Some(m) => m,
-            None => DrawingMode::Brush,    
-        };
-        
         //TEXT
-        let te_window = match ctx.memory_mut(|mem| mem.data.get_temp(Id::from("TE_open"))) {
-            Some(te) => te,
-            None => false,
-        };
+        let te_window = self.document.text_edit.is_some();
+        let path_mode = matches!(drawing_mode, DrawingMode::Polyline | DrawingMode::Polygon | DrawingMode::FilledPolygon);
+        let freehand = matches!(drawing_mode, DrawingMode::Brush | DrawingMode::Marker);
+
+        //Choosing another tool completes the polyline or polygon being drawn
+        if !path_mode && !self.document.path_points().is_empty() {
+            self.document.end_path();
+        }
 
         if drawing_mode != DrawingMode::Text {
-            let last_was_text = match drawings.last() {
-                Some(last) => match last {
-                    DrawingType::Text { p: _p, t: _t, s: _s } => true,
-                    _ => false,
-                },
-                None => false,
-            };
-            if last_was_text {
-                ctx.memory_mut(|mem| mem.data.remove::<bool>(Id::from("TE_open")));
-                ctx.memory_mut(|mem| mem.data.remove::<bool>(Id::from("TE_continue")));
-                ctx.memory_mut(|mem| mem.data.remove::<String>(Id::from("TE_text")));
-            }
+            self.document.end_text();
+        }
+        else {
+            self.text_editor(ctx, area);
         }
-        else if te_window {
-            let mut text: String = match ctx.memory_mut(|mem| mem.data.get_temp(Id::from("TE_text"))) {
-                Some(text) => text,
-                None => Default::default(),
-            };
-            let text_pos = match ctx.memory_mut(|mem| mem.data.get_temp(Id::from("Text_pos"))) {
-                Some(pos) => pos,
-                None => Pos2::ZERO,
-            };
-            let te_continue = match ctx.memory_mut(|mem| mem.data.get_temp(Id::from("TE_continue"))) {
-                Some(c) => c,
-                None => false,
-            };
 
-            //Computation of text editor position in order to maintain it inside the screen (values are obtained experimentally)
-            //TODO add orizzontal check for text box
-            let mut te_pos = self.adjust_drawing_pos(ctx, text_pos, true);
+        if let Some(mut mouse) = ctx.pointer_hover_pos() {
+            //The interaction with the canvas is only sensed when color picker and settings menu are closed and when not interacting with the configuration window (when it is open)
+            if area.contains(mouse) && !color_picker_open && !settings_menu_open 
+                && (!self.config_window || ctx.layer_id_at(mouse).unwrap_or(LayerId { order: Order::Background, id: Id::from("Configuration_check") }).order != Order::Middle) {
+                //Rescaling mouse position on the size of the screen to keep the position fixed on the canva
+                let pointer = mouse;
+                mouse = self.adjust_drawing_pos(mouse, false);
 
-            if te_pos.y + 97.0 > area.size()[1] + area.min.y {
-                te_pos = te_pos - Vec2::new(0.0, 95.0);
-            }
-            else {
-                te_pos = te_pos + Vec2::new(0.0, 20.0);
-            }
-            
-            Window::new("")
-            .fixed_pos(te_pos)
-            .show(ctx, |ui| {
-                let text_box = ui.text_edit_singleline(&mut text);
-
-                if text_box.lost_focus() {
-                    ctx.memory_mut(|mem| mem.data.remove::<bool>(Id::from("TE_open")));
-                    ctx.memory_mut(|mem| mem.data.remove::<bool>(Id::from("TE_continue")));
-                    ctx.memory_mut(|mem| mem.data.remove::<String>(Id::from("TE_text")));
+                if drawing_mode == DrawingMode::Select {
+                    self.select_on_canva(ctx, &transform, pointer, mouse);
                 }
-                else if text_box.changed() {
-                    ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("TE_text"), text.clone()));
-                    if te_continue {
-                        drawings.pop().unwrap_or(DrawingType::Text { p: text_pos, t: text.clone(), s: stroke });
-                    }
-                    else {
-                        ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("TE_continue"), true));
+
+                //A click on the canva completes the text being written, the following one starts a new text
+                let on_window = ctx.layer_id_at(pointer).is_some_and(|layer| layer.order == Order::Middle);
+                if ctx.input(|i| i.pointer.primary_clicked()) && drawing_mode == DrawingMode::Text && !on_window {
+                    if te_window {
+                        self.document.end_text();
+                    } else {
+                        self.document.start_text(mouse);
                     }
+                }
 
-                    drawings.push(DrawingType::Text { p: text_pos, t: text, s: stroke });
-                    ctx.memory_mut(|mem| {
-                        mem.data.insert_temp(Id::from("Drawing"), drawings.clone());
-                        mem.data.remove::<RedoList>(Id::from("Redo_list"));
-                    });
+                if path_mode {
+                    let clicked = ctx.input(|i| i.pointer.primary_clicked()) && !on_window;
+                    self.path_on_canva(ctx, &painter, &transform, mouse, clicked);
                 }
-            });
-        }
 
-        match ctx.pointer_hover_pos() {
-            Some(mut mouse) => {
-                let hover_rect = match ctx.memory(|mem| mem.data.get_temp(Id::from("hover_rect"))){
-                    Some(r) => r,
-                    None => area,
-                };
-
-                //The interaction with the canvas is only sensed when color picker and settings menu are closed and when not interacting with the configuration window (when it is open)
-                if hover_rect.contains(mouse) && !color_picker_open && !settings_menu_open 
-                    && !(self.config_window && ctx.layer_id_at(mouse).unwrap_or(LayerId { order: Order::Background, id: Id::from("Configuration_check") }).order == Order::Middle) {
-                    //Rescaling mouse position on the size of the screen to keep the position fixed on the canva
-                    mouse = self.adjust_drawing_pos(ctx, mouse, false);
-
-                    if ctx.input(|i| i.pointer.primary_clicked()) && !te_window{
-                        match drawing_mode {
-                            DrawingMode::Text => {
-                                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("TE_open"), true));
-                                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("TE_continue"), false));
-                                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("Text_pos"), mouse));
-                            },
-                            _ => {},
-                        }
-                    }
+                if drawing_mode == DrawingMode::Eraser && !on_window {
+                    self.erase_on_canva(ctx, &painter, &transform, pointer, mouse);
+                }
 
-                    // sense clicking for drawing 
-                    if ctx.input(|i| i.pointer.primary_down()) {
-                        //If the interaction is no longer with the configuration window it is closed without saving the changed values
-                        if self.config_window {
-                            self.config_window = false;
-                        }
+                if drawing_mode == DrawingMode::Eyedropper && !on_window {
+                    self.eyedropper_on_canva(ctx, pointer);
+                }
 
-                        let mut p0 = match ctx.memory(|mem| mem.data.get_temp(Id::from("initial_pos"))) {
-                            Some(p) => p,
-                            None => {
-                                let mut starting_pos = ctx.input(|i| i.pointer.press_origin()).unwrap();
-
-                                //Resize initial position (also previous position)
-                                starting_pos = self.adjust_drawing_pos(ctx, starting_pos, false);
-                                ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("initial_pos"), starting_pos));
-                                
-                                starting_pos
-                            }
-                        };
-                        //print of the drawings while dragging on the screen
-                        match drawing_mode {
-                            DrawingMode::Brush => {
-                                match drawings.last_mut() {
-                                    Some(d) => {
-                                        match d {
-                                            DrawingType::Brush { points, s: _s, end } => {
-                                                if !*end {
-                                                    points.push(mouse);
-                                                }
-                                                else {
-                                                    let mut ps = Vec::new();
-                                                    ps.push(mouse);
-                                                    drawings.push(DrawingType::Brush { points: ps, s: stroke, end: false });
-                                                }
-                                            },
-                                            _ => {
-                                                let mut ps = Vec::new();
-                                                ps.push(mouse);
-                                                drawings.push(DrawingType::Brush { points: ps, s: stroke, end: false });
-                                            },
-                                        }
-                                    },
-                                    None => {
-                                        let mut ps = Vec::new();
-                                        ps.push(mouse);
-                                        drawings.push(DrawingType::Brush { points: ps, s: stroke, end: false });
-                                    },
-                                };
-                                ctx.memory_mut(|mem| {
-                                    mem.data.insert_temp(Id::from("previous_pos"), mouse);
-                                    mem.data.insert_temp(Id::from("Drawing"), drawings.clone());
-                                });
-                            },
-                            DrawingMode::Rectangle => {
-                                if mouse.x < p0.x {
-                                    (mouse.x, p0.x) = (p0.x, mouse.x);
-                                }
-                                if mouse.y < p0.y {
-                                    (mouse.y, p0.y) = (p0.y, mouse.y);
-                                }
-                                let to_paint_border = Rect::from_min_max(self.adjust_drawing_pos(ctx, p0, true), self.adjust_drawing_pos(ctx, mouse, true));
-                                painter.rect_stroke(to_paint_border, 0.0, stroke);
-                                // tracing::info!("Painted rect with p0 {:?}, mouse {:?}, stroke {:?}", p0, mouse, stroke);
-                            },
-                            DrawingMode::Highlighter => {
-                                let mut minx = p0.x;
-                                let mut maxx = mouse.x;
-                                if mouse.x < p0.x {
-                                    (minx, maxx) = (maxx, minx);
-                                }
-                                let from_here = Pos2::new(minx , p0.y - stroke.width*5.);
-                                let to_there = Pos2::new(maxx , p0.y + stroke.width*5.);
-                                let mut color = stroke.color.clone();
-                                color[3] = color.a() / Self::HIGHLIGTHER_FACTOR ;
-                                let to_paint_border = Rect::from_min_max(self.adjust_drawing_pos(ctx, from_here, true), self.adjust_drawing_pos(ctx, to_there, true));
-                                painter.rect_filled(to_paint_border, 0.0, color );
-                                // tracing::info!("Painted highlight with p0 {:?}, mouse {:?}, stroke {:?}", p0, mouse, stroke);
-                            },
-                            DrawingMode::FilledRectangle => {
-                                if mouse.x < p0.x {
-                                    (mouse.x, p0.x) = (p0.x, mouse.x);
-                                }
-                                if mouse.y < p0.y {
-                                    (mouse.y, p0.y) = (p0.y, mouse.y);
-                                }
-                                let to_paint_border = Rect::from_min_max(self.adjust_drawing_pos(ctx, p0, true), self.adjust_drawing_pos(ctx, mouse, true));
-                                painter.rect_filled(to_paint_border, 0.0, stroke.color);
-                                // tracing::info!("Painted fillrect with p0 {:?}, mouse {:?}, stroke {:?}", p0, mouse, stroke);
-                            },
-                            DrawingMode::Circle => {
-                                // Constructed fixing the center with the starting position and the radius is considered between the start and the cursor current position
-                                // let radius = mouse.distance(p0) / visualization_ratio;
-                                // let mut center =  p0;
-                                // Constructed with one side on the starting point and the opposite on the cursor
-                                let radius = mouse.distance(p0) / (visualization_ratio*2.0);
-                                let mut center =  p0 + Vec2::new((mouse.x-p0.x)/2.0, (mouse.y-p0.y)/2.0);
-                                center = self.adjust_drawing_pos(ctx, center, true);
-
-                                painter.circle_stroke(center, radius, stroke);
-                                // tracing::info!("Painted circle with center {:?}, radius {:?}, stroke {:?}", center, radius, stroke);
-                            },
-                            DrawingMode::FilledCircle => {
-                                let radius = mouse.distance(p0) / (visualization_ratio*2.0);
-                                let mut center =  p0 + Vec2::new((mouse.x-p0.x)/2.0, (mouse.y-p0.y)/2.0);
-                                center = self.adjust_drawing_pos(ctx, center, true);
+                // sense clicking for drawing 
+                if ctx.input(|i| i.pointer.primary_down()) {
+                    //If the interaction is no longer with the configuration window it is closed without saving the changed values
+                    if self.config_window {
+                        self.config_window = false;
+                    }
 
-                                painter.circle_filled(center, radius, stroke.color);
-                                // tracing::info!("Painted circle with center {:?}, radius {:?}, stroke {:?}", center, radius, stroke);
-                            },
-                            DrawingMode::Arrow => {
-                                let origin = self.adjust_drawing_pos(ctx, p0, true);
-                                let direction = Vec2::new(mouse.x - p0.x, mouse.y - p0.y) / visualization_ratio;
-                                painter.arrow(origin, direction, stroke);
-                                // tracing::info!("Painted arrow with origin {:?}, vector {:?}, stroke {:?}", p0, Vec2::new(mouse.x - p0.x, mouse.y - p0.y), stroke);
-                            },
-                            _ => {},
+                    let press_origin = match ctx.input(|i| i.pointer.press_origin()) {
+                        Some(origin) => self.adjust_drawing_pos(origin, false),
+                        None => mouse,
+                    };
+                    let p0 = self.document.drag_origin(|| press_origin);
+                    let (start, end) = self.constrain_drag(ctx, p0, mouse);
+
+                    //print of the drawings while dragging on the screen
+                    if freehand {
+                        self.document.brush_to(mouse);
+                    }
+                    else if let Some(preview) = self.document.drag_drawing(start, end).map(|d| self.snap_highlight(d)) {
+                        //The redactions are computed once released, only their area is shown while dragging
+                        if let Some((_, r, _)) = preview.redaction() {
+                            painter.rect_stroke(transform.transform_rect(r), 0.0, Stroke::new(1.0, Color32::WHITE));
+                            painter.rect_stroke(transform.transform_rect(r).expand(1.0), 0.0, Stroke::new(1.0, Color32::BLACK));
                         }
-                        ctx.memory_mut(|mem| {
-                            mem.data.insert_temp(Id::from("mouse_pos"), mouse);
-                            mem.data.insert_temp(Id::from("hover_rect"), area);
-                        });
+                        let shapes = ctx.fonts(|fonts| preview.to_shapes(&transform, fonts));
+                        painter.extend(shapes);
                     }
+                }
 
-                    // save the drawing after releasing 
-                    if ctx.input(|i| i.pointer.primary_released()) {
-                        // tracing::info!("Pointer primary released");
-                        match ctx.memory(|mem| mem.data.get_temp::<Pos2>(Id::from("initial_pos"))) {
-                            Some(mut p0) => {
-                                match drawing_mode {
-                                    DrawingMode::Brush => {
-                                        match drawings.last_mut() {
-                                            Some(d) => match d {
-                                                DrawingType::Brush { points, s: _s, end } => {
-                                                    points.push(mouse);
-                                                    *end = true;
-                                                },
-                                                _ => {},
-                                            }
-                                            _ => {},
-                                        }
-                                        ctx.memory_mut(|mem| mem.data.remove::<Pos2>(Id::from("previous_pos")));
-                                    },
-                                    DrawingMode::Highlighter => {
-                                        let mut minx = p0.x;
-                                        let mut maxx = mouse.x;
-                                        if mouse.x < p0.x {
-                                            (minx, maxx) = (maxx, minx);
-                                        }
-                                        let from_here = Pos2::new(minx , p0.y - stroke.width*5.);
-                                        let to_there = Pos2::new(maxx , p0.y + stroke.width*5.);
-                                        drawings.push(DrawingType::Highlighter{ r: Rect::from_min_max(from_here, to_there), s: stroke });
-                                        tracing::info!("Added highlight with p0 {:?}, mouse {:?}, stroke {:?}", p0, mouse, stroke);
-                                    },
-                                    DrawingMode::Rectangle => {
-                                        if mouse.x < p0.x {
-                                            (mouse.x, p0.x) = (p0.x, mouse.x);
-                                        }
-                                        if mouse.y < p0.y {
-                                            (mouse.y, p0.y) = (p0.y, mouse.y);
-                                        }
-        
-                                        drawings.push(DrawingType::Rectangle { r: Rect::from_min_max(p0, mouse), s: stroke });
-                                        tracing::info!("Added rect with p0 {:?}, mouse {:?}, stroke {:?}", p0, mouse, stroke);
-                                    },
-                                    DrawingMode::FilledRectangle => {
-                                        if mouse.x < p0.x {
-                                            (mouse.x, p0.x) = (p0.x, mouse.x);
-                                        }
-                                        if mouse.y < p0.y {
-                                            (mouse.y, p0.y) = (p0.y, mouse.y);
-                                        }
-        
-                                        drawings.push(DrawingType::FilledRectangle { r: Rect::from_min_max(p0, mouse), s: stroke });
-                                        tracing::info!("Added rect with p0 {:?}, mouse {:?}, stroke {:?}", p0, mouse, stroke);
-                                    },
-                                    DrawingMode::Circle => {
-                                        // Constructed fixing the center with the starting position and the radius is considered between the start and the cursor current position
-                                        // let radius = mouse.distance(p0);
-                                        // let center = p0;
-                                        // Constructed with one side on the starting point and the opposite on the cursor
-                                        let radius = mouse.distance(p0) / 2.0;
-                                        let center =  p0 + Vec2::new((mouse.x-p0.x)/2.0, (mouse.y-p0.y)/2.0);
-
-                                        drawings.push(DrawingType::Circle { c: center, r: radius, s: stroke });
-                                        tracing::info!("Added circle with center {:?}, radius {:?}, stroke {:?}", center, radius, stroke);
-                                    },
-                                    DrawingMode::FilledCircle => {
-                                        // Constructed fixing the center with the starting position and the radius is considered between the start and the cursor current position
-                                        // let radius = mouse.distance(p0);
-                                        // let center = p0;
-                                        // Constructed with one side on the starting point and the opposite on the cursor
-                                        let radius = mouse.distance(p0) / 2.0;
-                                        let center =  p0 + Vec2::new((mouse.x-p0.x)/2.0, (mouse.y-p0.y)/2.0);
-
-                                        drawings.push(DrawingType::FilledCircle { c: center, r: radius, s: stroke });
-                                        tracing::info!("Added circle with center {:?}, radius {:?}, stroke {:?}", center, radius, stroke);
-                                    },
-                                    DrawingMode::Arrow => {
-                                        drawings.push(DrawingType::Arrow { p: p0, v: Vec2::new(mouse.x - p0.x, mouse.y - p0.y), s: stroke });
-                                        tracing::info!("Added arrow with origin {:?}, vector {:?}, stroke {:?}", p0, Vec2::new(mouse.x - p0.x, mouse.y - p0.y), stroke);
-                                    },
-                                    _ => {},
-                                }
-        
-                                ctx.memory_mut(|mem| {
-                                    mem.data.insert_temp(Id::from("Drawing"), drawings.clone());
-                                    mem.data.remove::<Pos2>(Id::from("initial_pos"));
-                                    mem.data.remove::<Rect>(Id::from("hover_rect"));
-                                    mem.data.remove::<RedoList>(Id::from("Redo_list"));
-                                });
-                            },
-                            None => {},
-                        };
-
-                        
+                // save the drawing after releasing 
+                if ctx.input(|i| i.pointer.primary_released()) {
+                    if let Some(p0) = self.document.end_drag() {
+                        let (start, end) = self.constrain_drag(ctx, p0, mouse);
+                        if freehand {
+                            self.document.end_brush(Some(mouse));
+                        }
+                        else if let Some(drawing) = self.document.drag_drawing(start, end).map(|d| self.snap_highlight(d)) {
+                            tracing::info!("Added {:?}", drawing);
+                            self.document.push(drawing);
+                        }
                     }
                 }
-                else {
-                    let primary_up = !ctx.input(|i| i.pointer.primary_down());
-
-                    if drawing_mode == DrawingMode::Brush || primary_up {
-                        ctx.memory_mut(|mem| {
-                            if drawing_mode == DrawingMode::Brush {
-                                match drawings.last_mut() {
-                                    Some(d) => match d {
-                                        DrawingType::Brush { points: _points, s: _s, end } => {
-                                            *end = true;
-                                        },
-                                        _ => {},
-                                    }
-                                    _ => {},
-                                }
-                                mem.data.insert_temp(Id::from("Drawing"), drawings.clone());
+            }
+            else {
+                let primary_up = !ctx.input(|i| i.pointer.primary_down());
 
-                                if !primary_up {
-                                    mem.data.insert_temp(Id::from("previous_pos"), mouse);
-                                }
-                                else {
-                                    mem.data.remove::<Pos2>(Id::from("previous_pos"));
-                                }
-                            }
-                            
-                            mem.data.remove::<Pos2>(Id::from("initial_pos"));
-                            mem.data.remove::<Rect>(Id::from("hover_rect"));
-                        });
+                //Leaving the canva completes the brush stroke, the other drawings are discarded only once the button is released
+                if freehand || primary_up {
+                    if freehand {
+                        self.document.end_brush(None);
                     }
+                    if drawing_mode == DrawingMode::Eraser {
+                        self.document.end_edit();
+                    }
+                    self.document.end_drag();
                 }
-            },
-            None => {},
+            }
         }
     }
 
     ///Scale the drawing position with the actual image reduction ratio in order to maintain the drawing in position after a rescale of the window that visualize it
-    fn adjust_drawing_pos(&mut self, ctx: &Context, pos: Pos2, render: bool) -> Pos2{
-        let adjusted_pos: Pos2;
-        let v_ratio: f32 = ctx.memory(|mem| {
-            match mem.data.get_temp::<f32>(Id::from("Visualization_ratio")){
-                Some(ratio) => ratio,
-                None => f32::default(),
-            }
-        });
-        let v_pos: Pos2 = ctx.memory(|mem| {
-            match mem.data.get_temp::<Pos2>(Id::from("Visualization_pos")){
-                Some(ratio) => ratio,
-                None => Pos2::default(),
-            }
-        });
+    pub fn adjust_drawing_pos(&self, pos: Pos2, render: bool) -> Pos2{
+        let v_ratio = self.document.canvas.ratio;
+        let v_pos = self.document.canvas.pos;
         let area_min = match self.get_selected_area() {
             Some(area) => area.min,
             None => pos2(0., 0.),
         };
         
         if render {
-            adjusted_pos = pos2(((pos.x - area_min.x) / v_ratio) + v_pos.x, ((pos.y - area_min.y) / v_ratio) + v_pos.y);
+            pos2(((pos.x - area_min.x) / v_ratio) + v_pos.x, ((pos.y - area_min.y) / v_ratio) + v_pos.y)
         } else {
-            adjusted_pos = pos2((pos.x - v_pos.x) * v_ratio + area_min.x, (pos.y - v_pos.y) * v_ratio + area_min.y);
+            pos2((pos.x - v_pos.x) * v_ratio + area_min.x, (pos.y - v_pos.y) * v_ratio + area_min.y)
         }
-        adjusted_pos
     }
 
-    ///Shows the saved drawings
-    pub fn show_drawings(&mut self, ctx: &Context, painter: &Painter, visualization_ratio: f32) {
-        let drawings = match ctx.memory(|mem| mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing"))) {
-            Some(v) => v,
-            None => Vec::<DrawingType>::new(),
+    ///Map the drawings coordinates on the canva, considering the selected area and the scale of the visualization
+    pub(crate) fn canvas_transform(&self) -> RectTransform {
+        let screen = self.screen.as_ref().expect("Screen must be defined to show the drawings");
+        let area_min = match self.get_selected_area() {
+            Some(area) => area.min,
+            None => pos2(0., 0.),
         };
-
-        //Visualization of saved drawings
-        for d in &drawings {
-            match d.clone() {
-                DrawingType::Brush { points, mut s, .. } => {
-                    //Change the thickness of the strok according to the window size in order to obtain a static dimension among the visualization 
-                    s.width /= visualization_ratio;
-                    for i in 1..points.len() {
-                        let to_paint = [self.adjust_drawing_pos(ctx, points[i], true), self.adjust_drawing_pos(ctx, points[i-1], true)];
-                        painter.line_segment(to_paint, s);
-                    }
-                },
-                DrawingType::Highlighter { r, s } => {
-                    let to_paint = Rect::from_min_max(self.adjust_drawing_pos(ctx, r.min, true), self.adjust_drawing_pos(ctx, r.max, true));
-                    let mut color = s.color.clone();
-                    color[3] = color.a() / Self::HIGHLIGTHER_FACTOR ;
-                    painter.rect_filled(to_paint, 0.0, color);
-                },
-                DrawingType::Rectangle { r, s } => {
-                    let to_paint = Rect::from_min_max(self.adjust_drawing_pos(ctx, r.min, true), self.adjust_drawing_pos(ctx, r.max, true));
-                    painter.rect_stroke(to_paint, 0.0, s);
-                },
-                DrawingType::FilledRectangle { r, s } => {
-                    let to_paint = Rect::from_min_max(self.adjust_drawing_pos(ctx, r.min, true), self.adjust_drawing_pos(ctx, r.max, true));
-                    painter.rect_filled(to_paint, 0.0, s.color);
-                },
-                DrawingType::Circle { mut c, mut r, s } => {
-                    c = self.adjust_drawing_pos(ctx, c, true);
-                    r /= visualization_ratio;
-                    painter.circle_stroke(c, r, s);
-                },
-                DrawingType::FilledCircle { mut c, mut r, s } => {
-                    c = self.adjust_drawing_pos(ctx, c, true);
-                    r /= visualization_ratio;
-                    painter.circle_filled(c, r, s.color);
-                },
-                DrawingType::Arrow { p, v, s } => {
-                    let origin = self.adjust_drawing_pos(ctx, p, true);
-                    let direction = v / visualization_ratio;
-                    painter.arrow(origin, direction, s);
-                },
-                DrawingType::Text { mut p , t , s} => {
-                    p = self.adjust_drawing_pos(ctx, p, true);
-                    //Regolazione del font in base alla dimensione della finestra di render
-                    let font_size = (KrustyGrab::BASE_TEXT_SIZE * s.width) / visualization_ratio; //TODO Giggling text, UNFIXABLE! Library bug! 
-                    // println!("Size: {font_size:?}");
-                    painter.text(p, Align2::LEFT_CENTER, t, FontId::new(font_size, egui::FontFamily::Proportional), s.color);
-                },
-            }
-        }
+        let from = Rect::from_min_size(area_min, Vec2::new(screen.width() as f32, screen.height() as f32));
+        let to = Rect::from_min_size(self.adjust_drawing_pos(area_min, true), from.size() / self.document.canvas.ratio);
+        RectTransform::from_to(from, to)
     }
 
-    ///Shows the saved drawings in the select mode (fullscreen)
-    pub fn show_drawings_in_select(&mut self, ctx: &Context, painter: &Painter) {
-        let drawings = match ctx.memory(|mem| mem.data.get_temp::<Vec<DrawingType>>(Id::from("Drawing"))) {
-            Some(v) => v,
-            None => Vec::<DrawingType>::new(),
-        };
+    ///Shows the saved drawings. The transform maps the screenshot coordinates on the painting area.
+    ///The redactions and the finished highlights are shown with textures of their pixels in the exported image, dropped once their drawing is no longer shown.
+    pub fn show_drawings(&mut self, ctx: &Context, painter: &Painter, transform: &RectTransform) {
+        let mut shown = HashSet::new();
+        let mut highlights = HashSet::new();
 
-        //Visualization of saved drawings
-        for d in &drawings {
-            match d.clone() {
-                DrawingType::Brush { points, s, .. } => {
-                    for i in 1..points.len() {
-                        let to_paint = [points[i], points[i-1]];
-                        painter.line_segment(to_paint, s);
-                    }
-                },
-                DrawingType::Highlighter { r, s } => {
-                    let mut color = s.color.clone();
-                    color[3] = color.a() / Self::HIGHLIGTHER_FACTOR ;
-                    painter.rect_filled(r, 0.0, color);
-                },
-                DrawingType::Rectangle { r, s } => {
-                    painter.rect_stroke(r, 0.0, s);
-                },
-                DrawingType::FilledRectangle { r, s } => {
-                    painter.rect_filled(r, 0.0, s.color);
-                },
-                DrawingType::Circle { c, r, s } => {
-                    painter.circle_stroke(c, r, s);
-                },
-                DrawingType::FilledCircle { c, r, s } => {
-                    painter.circle_filled(c, r, s.color);
-                },
-                DrawingType::Arrow { p, v, s } => {
-                    painter.arrow(p, v, s);
-                },
-                DrawingType::Text { p , t , s} => {
-                    //Regolazione del font in base alla dimensione della finestra di render
-                    let font_size = KrustyGrab::BASE_TEXT_SIZE * s.width;
-                    painter.text(p, Align2::LEFT_CENTER, t, FontId::new(font_size, egui::FontFamily::Proportional), s.color);
-                },
+        for (index, drawing) in self.document.drawings().to_vec().into_iter().enumerate() {
+            if let Some(redaction) = drawing.redaction() {
+                shown.extend(self.show_redaction(ctx, painter, transform, redaction));
+                continue;
             }
+            //The marker stroke being drawn changes at every frame, its translucent shapes are shown instead
+            if drawing.multiplies() && !matches!(drawing, DrawingType::Marker { end: false, .. }) {
+                if let Some(key) = self.show_highlight(ctx, painter, transform, index) {
+                    highlights.insert(key);
+                    continue;
+                }
+            }
+            painter.extend(ctx.fonts(|fonts| drawing.to_shapes(transform, fonts)));
         }
+
+        self.redaction_textures.retain(|key, _| shown.contains(key));
+        self.highlight_textures.retain(|key, _| highlights.contains(key));
     }
 }
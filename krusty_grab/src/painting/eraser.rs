@@ -0,0 +1,164 @@
+use egui::{emath::RectTransform, epaint::text::Fonts, Color32, Context, Painter, Pos2, Stroke};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    krustygrab::KrustyGrab,
+    painting::{document::AnnotationDocument, drawing::DrawingType, history::Command},
+};
+
+///Radius of the eraser for every unit of the thickness, in screenshot pixels
+pub const ERASER_RADIUS: f32 = 4.0;
+
+///What the eraser removes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum EraserMode {
+    ///Every drawing touched by the eraser
+    #[default]
+    Object,
+    ///The parts of the brush and marker strokes under the eraser
+    Stroke,
+}
+
+///Part of the segment from `a` to `b` inside the circle, as the interval of the segment parameter. None if the segment does not cross it.
+fn clip_segment(a: Pos2, b: Pos2, center: Pos2, radius: f32) -> Option<(f32, f32)> {
+    let (d, f) = (b - a, a - center);
+    let (qa, qb, qc) = (d.dot(d), 2. * f.dot(d), f.dot(f) - radius * radius);
+    let discriminant = qb * qb - 4. * qa * qc;
+    if qa <= f32::EPSILON || discriminant <= 0. {
+        return (qc <= 0.).then_some((0., 1.));
+    }
+
+    let root = discriminant.sqrt();
+    let (t0, t1) = (((-qb - root) / (2. * qa)).max(0.), ((-qb + root) / (2. * qa)).min(1.));
+    (t0 < t1).then_some((t0, t1))
+}
+
+///Pieces of the stroke left after erasing the circle. None if the circle does not touch the stroke.
+pub fn erase_points(points: &[Pos2], center: Pos2, radius: f32) -> Option<Vec<Vec<Pos2>>> {
+    if let [point] = points {
+        return (point.distance(center) <= radius).then(Vec::new);
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<Pos2> = Vec::new();
+    let mut touched = false;
+
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        if current.is_empty() {
+            current.push(a);
+        }
+
+        match clip_segment(a, b, center, radius) {
+            None => current.push(b),
+            Some((t0, t1)) => {
+                touched = true;
+                //The piece before the circle ends on its border, the next one starts from the other side
+                current.push(a + (b - a) * t0);
+                pieces.push(std::mem::take(&mut current));
+                if t1 < 1. {
+                    current = vec![a + (b - a) * t1, b];
+                }
+            },
+        }
+    }
+    pieces.push(current);
+
+    //Pieces made of a single position are dropped
+    let pieces = pieces.into_iter().filter(|piece| piece.windows(2).any(|p| p[0] != p[1])).collect();
+    touched.then_some(pieces)
+}
+
+impl AnnotationDocument {
+    ///Erase what is under the circle. The steps of the same drag are undone together until `end_edit` is called.
+    pub fn erase(&mut self, center: Pos2, radius: f32, mode: EraserMode, fonts: &Fonts) {
+        let mut steps = Vec::new();
+
+        //From the last drawing, so that the indexes of the following steps are not affected
+        for (index, drawing) in self.drawings().iter().enumerate().rev() {
+            match (mode, drawing) {
+                (EraserMode::Object, drawing) => {
+                    if drawing.hit_test(center, radius, fonts) {
+                        steps.push(Command::DeleteDrawing { index, drawing: drawing.clone() });
+                    }
+                },
+                (EraserMode::Stroke, DrawingType::Brush { points, .. } | DrawingType::Marker { points, .. }) => {
+                    let Some(pieces) = erase_points(points, center, radius) else {
+                        continue;
+                    };
+                    steps.push(Command::DeleteDrawing { index, drawing: drawing.clone() });
+                    for (i, piece) in pieces.into_iter().enumerate() {
+                        let mut drawing = drawing.clone();
+                        if let DrawingType::Brush { points, .. } | DrawingType::Marker { points, .. } = &mut drawing {
+                            *points = piece;
+                        }
+                        steps.push(Command::AddDrawing { index: index + i, drawing });
+                    }
+                },
+                (EraserMode::Stroke, _) => {},
+            }
+        }
+
+        if !steps.is_empty() {
+            self.execute_merging(Command::Erase { steps });
+        }
+    }
+}
+
+impl KrustyGrab {
+    ///Manage the eraser while the pointer is on the canva, showing its size.
+    ///`pointer` is the position in the window, `mouse` the same position in screenshot pixels.
+    pub fn erase_on_canva(&mut self, ctx: &Context, painter: &Painter, transform: &RectTransform, pointer: Pos2, mouse: Pos2) {
+        let radius = self.document.tool.thickness * ERASER_RADIUS;
+        painter.circle_stroke(pointer, radius * transform.scale().x, Stroke::new(1.5, Color32::WHITE));
+        painter.circle_stroke(pointer, radius * transform.scale().x + 1.5, Stroke::new(1., Color32::BLACK));
+
+        if ctx.input(|i| i.pointer.primary_down()) {
+            let mode = self.document.tool.eraser;
+            ctx.fonts(|fonts| self.document.erase(mouse, radius, mode, fonts));
+        } else {
+            self.document.end_edit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+
+    use super::*;
+
+    #[test]
+    fn segment_is_clipped_by_the_circle() {
+        let (a, b) = (pos2(0., 0.), pos2(10., 0.));
+        assert_eq!(clip_segment(a, b, pos2(5., 0.), 1.), Some((0.4, 0.6)));
+        //Only the part of the segment inside the circle
+        assert_eq!(clip_segment(a, b, pos2(0., 0.), 2.), Some((0., 0.2)));
+        assert_eq!(clip_segment(a, b, pos2(5., 5.), 1.), None);
+        //A segment of zero length is inside or outside as a whole
+        assert_eq!(clip_segment(a, a, pos2(0., 0.5), 1.), Some((0., 1.)));
+        assert_eq!(clip_segment(a, a, pos2(0., 5.), 1.), None);
+    }
+
+    #[test]
+    fn stroke_under_the_eraser_is_removed() {
+        let points = [pos2(0., 0.), pos2(2., 0.), pos2(4., 0.)];
+        assert_eq!(erase_points(&points, pos2(2., 0.), 5.), Some(vec![]));
+        assert_eq!(erase_points(&[pos2(1., 1.)], pos2(1., 2.), 2.), Some(vec![]));
+    }
+
+    #[test]
+    fn cut_in_the_middle_leaves_two_pieces() {
+        let points = [pos2(0., 0.), pos2(10., 0.), pos2(20., 0.)];
+        let pieces = erase_points(&points, pos2(10., 0.), 2.).unwrap();
+        assert_eq!(pieces, vec![vec![pos2(0., 0.), pos2(8., 0.)], vec![pos2(12., 0.), pos2(20., 0.)]]);
+    }
+
+    #[test]
+    fn stroke_out_of_the_eraser_is_untouched() {
+        let points = [pos2(0., 0.), pos2(10., 0.)];
+        assert_eq!(erase_points(&points, pos2(5., 5.), 2.), None);
+        assert_eq!(erase_points(&[pos2(0., 0.)], pos2(5., 5.), 2.), None);
+        assert_eq!(erase_points(&[], pos2(0., 0.), 2.), None);
+    }
+}
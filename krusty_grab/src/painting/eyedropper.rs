@@ -0,0 +1,107 @@
+use arboard::Clipboard;
+use egui::{pos2, vec2, Color32, Context, CursorIcon, Id, LayerId, Order, Pos2, Rect, Rgba, Stroke, TextStyle};
+
+use crate::krustygrab::KrustyGrab;
+
+///Pixels shown on every side of the pointer in the loupe
+const LOUPE_RADIUS: i32 = 5;
+///Side of a pixel in the loupe, in points
+const LOUPE_ZOOM: f32 = 10.0;
+///Distance of the loupe from the pointer, in points
+const LOUPE_OFFSET: f32 = 24.0;
+
+///Color as `#RRGGBB`
+pub fn hex(color: Color32) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r(), color.g(), color.b())
+}
+
+impl KrustyGrab {
+    ///Pixel of the shown screenshot under the pointer, given in the window.
+    ///The position is mapped on the screenshot with the ratio and the position of its visualization.
+    fn screen_pixel(&self, pointer: Pos2) -> Option<[usize; 2]> {
+        let screen = self.screen.as_ref()?;
+        let pixel = ((pointer - self.document.canvas.pos) * self.document.canvas.ratio).floor();
+
+        (pixel.x >= 0. && pixel.y >= 0. && (pixel.x as usize) < screen.width() && (pixel.y as usize) < screen.height())
+            .then_some([pixel.x as usize, pixel.y as usize])
+    }
+
+    ///Manage the eyedropper while the pointer is on the canva, showing the pixels around it enlarged with the value of the one under it.
+    ///A click makes the color the one of the drawings, a right click copies its value in the clipboard.
+    pub fn eyedropper_on_canva(&mut self, ctx: &Context, pointer: Pos2) {
+        let (Some([x, y]), Some(screen)) = (self.screen_pixel(pointer), self.screen.as_ref()) else {
+            return;
+        };
+        let color = screen[(x, y)];
+        ctx.set_cursor_icon(CursorIcon::Crosshair);
+
+        //The loupe is placed on the bottom right of the pointer, or on the other side when it would leave the window
+        let side = (2 * LOUPE_RADIUS + 1) as f32 * LOUPE_ZOOM;
+        let window = ctx.screen_rect();
+        let mut loupe = Rect::from_min_size(pointer + vec2(LOUPE_OFFSET, LOUPE_OFFSET), vec2(side, side));
+        if loupe.max.x > window.max.x {
+            loupe = loupe.translate(vec2(-side - 2. * LOUPE_OFFSET, 0.));
+        }
+        if loupe.max.y + LOUPE_OFFSET > window.max.y {
+            loupe = loupe.translate(vec2(0., -side - 3. * LOUPE_OFFSET));
+        }
+
+        let painter = ctx.layer_painter(LayerId::new(Order::Tooltip, Id::from("Eyedropper")));
+        for dy in -LOUPE_RADIUS..=LOUPE_RADIUS {
+            for dx in -LOUPE_RADIUS..=LOUPE_RADIUS {
+                let (px, py) = (x as i32 + dx, y as i32 + dy);
+                //Pixels out of the screenshot are shown dark
+                let pixel = if px >= 0 && py >= 0 && (px as usize) < screen.width() && (py as usize) < screen.height() {
+                    screen[(px as usize, py as usize)]
+                } else {
+                    Color32::from_gray(32)
+                };
+                let min = loupe.min + vec2((dx + LOUPE_RADIUS) as f32, (dy + LOUPE_RADIUS) as f32) * LOUPE_ZOOM;
+                painter.rect_filled(Rect::from_min_size(min, vec2(LOUPE_ZOOM, LOUPE_ZOOM)), 0.0, pixel);
+            }
+        }
+        let center = Rect::from_center_size(loupe.center(), vec2(LOUPE_ZOOM, LOUPE_ZOOM));
+        painter.rect_stroke(center, 0.0, Stroke::new(1.0, Color32::WHITE));
+        painter.rect_stroke(center.expand(1.0), 0.0, Stroke::new(1.0, Color32::BLACK));
+        painter.rect_stroke(loupe, 0.0, Stroke::new(1.0, Color32::BLACK));
+
+        //Value of the pixel under the loupe
+        let value = format!("{}  rgb({}, {}, {})", hex(color), color.r(), color.g(), color.b());
+        let galley = ctx.fonts(|fonts| fonts.layout_no_wrap(value, TextStyle::Body.resolve(&ctx.style()), Color32::WHITE));
+        let label = Rect::from_min_size(pos2(loupe.min.x, loupe.max.y + 6.), galley.size());
+        painter.rect_filled(label.expand(3.), 3.0, Color32::from_black_alpha(200));
+        painter.galley(label.min, galley);
+
+        if ctx.input(|i| i.pointer.primary_clicked()) {
+            self.document.tool.color = Rgba::from(color);
+            tracing::info!("Color {} picked from the screenshot", hex(color));
+        }
+
+        if ctx.input(|i| i.pointer.secondary_clicked()) {
+            match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(hex(color))) {
+                Ok(()) => tracing::info!("Color {} copied in the clipboard", hex(color)),
+                Err(e) => tracing::error!("Unable to copy in the clipboard: {e:?}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, Color32, ColorImage};
+
+    use super::*;
+
+    #[test]
+    fn pointer_is_mapped_with_the_canva_scale() {
+        let mut app = KrustyGrab::default();
+        app.set_temp_image(Some(ColorImage::new([8, 6], Color32::WHITE)));
+        app.document.canvas.ratio = 2.;
+        app.document.canvas.pos = pos2(10., 20.);
+
+        assert_eq!(app.screen_pixel(pos2(10., 20.)), Some([0, 0]));
+        assert_eq!(app.screen_pixel(pos2(13.2, 21.7)), Some([6, 3]));
+        assert_eq!(app.screen_pixel(pos2(9.9, 21.)), None);
+        assert_eq!(app.screen_pixel(pos2(14., 21.)), None);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use egui::{
+    ecolor::{gamma_from_linear, linear_from_gamma},
+    emath::RectTransform,
+    epaint::text::Fonts,
+    pos2, vec2, Color32, ColorImage, Context, Painter, Rect, TextureOptions,
+};
+
+use crate::{
+    krustygrab::KrustyGrab,
+    painting::{drawing::DrawingType, rasterizer::render_drawings, redaction::pixel_bounds},
+};
+
+///Alpha of the highlight shapes. The rasterizer multiplies their full color, the alpha only marks how much of a pixel they cover.
+///The canva can only blend with the alpha, so it shows the finished highlights with the rasterized pixels,
+///the translucent shapes are only used while the highlight is drawn.
+pub const HIGHLIGHT_ALPHA: f32 = 0.4;
+///Width of the marker strokes for every unit of the thickness, in screenshot pixels
+pub const MARKER_WIDTH: f32 = 6.0;
+
+///Rows above and below the pointer looked at to find the text row
+const SNAP_SEARCH: usize = 80;
+///Largest distance of the pointer from the text row to snap on it
+const SNAP_DISTANCE: usize = 8;
+///Difference of luminance between the darkest and the brightest pixel of a row with text
+const INK_CONTRAST: u8 = 48;
+///Rows without text allowed inside a text row, e.g. between the dot and the stem of an i
+const MAX_GAP: usize = 1;
+///Space added above and below the text row
+const SNAP_PADDING: f32 = 2.0;
+
+///Color of the highlight shapes, translucent to approximate the multiplication while the highlight is drawn
+pub fn highlight_color(color: Color32) -> Color32 {
+    color.to_opaque().linear_multiply(HIGHLIGHT_ALPHA)
+}
+
+///Blend the color of a highlight, as given by [`highlight_color`], on the premultiplied pixel multiplying them.
+///The full color is multiplied where the highlight is opaque, so the text under it stays as dark as it was.
+pub fn multiply(dst: [f32; 4], src: [f32; 4]) -> [f32; 4] {
+    if src[3] <= 0.0 {
+        return dst;
+    }
+    //The highlight color is translucent in linear space, the edges of the shapes fade its alpha
+    let coverage = (src[3] / HIGHLIGHT_ALPHA).min(1.0);
+    let mut out = dst;
+    for i in 0..3 {
+        let color = gamma_from_linear((linear_from_gamma(src[i]) / src[3]).min(1.0));
+        out[i] = dst[i] * (1.0 - coverage + coverage * color);
+    }
+    out
+}
+
+///Vertical range of the row of text crossing `y` between the horizontal bounds, in pixels of the image.
+///None if there is no text near `y`, or if the content around it has no clear rows like a picture.
+pub fn text_band(image: &ColorImage, [x0, x1]: [f32; 2], y: f32) -> Option<[f32; 2]> {
+    let [width, height] = image.size;
+    let clamp = |v: f32, max: usize| (v.max(0.0) as usize).min(max);
+    let (x0, x1, y) = (clamp(x0.floor(), width), clamp(x1.ceil(), width), clamp(y, height.saturating_sub(1)));
+    if x0 >= x1 || height == 0 {
+        return None;
+    }
+
+    let (top, bottom) = (y.saturating_sub(SNAP_SEARCH), (y + SNAP_SEARCH + 1).min(height));
+    //A row has text if its pixels are not all of the same luminance
+    let ink: Vec<bool> = (top..bottom)
+        .map(|row| {
+            let luminance = image.pixels[row * width + x0..row * width + x1].iter().map(|p| {
+                let [r, g, b, _] = p.to_array().map(u32::from);
+                ((r * 299 + g * 587 + b * 114) / 1000) as u8
+            });
+            let (min, max) = luminance.fold((u8::MAX, u8::MIN), |(min, max), l| (min.min(l), max.max(l)));
+            max - min > INK_CONTRAST
+        })
+        .collect();
+
+    //Nearest row with text, then the rows connected to it
+    let start = y - top;
+    let found = (0..=SNAP_DISTANCE).flat_map(|d| [start.checked_sub(d), Some(start + d)]).flatten().find(|&r| ink.get(r) == Some(&true))?;
+
+    let ink_at = |r: Option<usize>| r.and_then(|r| ink.get(r)) == Some(&true);
+    let (mut first, mut last) = (found, found);
+    while (1..=MAX_GAP + 1).any(|d| ink_at(first.checked_sub(d))) {
+        first -= 1;
+    }
+    while (1..=MAX_GAP + 1).any(|d| ink_at(Some(last + d))) {
+        last += 1;
+    }
+
+    //A band reaching the end of the search, but not of the image, is not a row of text
+    if (first == 0 && top > 0) || (last + 1 == ink.len() && bottom < height) {
+        return None;
+    }
+    Some([(top + first) as f32 - SNAP_PADDING, (top + last + 1) as f32 + SNAP_PADDING])
+}
+
+///Pixels of the exported image covered by the last drawing, a highlight, with their bounds as given by [`pixel_bounds`].
+///The highlight is multiplied on the screenshot and on the drawings made before it, rendered on a copy of the screenshot
+///cut around them, so that the canva can show the same pixels as the exported image.
+pub fn highlighted_area(image: &ColorImage, drawings: &[DrawingType], fonts: &Fonts) -> Option<([usize; 4], ColorImage)> {
+    let (highlight, before) = drawings.split_last()?;
+    //The shapes are antialiased on one more pixel
+    let bounds = pixel_bounds(highlight.bounds(fonts).expand(1.0), image.size);
+    let [x0, y0, x1, y1] = bounds;
+    if x0 >= x1 || y0 >= y1 {
+        return None;
+    }
+    let area = Rect::from_min_max(pos2(x0 as f32, y0 as f32), pos2(x1 as f32, y1 as f32));
+
+    //The redactions sample the whole rectangle they cover, so the copy must contain it
+    let shown = |d: &&DrawingType| match d.redaction() {
+        Some((_, r, _)) => r.intersects(area),
+        None => true,
+    };
+    let drawings: Vec<&DrawingType> = before.iter().filter(shown).chain([highlight]).collect();
+    let [cx0, cy0, cx1, cy1] = drawings
+        .iter()
+        .filter_map(|d| d.redaction())
+        .fold(bounds, |[cx0, cy0, cx1, cy1], (_, r, _)| {
+            let [rx0, ry0, rx1, ry1] = pixel_bounds(r, image.size);
+            [cx0.min(rx0), cy0.min(ry0), cx1.max(rx1), cy1.max(ry1)]
+        });
+
+    //The copy starts on a whole pixel, so the drawings are rasterized as on the full image
+    let pixels = (cy0..cy1).flat_map(|y| image.pixels[y * image.width() + cx0..y * image.width() + cx1].iter().copied()).collect();
+    let copy = ColorImage { size: [cx1 - cx0, cy1 - cy0], pixels };
+    let offset = vec2(-(cx0 as f32), -(cy0 as f32));
+    let drawings: Vec<DrawingType> = drawings
+        .into_iter()
+        .map(|d| {
+            let mut d = d.clone();
+            d.translate(offset);
+            d
+        })
+        .collect();
+    let rendered = render_drawings(&copy, &drawings)?;
+
+    let pixels = (y0..y1).flat_map(|y| rendered.pixels[(y - cy0) * rendered.width() + x0 - cx0..(y - cy0) * rendered.width() + x1 - cx0].iter().copied()).collect();
+    Some((bounds, ColorImage { size: [x1 - x0, y1 - y0], pixels }))
+}
+
+impl DrawingType {
+    ///Check if the drawing is multiplied on the pixels under it in the exported image
+    pub fn multiplies(&self) -> bool {
+        matches!(self, DrawingType::Highlighter { .. } | DrawingType::Marker { .. })
+    }
+}
+
+///Texture showing a highlight on the canva, identified by the covered pixels and the drawings rendered in them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HighlightKey {
+    bounds: [usize; 4],
+    drawings: u64,
+}
+
+impl KrustyGrab {
+    ///Show the highlight at the given index with the pixels it has in the exported image. The textures are computed once
+    ///and kept while used, until the screenshot changes.
+    pub fn show_highlight(&mut self, ctx: &Context, painter: &Painter, transform: &RectTransform, index: usize) -> Option<HighlightKey> {
+        let size = self.get_temp_image_size()?;
+        let drawings = self.document.drawings().get(..=index)?;
+        let bounds = ctx.fonts(|fonts| pixel_bounds(drawings[index].bounds(fonts).expand(1.0), size));
+        //The texture depends on every drawing under the highlight
+        let mut hasher = DefaultHasher::new();
+        format!("{:?}", drawings).hash(&mut hasher);
+        let key = HighlightKey { bounds, drawings: hasher.finish() };
+
+        if !self.highlight_textures.contains_key(&key) {
+            let drawings = &self.document.drawings()[..=index];
+            let (_, area) = ctx.fonts(|fonts| highlighted_area(self.get_temp_image_ref()?, drawings, fonts))?;
+            let texture = ctx.load_texture("Highlight", area, TextureOptions::LINEAR);
+            self.highlight_textures.insert(key, texture);
+        }
+
+        let [x0, y0, x1, y1] = bounds;
+        let texture = &self.highlight_textures[&key];
+        let pixels = Rect::from_min_max(pos2(x0 as f32, y0 as f32), pos2(x1 as f32, y1 as f32));
+        painter.image(texture.id(), transform.transform_rect(pixels), Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)), Color32::WHITE);
+        Some(key)
+    }
+
+    ///Fit the highlight on the row of text at its height, when the text lines are highlighted
+    pub fn snap_highlight(&self, drawing: DrawingType) -> DrawingType {
+        match drawing {
+            DrawingType::Highlighter { r, s, l } if self.document.tool.highlight_snap => {
+                let band = self.get_temp_image_ref().and_then(|image| text_band(image, [r.min.x, r.max.x], r.center().y));
+                match band {
+                    Some([top, bottom]) => DrawingType::Highlighter { r: Rect::from_x_y_ranges(r.x_range(), top..=bottom), s, l },
+                    None => DrawingType::Highlighter { r, s, l },
+                }
+            },
+            drawing => drawing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{FontDefinitions, Stroke};
+
+    use super::*;
+
+    fn fonts() -> Fonts {
+        Fonts::new(1.0, 1024, FontDefinitions::default())
+    }
+
+    ///White page with a row of glyphs, dark strokes one column out of three, on the given rows
+    fn page(rows: std::ops::Range<usize>) -> ColorImage {
+        let mut image = ColorImage::new([100, 200], Color32::WHITE);
+        for y in rows {
+            for x in (10..60).step_by(3) {
+                image[(x, y)] = Color32::BLACK;
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn canva_highlight_matches_the_export() {
+        let mut image = ColorImage::new([40, 30], Color32::WHITE);
+        for (i, pixel) in image.pixels.iter_mut().enumerate() {
+            let v = (i * 37 % 256) as u8;
+            *pixel = Color32::from_rgb(v, 255 - v, v / 2);
+        }
+        let drawings = [
+            //Under the highlight, and redacted across its border
+            DrawingType::FilledRectangle { r: Rect::from_min_max(pos2(4., 4.), pos2(14., 9.)), s: Stroke::new(1., Color32::BLUE), rounding: 0., l: Default::default() },
+            DrawingType::Pixelate { r: Rect::from_min_max(pos2(20., 2.), pos2(36., 20.)), s: Stroke::new(1., Color32::BLACK) },
+            //Far from the highlight
+            DrawingType::Blur { r: Rect::from_min_max(pos2(0., 24.), pos2(10., 30.)), s: Stroke::new(1., Color32::BLACK) },
+            DrawingType::Highlighter { r: Rect::from_min_max(pos2(2.5, 5.3), pos2(27.2, 12.6)), s: Stroke::new(1., Color32::YELLOW), l: Default::default() },
+        ];
+
+        let exported = render_drawings(&image, &drawings).unwrap();
+        let ([x0, y0, x1, y1], area) = highlighted_area(&image, &drawings, &fonts()).unwrap();
+        assert_eq!(area.size, [x1 - x0, y1 - y0]);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                assert_eq!(area[(x - x0, y - y0)], exported[(x, y)], "pixel {x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn highlight_textures_follow_the_drawings() {
+        let ctx = Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |_| {});
+        let area = Rect::from_min_max(pos2(0., 0.), pos2(20., 20.));
+        let painter = Painter::new(ctx.clone(), egui::LayerId::background(), area);
+        let transform = RectTransform::identity(area);
+
+        let mut app = KrustyGrab::default();
+        app.set_temp_image(Some(ColorImage::new([20, 20], Color32::WHITE)));
+        app.document.push(DrawingType::Highlighter { r: Rect::from_min_max(pos2(2., 2.), pos2(12., 8.)), s: Stroke::new(1., Color32::YELLOW), l: Default::default() });
+        app.show_drawings(&ctx, &painter, &transform);
+        assert_eq!(app.highlight_textures.len(), 1);
+
+        //Moving the highlight replaces its texture
+        app.document.translate(0, vec2(3., 3.));
+        app.show_drawings(&ctx, &painter, &transform);
+        assert_eq!(app.highlight_textures.len(), 1);
+
+        app.set_temp_image(Some(ColorImage::new([20, 20], Color32::BLACK)));
+        assert!(app.highlight_textures.is_empty());
+    }
+
+    #[test]
+    fn highlight_snaps_on_the_text_row() {
+        let image = page(90..100);
+        assert_eq!(text_band(&image, [5., 70.], 95.), Some([88., 102.]));
+        //The pointer can be a bit out of the row
+        assert_eq!(text_band(&image, [5., 70.], 105.), Some([88., 102.]));
+        assert_eq!(text_band(&image, [5., 70.], 120.), None);
+    }
+
+    #[test]
+    fn no_snap_on_a_flat_area() {
+        let image = page(90..100);
+        //Right of the text the page is blank
+        assert_eq!(text_band(&image, [65., 95.], 95.), None);
+        assert_eq!(text_band(&ColorImage::new([100, 200], Color32::GRAY), [0., 100.], 95.), None);
+    }
+
+    #[test]
+    fn no_snap_on_a_picture() {
+        //Every row has contrast, there is no band of text
+        let mut image = ColorImage::new([100, 200], Color32::WHITE);
+        for (i, pixel) in image.pixels.iter_mut().enumerate() {
+            let v = ((i % 100) * 53 % 256) as u8;
+            *pixel = Color32::from_gray(v);
+        }
+        assert_eq!(text_band(&image, [0., 100.], 100.), None);
+    }
+}
@@ -0,0 +1,264 @@
+use std::{collections::VecDeque, mem::size_of};
+
+use egui::{ColorImage, Rect, Stroke, Vec2};
+
+use crate::painting::drawing::DrawingType;
+
+///Memory that the history can use if not configured, in megabytes
+pub const DEFAULT_HISTORY_BUDGET_MB: usize = 256;
+
+///Screenshot shown in the editor, with the selected area and the drawings made on it
+#[derive(Clone)]
+pub struct ImageState {
+    pub image: Option<ColorImage>,
+    pub selected_area: Option<Rect>,
+    pub drawings: Vec<DrawingType>,
+}
+
+///Edit recorded in the history. Every command holds what is needed to both undo and redo it.
+#[derive(Clone)]
+pub enum Command {
+    AddDrawing { index: usize, drawing: DrawingType },
+    DeleteDrawing { index: usize, drawing: DrawingType },
+    MoveDrawing { index: usize, offset: Vec2 },
+    RestyleDrawing { index: usize, before: Stroke, after: Stroke },
+    ///Change of the geometry or content of a drawing, e.g. a text being written
+    ReplaceDrawing { index: usize, before: DrawingType, after: DrawingType },
+    ///Change of the selected area of the screenshot
+    Crop { before: Option<Rect>, after: Option<Rect> },
+    ///A new screenshot or an opened image, replacing the current one with its drawings
+    ReplaceImage { before: Box<ImageState>, after: Box<ImageState> },
+    ///Drawings removed or split by the eraser, applied in order
+    Erase { steps: Vec<Command> },
+}
+
+impl Command {
+    ///Description shown in the history panel
+    pub fn label(&self) -> String {
+        match self {
+            Command::AddDrawing { drawing, .. } => format!("Add {}", drawing.name()),
+            Command::DeleteDrawing { drawing, .. } => format!("Delete {}", drawing.name()),
+            Command::MoveDrawing { .. } => "Move drawing".to_string(),
+            Command::RestyleDrawing { .. } => "Change style".to_string(),
+            Command::ReplaceDrawing { after, .. } => format!("Edit {}", after.name()),
+            Command::Crop { .. } => "Cut screenshot".to_string(),
+            Command::ReplaceImage { .. } => "New image".to_string(),
+            Command::Erase { .. } => "Erase".to_string(),
+        }
+    }
+
+    ///Approximate memory used by the command, in bytes
+    fn size(&self) -> usize {
+        let image_size = |state: &ImageState| {
+            state.image.as_ref().map_or(0, |im| im.pixels.len() * size_of::<egui::Color32>())
+                + state.drawings.iter().map(DrawingType::size).sum::<usize>()
+        };
+
+        size_of::<Self>()
+            + match self {
+                Command::AddDrawing { drawing, .. } | Command::DeleteDrawing { drawing, .. } => drawing.size(),
+                Command::ReplaceDrawing { before, after, .. } => before.size() + after.size(),
+                Command::ReplaceImage { before, after } => image_size(before) + image_size(after),
+                Command::Erase { steps } => steps.iter().map(Command::size).sum(),
+                Command::MoveDrawing { .. } | Command::RestyleDrawing { .. } | Command::Crop { .. } => 0,
+            }
+    }
+
+    ///Merge the following command in this one, if both are steps of the same continuous edit
+    fn merge(&mut self, next: &Command) -> bool {
+        match (self, next) {
+            (Command::MoveDrawing { index, offset }, Command::MoveDrawing { index: next_index, offset: next_offset }) if index == next_index => {
+                *offset += *next_offset;
+                true
+            },
+            (Command::AddDrawing { index, drawing }, Command::ReplaceDrawing { index: next_index, after: next_after, .. }) if index == next_index => {
+                *drawing = next_after.clone();
+                true
+            },
+            (Command::ReplaceDrawing { index, after, .. }, Command::ReplaceDrawing { index: next_index, after: next_after, .. }) if index == next_index => {
+                *after = next_after.clone();
+                true
+            },
+            (Command::RestyleDrawing { index, after, .. }, Command::RestyleDrawing { index: next_index, after: next_after, .. }) if index == next_index => {
+                *after = *next_after;
+                true
+            },
+            (Command::Erase { steps }, Command::Erase { steps: next_steps }) => {
+                steps.extend(next_steps.iter().cloned());
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+///Undo and redo stacks of the commands. The oldest commands are dropped when the memory budget is exceeded.
+pub struct History {
+    done: VecDeque<Command>,
+    undone: Vec<Command>,
+    ///Memory budget in bytes
+    budget: usize,
+    ///Memory used by the done and undone commands, kept updated as they are recorded and dropped
+    size: usize,
+    ///Set when the next command has to start a new entry, even if it could be merged with the last one
+    sealed: bool,
+}
+
+impl History {
+    pub fn new(budget: usize) -> Self {
+        Self { done: VecDeque::new(), undone: Vec::new(), budget, size: 0, sealed: true }
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.trim();
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.budget);
+    }
+
+    ///Record a command already applied. The undone commands can no longer be redone.
+    pub fn push(&mut self, command: Command) {
+        self.size -= self.undone.drain(..).map(|c| c.size()).sum::<usize>();
+        self.size += command.size();
+        self.done.push_back(command);
+        self.sealed = true;
+        self.trim();
+    }
+
+    ///Record a step of a continuous edit (dragging, typing), merged with the previous step of the same edit
+    pub fn push_merging(&mut self, command: Command) {
+        if !self.sealed && self.undone.is_empty() {
+            if let Some(last) = self.done.back_mut() {
+                let before = last.size();
+                if last.merge(&command) {
+                    self.size = self.size - before + last.size();
+                    self.trim();
+                    return;
+                }
+            }
+        }
+        self.push(command);
+        self.sealed = false;
+    }
+
+    ///End the continuous edit in progress, the next steps will be recorded separately
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    ///Move the last command in the undone ones, returning it so that it can be reverted
+    pub fn undo(&mut self) -> Option<&Command> {
+        let command = self.done.pop_back()?;
+        self.undone.push(command);
+        self.sealed = true;
+        self.undone.last()
+    }
+
+    ///Move the last undone command back in the done ones, returning it so that it can be applied again
+    pub fn redo(&mut self) -> Option<&Command> {
+        let command = self.undone.pop()?;
+        self.done.push_back(command);
+        self.sealed = true;
+        self.done.back()
+    }
+
+    ///Drop the next command that can be redone
+    pub fn forget_redo(&mut self) {
+        if let Some(c) = self.undone.pop() {
+            self.size -= c.size();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    ///Commands that can be undone, from the oldest
+    pub fn done(&self) -> impl Iterator<Item = &Command> {
+        self.done.iter()
+    }
+
+    ///Commands that can be redone, from the next one
+    pub fn undone(&self) -> impl Iterator<Item = &Command> {
+        self.undone.iter().rev()
+    }
+
+    ///Drop the oldest commands, then the farthest undone ones, until the budget is respected. The last command is always kept.
+    fn trim(&mut self) {
+        while self.size > self.budget && self.done.len() + self.undone.len() > 1 {
+            let dropped = if self.done.len() > 1 || self.undone.is_empty() {
+                self.done.pop_front()
+            } else {
+                Some(self.undone.remove(0))
+            };
+            self.size -= dropped.map_or(0, |c| c.size());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, vec2, Color32};
+
+    use super::*;
+
+    fn brush(points: usize) -> DrawingType {
+        DrawingType::Brush { points: vec![pos2(0., 0.); points], s: Stroke::new(1., Color32::RED), end: true, l: Default::default() }
+    }
+
+    ///Size of the recorded commands, computed from scratch
+    fn recomputed_size(history: &History) -> usize {
+        history.done().chain(history.undone()).map(Command::size).sum()
+    }
+
+    #[test]
+    fn size_is_kept_updated() {
+        let mut history = History::new(usize::MAX);
+        history.push(Command::AddDrawing { index: 0, drawing: brush(10) });
+        history.push(Command::AddDrawing { index: 1, drawing: brush(100) });
+        assert_eq!(history.size, recomputed_size(&history));
+
+        //Merged steps replace the size of the command they are merged in
+        history.push_merging(Command::AddDrawing { index: 2, drawing: brush(1) });
+        history.push_merging(Command::ReplaceDrawing { index: 2, before: brush(1), after: brush(50) });
+        assert_eq!(history.done().count(), 3);
+        assert_eq!(history.size, recomputed_size(&history));
+
+        //The undone commands count until a new command drops them
+        history.undo();
+        history.undo();
+        assert_eq!(history.size, recomputed_size(&history));
+        history.push(Command::MoveDrawing { index: 0, offset: vec2(1., 1.) });
+        assert_eq!(history.undone().count(), 0);
+        assert_eq!(history.size, recomputed_size(&history));
+
+        //A forgotten command no longer counts
+        history.undo();
+        history.forget_redo();
+        assert_eq!(history.undone().count(), 0);
+        assert_eq!(history.size, recomputed_size(&history));
+    }
+
+    #[test]
+    fn oldest_commands_are_dropped_over_budget() {
+        let command = |index| Command::AddDrawing { index, drawing: brush(100) };
+        let mut history = History::new(command(0).size() * 3);
+        for i in 0..5 {
+            history.push(command(i));
+        }
+        assert_eq!(history.done().count(), 3);
+        assert!(matches!(history.done().next(), Some(Command::AddDrawing { index: 2, .. })));
+        assert_eq!(history.size, recomputed_size(&history));
+
+        //The last command is kept even if it exceeds the budget alone
+        history.set_budget(0);
+        assert_eq!(history.done().count(), 1);
+        assert_eq!(history.size, recomputed_size(&history));
+    }
+}
@@ -14,6 +14,10 @@ pub fn icon_img(name: &str, ctx: &Context) -> TextureId {
                 "highlighter",
                 include_bytes!("./images/highlighter.svg"),
                 egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "marker" => RetainedImage::from_svg_bytes_with_size(
+                "marker",
+                include_bytes!("./images/marker.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
             "circle" => RetainedImage::from_svg_bytes_with_size(
                 "circle",
                 include_bytes!("./images/circle.svg"),
@@ -22,6 +26,14 @@ pub fn icon_img(name: &str, ctx: &Context) -> TextureId {
                 "circle_full",
                 include_bytes!("./images/circle_full.svg"),
                 egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "ellipse" => RetainedImage::from_svg_bytes_with_size(
+                "ellipse",
+                include_bytes!("./images/ellipse.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "ellipse_full" => RetainedImage::from_svg_bytes_with_size(
+                "ellipse_full",
+                include_bytes!("./images/ellipse_full.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
             "rect" => RetainedImage::from_svg_bytes_with_size(
                 "rect",
                 include_bytes!("./images/rect.svg"),
@@ -34,6 +46,22 @@ pub fn icon_img(name: &str, ctx: &Context) -> TextureId {
             "arrow",
             include_bytes!("./images/arrow.svg"),
             egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "line" => RetainedImage::from_svg_bytes_with_size(
+                "line",
+                include_bytes!("./images/line.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "polyline" => RetainedImage::from_svg_bytes_with_size(
+                "polyline",
+                include_bytes!("./images/polyline.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "polygon" => RetainedImage::from_svg_bytes_with_size(
+                "polygon",
+                include_bytes!("./images/polygon.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "polygon_full" => RetainedImage::from_svg_bytes_with_size(
+                "polygon_full",
+                include_bytes!("./images/polygon_full.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
             "text" => RetainedImage::from_svg_bytes_with_size(
                 "text",
                 include_bytes!("./images/text.svg"),
@@ -58,6 +86,26 @@ pub fn icon_img(name: &str, ctx: &Context) -> TextureId {
                 "select",
                 include_bytes!("./images/select.svg"),
                 egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "pointer" => RetainedImage::from_svg_bytes_with_size(
+                "pointer",
+                include_bytes!("./images/pointer.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "redact" => RetainedImage::from_svg_bytes_with_size(
+                "redact",
+                include_bytes!("./images/redact.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "step" => RetainedImage::from_svg_bytes_with_size(
+                "step",
+                include_bytes!("./images/step.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "eyedropper" => RetainedImage::from_svg_bytes_with_size(
+                "eyedropper",
+                include_bytes!("./images/eyedropper.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "eraser" => RetainedImage::from_svg_bytes_with_size(
+                "eraser",
+                include_bytes!("./images/eraser.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
             "cut" => RetainedImage::from_svg_bytes_with_size(
                 "cut",
                 include_bytes!("./images/cut.svg"),
@@ -89,6 +137,10 @@ pub fn icon_img(name: &str, ctx: &Context) -> TextureId {
                 "highlighter",
                 include_bytes!("./images/highlighter_light.svg"),
                 egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "marker" => RetainedImage::from_svg_bytes_with_size(
+                "marker",
+                include_bytes!("./images/marker_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
             "circle" => RetainedImage::from_svg_bytes_with_size(
                 "circle",
                 include_bytes!("./images/circle_light.svg"),
@@ -97,6 +149,14 @@ pub fn icon_img(name: &str, ctx: &Context) -> TextureId {
                 "circle_full",
                 include_bytes!("./images/circle_full_light.svg"),
                 egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "ellipse" => RetainedImage::from_svg_bytes_with_size(
+                "ellipse",
+                include_bytes!("./images/ellipse_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "ellipse_full" => RetainedImage::from_svg_bytes_with_size(
+                "ellipse_full",
+                include_bytes!("./images/ellipse_full_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
             "rect" => RetainedImage::from_svg_bytes_with_size(
                 "rect",
                 include_bytes!("./images/rect_light.svg"),
@@ -109,6 +169,22 @@ pub fn icon_img(name: &str, ctx: &Context) -> TextureId {
                 "arrow",
                 include_bytes!("./images/arrow_light.svg"),
                 egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "line" => RetainedImage::from_svg_bytes_with_size(
+                "line",
+                include_bytes!("./images/line_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "polyline" => RetainedImage::from_svg_bytes_with_size(
+                "polyline",
+                include_bytes!("./images/polyline_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "polygon" => RetainedImage::from_svg_bytes_with_size(
+                "polygon",
+                include_bytes!("./images/polygon_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "polygon_full" => RetainedImage::from_svg_bytes_with_size(
+                "polygon_full",
+                include_bytes!("./images/polygon_full_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
             "text" => RetainedImage::from_svg_bytes_with_size(
                 "text",
                 include_bytes!("./images/text_light.svg"),
@@ -133,6 +209,26 @@ pub fn icon_img(name: &str, ctx: &Context) -> TextureId {
                 "select",
                 include_bytes!("./images/select_light.svg"),
                 egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "pointer" => RetainedImage::from_svg_bytes_with_size(
+                "pointer",
+                include_bytes!("./images/pointer_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "redact" => RetainedImage::from_svg_bytes_with_size(
+                "redact",
+                include_bytes!("./images/redact_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "step" => RetainedImage::from_svg_bytes_with_size(
+                "step",
+                include_bytes!("./images/step_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "eyedropper" => RetainedImage::from_svg_bytes_with_size(
+                "eyedropper",
+                include_bytes!("./images/eyedropper_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
+            "eraser" => RetainedImage::from_svg_bytes_with_size(
+                "eraser",
+                include_bytes!("./images/eraser_light.svg"),
+                egui_extras::image::FitTo::Original).unwrap().texture_id(ctx),
             "cut" => RetainedImage::from_svg_bytes_with_size(
                 "cut",
                 include_bytes!("./images/cut_light.svg"),
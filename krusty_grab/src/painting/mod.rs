@@ -1,2 +1,19 @@
+pub mod arrow;
+pub mod document;
 pub mod drawing;
-pub mod icons;
\ No newline at end of file
+pub mod eraser;
+pub mod eyedropper;
+pub mod highlight;
+pub mod history;
+pub mod icons;
+pub mod palette;
+pub mod path;
+pub mod rasterizer;
+pub mod redaction;
+pub mod selection;
+pub mod shapes;
+pub mod smoothing;
+pub mod steps;
+pub mod style;
+pub mod text;
+pub mod project;
@@ -0,0 +1,171 @@
+use egui::{vec2, Button, Color32, Context, CursorIcon, Rgba, Stroke, Ui, Widget};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    krustygrab::{KrustyGrab, KrustyGrabConfig},
+    painting::{document::{DrawingMode, ToolState}, eraser::EraserMode, eyedropper::hex},
+};
+
+///Colors kept in the recent row
+const RECENT_COLORS: usize = 6;
+///Side of the color swatches in the toolbar
+const SWATCH_SIZE: f32 = 16.0;
+
+///Tool settings kept between the sessions, stored with the preferences
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ToolSettings {
+    ///Swatches added by the user
+    pub palette: Vec<Color32>,
+    ///Last colors chosen, from the newest
+    pub recent: Vec<Color32>,
+    pub color: Color32,
+    pub thickness: f32,
+    pub mode: DrawingMode,
+    pub eraser: EraserMode,
+}
+
+impl Default for ToolSettings {
+    fn default() -> Self {
+        let tool = ToolState::default();
+        Self {
+            palette: vec![Color32::RED, Color32::YELLOW, Color32::GREEN, Color32::BLUE, Color32::BLACK, Color32::WHITE],
+            recent: Vec::new(),
+            color: tool.color.into(),
+            thickness: tool.thickness,
+            mode: tool.mode,
+            eraser: tool.eraser,
+        }
+    }
+}
+
+impl ToolSettings {
+    ///Resume the tool as it was left
+    pub fn restore(&self, tool: &mut ToolState) {
+        tool.color = Rgba::from(self.color);
+        tool.thickness = self.thickness;
+        tool.mode = self.mode;
+        tool.eraser = self.eraser;
+    }
+}
+
+///Button filled with the color, outlined when it is the active one
+fn swatch(ui: &mut Ui, color: Color32, active: bool) -> egui::Response {
+    let outline = if active { Stroke::new(2.0, ui.visuals().selection.stroke.color) } else { Stroke::new(1.0, Color32::GRAY) };
+    Button::new("")
+        .fill(color)
+        .stroke(outline)
+        .min_size(vec2(SWATCH_SIZE, SWATCH_SIZE))
+        .ui(ui)
+        .on_hover_cursor(CursorIcon::PointingHand)
+}
+
+impl KrustyGrab {
+    ///Swatches of the palette and of the recent colors. A click makes the color the active one, a right click removes a swatch from the palette.
+    pub fn palette_options(&mut self, ui: &mut Ui) {
+        //The color of the selected drawing is shown and changed instead of the tool one
+        let (color, thickness) = self.shown_style();
+        let active = Color32::from(color);
+        let mut picked = None;
+        let mut removed = None;
+
+        for (i, color) in self.config.tools.palette.iter().enumerate() {
+            let response = swatch(ui, *color, *color == active).on_hover_text_at_pointer(format!("{}, right click to remove it", hex(*color)));
+            if response.clicked() {
+                picked = Some(*color);
+            }
+            if response.secondary_clicked() {
+                removed = Some(i);
+            }
+        }
+
+        let add = ui
+            .add_enabled(!self.config.tools.palette.contains(&active), Button::new("+").min_size(vec2(SWATCH_SIZE, SWATCH_SIZE)))
+            .on_hover_cursor(CursorIcon::PointingHand)
+            .on_hover_text_at_pointer("Add the color to the palette");
+        if add.clicked() {
+            self.config.tools.palette.push(active);
+            tracing::info!("Color {} added to the palette", hex(active));
+        }
+        if let Some(i) = removed {
+            let color = self.config.tools.palette.remove(i);
+            tracing::info!("Color {} removed from the palette", hex(color));
+        }
+
+        if !self.config.tools.recent.is_empty() {
+            ui.separator();
+        }
+        for color in &self.config.tools.recent {
+            if swatch(ui, *color, false).on_hover_text_at_pointer(hex(*color)).clicked() {
+                picked = Some(*color);
+            }
+        }
+
+        if let Some(color) = picked {
+            tracing::info!("Color changed to {}", hex(color));
+            self.set_style(Rgba::from(color), thickness);
+        }
+        if add.clicked() || removed.is_some() {
+            KrustyGrabConfig::store_tools(&self.config.tools);
+        }
+    }
+
+    ///Keep the color, thickness and tool in use, adding the color to the recent ones once it is no longer being changed.
+    ///Return true if they changed, so that they can be stored.
+    pub fn remember_tools(&mut self, ctx: &Context) -> bool {
+        if ctx.input(|i| i.pointer.any_down()) || ctx.memory(|mem| mem.any_popup_open()) {
+            return false;
+        }
+
+        let tool = &self.document.tool;
+        let tools = &mut self.config.tools;
+        let before = tools.clone();
+        (tools.color, tools.thickness, tools.mode, tools.eraser) = (tool.color.into(), tool.thickness, tool.mode, tool.eraser);
+        if tools.recent.first() != Some(&tools.color) {
+            let color = tools.color;
+            tools.recent.retain(|c| *c != color);
+            tools.recent.insert(0, color);
+            tools.recent.truncate(RECENT_COLORS);
+        }
+        *tools != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::RawInput;
+
+    use super::*;
+
+    #[test]
+    fn recent_colors_start_from_the_newest() {
+        let ctx = Context::default();
+        let _ = ctx.run(RawInput::default(), |_| {});
+        let mut app = KrustyGrab::default();
+
+        let colors = [Color32::RED, Color32::BLUE, Color32::RED, Color32::GOLD, Color32::KHAKI, Color32::BROWN, Color32::GRAY, Color32::WHITE];
+        for color in colors {
+            app.document.tool.color = Rgba::from(color);
+            assert!(app.remember_tools(&ctx));
+            //Nothing to store until the tool changes again
+            assert!(!app.remember_tools(&ctx));
+        }
+        assert_eq!(app.config.tools.recent, [Color32::WHITE, Color32::GRAY, Color32::BROWN, Color32::KHAKI, Color32::GOLD, Color32::RED]);
+        assert_eq!(app.config.tools.color, Color32::WHITE);
+    }
+
+    #[test]
+    fn tool_is_restored() {
+        let ctx = Context::default();
+        let _ = ctx.run(RawInput::default(), |_| {});
+        let mut app = KrustyGrab::default();
+        app.document.tool.thickness = 4.;
+        app.document.tool.mode = DrawingMode::Eraser;
+        app.document.tool.eraser = EraserMode::Stroke;
+        app.remember_tools(&ctx);
+
+        let mut tool = ToolState::default();
+        app.config.tools.restore(&mut tool);
+        assert_eq!((tool.thickness, tool.mode, tool.eraser), (4., DrawingMode::Eraser, EraserMode::Stroke));
+    }
+}
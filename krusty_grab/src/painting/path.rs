@@ -0,0 +1,208 @@
+use egui::{emath::RectTransform, epaint::Mesh, Color32, Context, Key, Painter, PointerButton, Pos2, Shape, Stroke, Vec2};
+
+use crate::{
+    krustygrab::KrustyGrab,
+    painting::document::DrawingMode,
+};
+
+///Step of the angles the lines snap to while Shift is held, in degrees
+pub const SNAP_ANGLE: f32 = 15.0;
+
+///Move `pos` on the closest direction from `origin` multiple of the snap angle, keeping its distance
+pub fn snap_angle(origin: Pos2, pos: Pos2) -> Pos2 {
+    let offset = pos - origin;
+    let step = SNAP_ANGLE.to_radians();
+    let angle = (offset.angle() / step).round() * step;
+    origin + Vec2::angled(angle) * offset.length()
+}
+
+///Signed area of the polygon, positive when its points go clockwise on the screen
+fn signed_area(points: &[Pos2]) -> f32 {
+    let n = points.len();
+    (0..n).map(|i| points[i].x * points[(i + 1) % n].y - points[(i + 1) % n].x * points[i].y).sum::<f32>() / 2.
+}
+
+fn cross(o: Pos2, a: Pos2, b: Pos2) -> f32 {
+    (a - o).x * (b - o).y - (a - o).y * (b - o).x
+}
+
+///Check if the point is inside the polygon, with the even-odd rule
+pub fn polygon_contains(points: &[Pos2], pos: Pos2) -> bool {
+    let n = points.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + n - 1) % n]);
+        if (a.y > pos.y) != (b.y > pos.y) && pos.x < a.x + (pos.y - a.y) * (b.x - a.x) / (b.y - a.y) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+///Split the polygon in triangles by ear clipping, so that also the concave polygons can be filled.
+///The points of a self intersecting polygon that can not be clipped are joined in a fan.
+fn triangulate(points: &[Pos2]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if signed_area(points) < 0. {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (points[remaining[(i + n - 1) % n]], points[remaining[i]], points[remaining[(i + 1) % n]]);
+            //The vertex must be convex, with no other vertex inside its triangle
+            cross(a, b, c) > 0.
+                && remaining.iter().map(|&j| points[j]).filter(|p| *p != a && *p != b && *p != c).all(|p| {
+                    !(cross(a, b, p) >= 0. && cross(b, c, p) >= 0. && cross(c, a, p) >= 0.)
+                })
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]]);
+                remaining.remove(i);
+            },
+            None => break,
+        }
+    }
+
+    triangles.extend((1..remaining.len().saturating_sub(1)).map(|i| [remaining[0], remaining[i], remaining[i + 1]]));
+    triangles
+}
+
+///Polygon filled with the color, given in the coordinates of the painting area
+pub fn filled_polygon(points: Vec<Pos2>, color: Color32) -> Shape {
+    let mut mesh = Mesh::default();
+    for triangle in triangulate(&points) {
+        for i in triangle {
+            mesh.colored_vertex(points[i], color);
+        }
+    }
+    mesh.indices = (0..mesh.vertices.len() as u32).collect();
+    Shape::mesh(mesh)
+}
+
+impl KrustyGrab {
+    ///Manage the polyline and polygon tools while the pointer is on the canva: every click adds a point,
+    ///a double click or Enter completes the drawing and Esc discards it. A polygon is also closed clicking on its first point.
+    ///`mouse` is the pointer position in screenshot pixels, `clicked` tells if the canva has been clicked.
+    pub fn path_on_canva(&mut self, ctx: &Context, painter: &Painter, transform: &RectTransform, mouse: Pos2, clicked: bool) {
+        let mode = self.document.tool.mode;
+        let pos = match self.document.path_points().last() {
+            Some(last) if ctx.input(|i| i.modifiers.shift) => snap_angle(*last, mouse),
+            _ => mouse,
+        };
+        let (double_clicked, enter, escape) =
+            ctx.input(|i| (i.pointer.button_double_clicked(PointerButton::Primary), i.key_pressed(Key::Enter), i.key_pressed(Key::Escape)));
+
+        if escape {
+            self.document.cancel_path();
+            return;
+        }
+        if enter {
+            self.document.end_path();
+            return;
+        }
+
+        if clicked {
+            let tolerance = KrustyGrab::SELECT_TOLERANCE / transform.scale().x;
+            let closing = mode != DrawingMode::Polyline
+                && self.document.path_points().len() > 2
+                && self.document.path_points()[0].distance(mouse) <= tolerance;
+
+            //The first click of a double click already added the point
+            if double_clicked || closing {
+                self.document.end_path();
+                return;
+            }
+            self.document.add_path_point(pos);
+        }
+
+        if let Some(preview) = self.document.path_drawing(Some(pos)) {
+            let shapes = ctx.fonts(|fonts| preview.to_shapes(transform, fonts));
+            painter.extend(shapes);
+        }
+        for point in self.document.path_points() {
+            painter.circle(transform.transform_pos(*point), 3., Color32::WHITE, Stroke::new(1., Color32::BLACK));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+
+    use super::*;
+
+    ///Total area of the triangles
+    fn area(points: &[Pos2], triangles: &[[usize; 3]]) -> f32 {
+        triangles.iter().map(|[a, b, c]| cross(points[*a], points[*b], points[*c]).abs() / 2.).sum()
+    }
+
+    #[test]
+    fn concave_polygon_is_covered_by_its_triangles() {
+        //L shape, with the notch in the top right corner
+        let points = [pos2(0., 0.), pos2(10., 0.), pos2(10., 20.), pos2(20., 20.), pos2(20., 30.), pos2(0., 30.)];
+        let triangles = triangulate(&points);
+
+        assert_eq!(triangles.len(), points.len() - 2);
+        assert_eq!(area(&points, &triangles), signed_area(&points).abs());
+        for [a, b, c] in &triangles {
+            let centroid = pos2((points[*a].x + points[*b].x + points[*c].x) / 3., (points[*a].y + points[*b].y + points[*c].y) / 3.);
+            assert!(polygon_contains(&points, centroid));
+        }
+
+        //Same result with the points in the opposite order
+        let mut reversed = points;
+        reversed.reverse();
+        assert_eq!(area(&reversed, &triangulate(&reversed)), signed_area(&points).abs());
+    }
+
+    #[test]
+    fn collinear_points_do_not_add_area() {
+        let square = [pos2(0., 0.), pos2(5., 0.), pos2(10., 0.), pos2(10., 10.), pos2(0., 10.)];
+        assert_eq!(area(&square, &triangulate(&square)), 100.);
+
+        let line = [pos2(0., 0.), pos2(5., 5.), pos2(10., 10.), pos2(20., 20.)];
+        assert_eq!(area(&line, &triangulate(&line)), 0.);
+    }
+
+    #[test]
+    fn degenerate_polygons_are_triangulated() {
+        assert!(triangulate(&[]).is_empty());
+        assert!(triangulate(&[pos2(1., 1.)]).is_empty());
+        assert!(triangulate(&[pos2(1., 1.), pos2(2., 2.)]).is_empty());
+
+        //Repeated points and a self intersecting bow tie end in a fan
+        let repeated = [pos2(0., 0.), pos2(0., 0.), pos2(10., 0.), pos2(10., 0.), pos2(0., 10.)];
+        assert_eq!(triangulate(&repeated).len(), 3);
+        let bow_tie = [pos2(0., 0.), pos2(10., 10.), pos2(10., 0.), pos2(0., 10.)];
+        assert_eq!(triangulate(&bow_tie).len(), 2);
+    }
+
+    #[test]
+    fn points_in_the_notch_are_outside() {
+        let points = [pos2(0., 0.), pos2(10., 0.), pos2(10., 20.), pos2(20., 20.), pos2(20., 30.), pos2(0., 30.)];
+        assert!(polygon_contains(&points, pos2(5., 5.)));
+        assert!(polygon_contains(&points, pos2(15., 25.)));
+        assert!(!polygon_contains(&points, pos2(15., 5.)));
+        assert!(!polygon_contains(&points, pos2(-1., 5.)));
+        assert!(!polygon_contains(&[], pos2(0., 0.)));
+    }
+
+    #[test]
+    fn snap_keeps_the_distance() {
+        let origin = pos2(10., 10.);
+        let snapped = snap_angle(origin, pos2(20., 11.));
+        assert!((snapped - pos2(20., 10.)).length() < 0.1);
+        assert!(((snapped - origin).length() - (pos2(20., 11.) - origin).length()).abs() < 1e-4);
+
+        //Near the diagonal the line snaps to 45 degrees
+        let snapped = snap_angle(origin, pos2(20., 19.));
+        assert!((snapped.x - snapped.y).abs() < 1e-4);
+
+        assert_eq!(snap_angle(origin, origin), origin);
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Cursor, Read, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context as _, Error};
+use egui::{vec2, ColorImage, Pos2, Rect};
+use image::{ImageOutputFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    krustygrab::KrustyGrab,
+    painting::drawing::DrawingType,
+};
+
+///Extension of the project files
+pub const PROJECT_EXTENSION: &str = "kgrab";
+
+///First bytes of every project file, followed by the version of the format
+const MAGIC: &[u8; 5] = b"KGRAB";
+///Version of the format, raised whenever the header or the saved annotations change, e.g. when a drawing gains a field
+const VERSION: u8 = 2;
+
+///Annotations of a project, stored as RON after the image
+#[derive(Serialize, Deserialize)]
+struct ProjectDocument {
+    drawings: Vec<DrawingType>,
+    ///Drawings that can be redone, the next one first
+    redo_list: Vec<DrawingType>,
+    selected_area: Option<Rect>,
+}
+
+///Screenshot with its annotations, kept editable.
+///The file contains the magic and version, the length of the PNG encoded image, the image itself and the RON document of the annotations.
+pub struct Project {
+    pub image: ColorImage,
+    pub drawings: Vec<DrawingType>,
+    ///Drawings that can be redone, the next one first
+    pub redo_list: Vec<DrawingType>,
+    pub selected_area: Option<Rect>,
+}
+
+impl Project {
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        //The PNG holds unmultiplied colors, so the image can be opened by any viewer
+        let raw: Vec<u8> = self.image.pixels.iter().flat_map(|p| p.to_srgba_unmultiplied()).collect();
+        let image = RgbaImage::from_raw(self.image.width() as u32, self.image.height() as u32, raw)
+            .expect("ColorImage buffer always matches its size");
+        let mut png = Cursor::new(Vec::new());
+        image.write_to(&mut png, ImageOutputFormat::Png)?;
+        let png = png.into_inner();
+
+        let document = ProjectDocument {
+            drawings: self.drawings.clone(),
+            redo_list: self.redo_list.clone(),
+            selected_area: self.selected_area,
+        };
+        let document = ron::to_string(&document)?;
+
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&[VERSION])?;
+        file.write_all(&(png.len() as u64).to_le_bytes())?;
+        file.write_all(&png)?;
+        file.write_all(document.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+
+        let mut header = [0u8; 6];
+        file.read_exact(&mut header).context("The file is too short to be a project")?;
+        if &header[..5] != MAGIC {
+            bail!("Not a KrustyGrab project");
+        }
+        if header[5] != VERSION {
+            bail!("Unsupported project version {}", header[5]);
+        }
+
+        let mut length = [0u8; 8];
+        file.read_exact(&mut length)?;
+        //The length is checked against the rest of the file before allocating the image
+        let length = u64::from_le_bytes(length);
+        if length > size - (header.len() + 8) as u64 {
+            bail!("The image of the project is truncated");
+        }
+        let mut png = vec![0u8; length as usize];
+        file.read_exact(&mut png).context("The image of the project is truncated")?;
+        let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)?.into_rgba8();
+        let image = ColorImage::from_rgba_unmultiplied([image.width() as usize, image.height() as usize], image.as_raw());
+
+        let mut document = String::new();
+        file.read_to_string(&mut document)?;
+        let document: ProjectDocument = ron::from_str(&document).context("Invalid annotations")?;
+        if let Some(area) = document.selected_area {
+            //The area is used to cut the image, it must lie inside it
+            let inside = Rect::from_min_size(Pos2::ZERO, vec2(image.width() as f32, image.height() as f32));
+            if !area.is_finite() || area.is_negative() || !inside.contains_rect(area) {
+                bail!("The selected area of the project is out of the image");
+            }
+        }
+
+        Ok(Self {
+            image,
+            drawings: document.drawings,
+            redo_list: document.redo_list,
+            selected_area: document.selected_area,
+        })
+    }
+}
+
+impl KrustyGrab {
+    ///Save the current screenshot and its annotations in a project file
+    pub fn save_project(&self, path: &Path) -> Result<(), Error> {
+        Project {
+            image: self.get_temp_image().context("There is no screenshot to save")?,
+            drawings: self.document.drawings().to_vec(),
+            redo_list: self.document.redo_drawings(),
+            selected_area: self.get_selected_area(),
+        }
+        .save(path)
+    }
+
+    ///Replace the current screenshot and annotations with the ones of a project file
+    pub fn open_project(&mut self, path: &Path) -> Result<(), Error> {
+        let project = Project::load(path)?;
+
+        //The shown screenshot is cut to the saved area, as the drawings are placed relative to it
+        self.set_temp_image(Some(project.image));
+        self.show_area(project.selected_area);
+
+        self.document.replace(project.drawings, project.redo_list);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use egui::{pos2, vec2, Color32, Stroke};
+
+    use crate::painting::text::TextOptions;
+
+    use super::*;
+
+    ///Project file in the temporary folder, unique for the test process
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("krustygrab_{}_{}.{}", name, std::process::id(), PROJECT_EXTENSION))
+    }
+
+    #[test]
+    fn project_round_trip() {
+        let mut image = ColorImage::new([7, 5], Color32::from_rgb(10, 20, 30));
+        image[(3, 2)] = Color32::GREEN;
+        let project = Project {
+            image,
+            drawings: vec![
+                DrawingType::Brush { points: vec![pos2(1., 1.), pos2(4., 3.)], s: Stroke::new(2., Color32::RED), end: true, l: Default::default() },
+                DrawingType::Text { p: pos2(2., 2.), t: "Note".to_string(), s: Stroke::new(1., Color32::BLACK), o: TextOptions::default() },
+            ],
+            redo_list: vec![DrawingType::Circle { c: pos2(3., 3.), r: 2., s: Stroke::new(1., Color32::BLUE), l: Default::default() }],
+            selected_area: Some(Rect::from_min_max(pos2(1., 1.), pos2(6., 4.))),
+        };
+        let path = temp_path("round_trip");
+        project.save(&path).unwrap();
+
+        let loaded = Project::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.image == project.image);
+        assert_eq!(format!("{:?}", loaded.drawings), format!("{:?}", project.drawings));
+        assert_eq!(format!("{:?}", loaded.redo_list), format!("{:?}", project.redo_list));
+        assert_eq!(loaded.selected_area, project.selected_area);
+    }
+
+    #[test]
+    fn image_is_stored_as_a_plain_png() {
+        let path = temp_path("plain_png");
+        let image = ColorImage::new([2, 1], Color32::from_rgba_unmultiplied(200, 100, 50, 128));
+        Project { image, drawings: Vec::new(), redo_list: Vec::new(), selected_area: None }.save(&path).unwrap();
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let length = u64::from_le_bytes(content[MAGIC.len() + 1..MAGIC.len() + 9].try_into().unwrap()) as usize;
+        let png = image::load_from_memory(&content[MAGIC.len() + 9..MAGIC.len() + 9 + length]).unwrap().into_rgba8();
+        let pixel = png.get_pixel(0, 0).0;
+        assert_eq!(pixel[3], 128);
+        assert!(pixel[0].abs_diff(200) <= 1 && pixel[1].abs_diff(100) <= 1 && pixel[2].abs_diff(50) <= 1, "{pixel:?}");
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let path = temp_path("version");
+        Project { image: ColorImage::new([2, 2], Color32::WHITE), drawings: Vec::new(), redo_list: Vec::new(), selected_area: None }.save(&path).unwrap();
+        let mut content = std::fs::read(&path).unwrap();
+        assert_eq!(content[MAGIC.len()], VERSION);
+
+        content[MAGIC.len()] = VERSION + 1;
+        std::fs::write(&path, &content).unwrap();
+        let newer = Project::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(newer.is_err());
+    }
+
+    #[test]
+    fn areas_out_of_the_image_are_rejected() {
+        let path = temp_path("area");
+        for area in [
+            Rect::from_min_max(pos2(1., 1.), pos2(9., 4.)),
+            Rect::from_min_max(pos2(-1., 0.), pos2(3., 3.)),
+            Rect::from_min_max(pos2(3., 3.), pos2(1., 1.)),
+            Rect::from_min_max(pos2(0., 0.), pos2(f32::NAN, 2.)),
+        ] {
+            Project { image: ColorImage::new([6, 5], Color32::WHITE), drawings: Vec::new(), redo_list: Vec::new(), selected_area: Some(area) }.save(&path).unwrap();
+            assert!(Project::load(&path).is_err(), "{area:?}");
+        }
+
+        //The whole image is a valid area
+        let area = Rect::from_min_max(Pos2::ZERO, pos2(6., 5.));
+        Project { image: ColorImage::new([6, 5], Color32::WHITE), drawings: Vec::new(), redo_list: Vec::new(), selected_area: Some(area) }.save(&path).unwrap();
+        let loaded = Project::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().selected_area, Some(area));
+    }
+
+    #[test]
+    fn oversized_image_length_is_an_error() {
+        let path = temp_path("oversized");
+        let mut content = MAGIC.to_vec();
+        content.push(VERSION);
+        content.extend_from_slice(&u64::MAX.to_le_bytes());
+        content.extend_from_slice(b"not an image");
+        std::fs::write(&path, content).unwrap();
+
+        let loaded = Project::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+
+    #[test]
+    fn reopened_crop_keeps_drawings_in_place() {
+        //Screenshot with a marked pixel, covered by a drawing and saved cropped
+        let mut image = ColorImage::new([64, 48], Color32::WHITE);
+        image[(30, 20)] = Color32::RED;
+        let mark = pos2(30.5, 20.5);
+        let area = Rect::from_min_max(pos2(10., 8.), pos2(50., 40.));
+
+        let mut app = KrustyGrab::default();
+        app.set_temp_image(Some(image));
+        app.set_select_area(Some(area));
+        app.document.push(DrawingType::FilledRectangle {
+            r: Rect::from_center_size(mark, vec2(3., 3.)),
+            s: Stroke::new(1., Color32::BLUE),
+            rounding: 0.,
+            l: Default::default(),
+        });
+        let path = temp_path("crop");
+        app.save_project(&path).unwrap();
+
+        let mut reopened = KrustyGrab::default();
+        reopened.open_project(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        //The canva shows only the saved area
+        let screen = reopened.screen.clone().unwrap();
+        assert_eq!(screen.size, [40, 32]);
+
+        //The drawing is shown on the marked pixel
+        let shown = reopened.canvas_transform().transform_pos(mark);
+        let pixel = (shown.x as usize, shown.y as usize);
+        assert_eq!(screen[pixel], Color32::RED);
+
+        //The export matches the canva
+        let exported = reopened.annotated_image().unwrap();
+        assert_eq!(exported.size, screen.size);
+        assert_eq!(exported[pixel], Color32::BLUE);
+    }
+}
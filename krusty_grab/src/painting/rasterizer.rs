@@ -0,0 +1,339 @@
+use egui::{
+    emath::RectTransform,
+    epaint::{
+        image::ImageData,
+        text::{FontDefinitions, Fonts},
+        Mesh, TessellationOptions, Tessellator, Vertex,
+    },
+    pos2, vec2, Color32, ColorImage, Rect, Shape,
+};
+
+use crate::krustygrab::KrustyGrab;
+use crate::painting::{drawing::DrawingType, highlight::multiply, redaction::redacted_area};
+
+///Largest font atlas side used for the offscreen rendering
+const MAX_TEXTURE_SIDE: usize = 8192;
+
+///Texture sampled by the tessellated meshes. Solid shapes use its white top left pixel, text uses the glyphs.
+struct FontTexture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+}
+
+impl FontTexture {
+    ///Bilinear sample at normalized coordinates, as done by the GPU with linear filtering
+    fn sample(&self, uv: egui::Pos2) -> [f32; 4] {
+        let [w, h] = self.size;
+        let x = (uv.x * w as f32 - 0.5).clamp(0.0, (w - 1) as f32);
+        let y = (uv.y * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+        let texel = |x: usize, y: usize| self.pixels[y * w + x].to_array().map(|c| c as f32);
+        let (p00, p10, p01, p11) = (texel(x0, y0), texel(x1, y0), texel(x0, y1), texel(x1, y1));
+
+        let mut out = [0.0; 4];
+        for i in 0..4 {
+            let top = p00[i] + (p10[i] - p00[i]) * fx;
+            let bottom = p01[i] + (p11[i] - p01[i]) * fx;
+            out[i] = (top + (bottom - top) * fy) / 255.0;
+        }
+        out
+    }
+}
+
+///Render the drawings on a copy of the image at its native resolution.
+///Drawings coordinates are expected to be expressed in image pixels.
+///The redactions replace the pixels rendered so far with the redacted screenshot, as shown on the canva, the drawings made after them are painted on top.
+///Return None if the font atlas could not provide the texture used by the meshes.
+pub fn render_drawings(image: &ColorImage, drawings: &[DrawingType]) -> Option<ColorImage> {
+    let mut output = image.clone();
+    if drawings.is_empty() {
+        return Some(output);
+    }
+
+    let fonts = Fonts::new(1.0, MAX_TEXTURE_SIDE, FontDefinitions::default());
+    let image_rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(image.width() as f32, image.height() as f32));
+    let transform = RectTransform::identity(image_rect);
+
+    let shapes: Vec<Vec<Shape>> = drawings
+        .iter()
+        .map(|d| d.to_shapes(&transform, &fonts))
+        .collect();
+
+    //The atlas is fresh, so its first delta contains the whole font image, glyphs laid out above included
+    let texture = match fonts.font_image_delta().map(|delta| delta.image) {
+        Some(ImageData::Font(font_image)) => FontTexture {
+            size: font_image.size,
+            pixels: font_image.srgba_pixels(None).collect(),
+        },
+        _ => {
+            tracing::error!("The font atlas has no image to render the drawings");
+            return None;
+        },
+    };
+
+    let prepared_discs = fonts.texture_atlas().lock().prepared_discs();
+    let mut tessellator = Tessellator::new(1.0, TessellationOptions::default(), texture.size, prepared_discs);
+    tessellator.set_clip_rect(image_rect);
+
+    for (drawing, shapes) in drawings.iter().zip(shapes) {
+        if let Some((redaction, rect, strength)) = drawing.redaction() {
+            if let Some(([x0, y0, x1, _], area)) = redacted_area(image, rect, redaction, strength) {
+                for (y, row) in area.pixels.chunks_exact(x1 - x0).enumerate() {
+                    let start = (y0 + y) * output.width() + x0;
+                    output.pixels[start..start + row.len()].copy_from_slice(row);
+                }
+            }
+        }
+
+        let blend = if drawing.multiplies() { multiply } else { over };
+        for shape in shapes {
+            let mut mesh = Mesh::default();
+            tessellator.tessellate_shape(shape, &mut mesh);
+            rasterize_mesh(&mut output, &mesh, &texture, blend);
+        }
+    }
+
+    Some(output)
+}
+
+///Blend of a premultiplied color on a pixel
+type Blend = fn(dst: [f32; 4], src: [f32; 4]) -> [f32; 4];
+
+///Premultiplied alpha blending, the one used by egui
+fn over(dst: [f32; 4], src: [f32; 4]) -> [f32; 4] {
+    std::array::from_fn(|i| src[i] + dst[i] * (1.0 - src[3]))
+}
+
+///Fill the triangles of the mesh on the image, blending them on the pixels
+fn rasterize_mesh(image: &mut ColorImage, mesh: &Mesh, texture: &FontTexture, blend: Blend) {
+    for triangle in mesh.indices.chunks_exact(3) {
+        let a = &mesh.vertices[triangle[0] as usize];
+        let b = &mesh.vertices[triangle[1] as usize];
+        let c = &mesh.vertices[triangle[2] as usize];
+        rasterize_triangle(image, [a, b, c], texture, blend);
+    }
+}
+
+fn rasterize_triangle(image: &mut ColorImage, [a, b, c]: [&Vertex; 3], texture: &FontTexture, blend: Blend) {
+    let area = edge(a.pos, b.pos, c.pos);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+
+    let [width, height] = image.size;
+    let min_x = a.pos.x.min(b.pos.x).min(c.pos.x).floor().max(0.0) as usize;
+    let min_y = a.pos.y.min(b.pos.y).min(c.pos.y).floor().max(0.0) as usize;
+    let max_x = (a.pos.x.max(b.pos.x).max(c.pos.x).ceil().max(0.0) as usize).min(width);
+    let max_y = (a.pos.y.max(b.pos.y).max(c.pos.y).ceil().max(0.0) as usize).min(height);
+
+    let colors = [a.color, b.color, c.color].map(|c| c.to_array().map(|v| v as f32 / 255.0));
+    //Pixels lying exactly on an edge shared by two triangles must be blended only once
+    let owned = [owns_edge(a.pos, b.pos, c.pos), owns_edge(b.pos, c.pos, a.pos), owns_edge(c.pos, a.pos, b.pos)];
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let p = pos2(x as f32 + 0.5, y as f32 + 0.5);
+
+            //Barycentric weights, normalized so that the winding order does not matter
+            let wa = edge(b.pos, c.pos, p) / area;
+            let wb = edge(c.pos, a.pos, p) / area;
+            let wc = edge(a.pos, b.pos, p) / area;
+            let inside = [wa, wb, wc]
+                .into_iter()
+                .zip(owned)
+                .all(|(w, owned)| w > 0.0 || (w == 0.0 && owned));
+            if !inside {
+                continue;
+            }
+
+            let uv = pos2(
+                a.uv.x * wa + b.uv.x * wb + c.uv.x * wc,
+                a.uv.y * wa + b.uv.y * wb + c.uv.y * wc,
+            );
+            let texel = texture.sample(uv);
+
+            let mut src = [0.0; 4];
+            for i in 0..4 {
+                src[i] = (colors[0][i] * wa + colors[1][i] * wb + colors[2][i] * wc) * texel[i];
+            }
+            if src[3] <= 0.0 {
+                continue;
+            }
+
+            let pixel = &mut image.pixels[y * width + x];
+            let dst = pixel.to_array().map(|v| v as f32 / 255.0);
+            let blended = blend(dst, src).map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u8);
+            *pixel = Color32::from_rgba_premultiplied(blended[0], blended[1], blended[2], blended[3]);
+        }
+    }
+}
+
+///Tell if the triangle owns the pixels on the edge opposite to the given vertex.
+///Of two triangles sharing an edge only one owns it, since their inward normals point in opposite directions.
+fn owns_edge(vertex: egui::Pos2, from: egui::Pos2, to: egui::Pos2) -> bool {
+    let d = to - from;
+    let mut normal = vec2(-d.y, d.x);
+    if normal.dot(vertex - from) < 0.0 {
+        normal = -normal;
+    }
+    normal.x > 0.0 || (normal.x == 0.0 && normal.y > 0.0)
+}
+
+///Signed area of the parallelogram defined by the three points
+fn edge(a: egui::Pos2, b: egui::Pos2, p: egui::Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+impl KrustyGrab {
+    ///Return the screenshot with the drawings rendered on it, restricted to the selected area if any.
+    ///None if there is no screenshot or the drawings could not be rendered.
+    pub fn annotated_image(&self) -> Option<ColorImage> {
+        let base = self.get_temp_image()?;
+        let rendered = render_drawings(&base, self.document.drawings())?;
+
+        match self.get_selected_area() {
+            Some(area) => Some(rendered.region(&area, None)),
+            None => Some(rendered),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::painting::redaction::Redaction;
+    use egui::Stroke;
+
+    fn white_image() -> ColorImage {
+        ColorImage::new([40, 30], Color32::WHITE)
+    }
+
+    fn pixel(image: &ColorImage, x: usize, y: usize) -> Color32 {
+        image.pixels[y * image.width() + x]
+    }
+
+    #[test]
+    fn no_drawings_returns_the_image() {
+        let image = white_image();
+        let rendered = render_drawings(&image, &[]).unwrap();
+        assert_eq!(rendered.size, image.size);
+        assert_eq!(rendered.pixels, image.pixels);
+    }
+
+    #[test]
+    fn filled_rectangle_covers_its_area_only() {
+        let image = white_image();
+        let drawings = vec![DrawingType::FilledRectangle {
+            r: Rect::from_min_max(pos2(10.0, 5.0), pos2(20.0, 15.0)),
+            s: Stroke::new(1.0, Color32::RED),
+            rounding: 0.,
+            l: Default::default(),
+        }];
+        let rendered = render_drawings(&image, &drawings).unwrap();
+
+        assert_eq!(rendered.size, image.size);
+        assert_eq!(pixel(&rendered, 15, 10), Color32::RED);
+        assert_eq!(pixel(&rendered, 11, 6), Color32::RED);
+        assert_eq!(pixel(&rendered, 5, 10), Color32::WHITE);
+        assert_eq!(pixel(&rendered, 25, 10), Color32::WHITE);
+        assert_eq!(pixel(&rendered, 15, 20), Color32::WHITE);
+    }
+
+    #[test]
+    fn line_is_drawn_along_its_segment() {
+        let image = white_image();
+        let drawings = vec![DrawingType::Brush {
+            points: vec![pos2(5.0, 10.5), pos2(35.0, 10.5)],
+            s: Stroke::new(3.0, Color32::BLUE),
+            end: true,
+            l: Default::default(),
+        }];
+        let rendered = render_drawings(&image, &drawings).unwrap();
+
+        for x in [8, 20, 32] {
+            assert_eq!(pixel(&rendered, x, 10), Color32::BLUE);
+        }
+        assert_eq!(pixel(&rendered, 20, 2), Color32::WHITE);
+        assert_eq!(pixel(&rendered, 20, 25), Color32::WHITE);
+        assert_eq!(pixel(&rendered, 1, 10), Color32::WHITE);
+    }
+
+    #[test]
+    fn translucent_fill_is_blended_with_the_image() {
+        let image = ColorImage::new([10, 10], Color32::BLACK);
+        let drawings = vec![DrawingType::FilledRectangle {
+            r: Rect::from_min_max(pos2(0.0, 0.0), pos2(10.0, 10.0)),
+            s: Stroke::new(1.0, Color32::from_rgba_unmultiplied(255, 255, 255, 128)),
+            rounding: 0.,
+            l: Default::default(),
+        }];
+        let rendered = render_drawings(&image, &drawings).unwrap();
+
+        //Every pixel is covered exactly once, shared triangle edges included
+        let expected = pixel(&rendered, 5, 2);
+        assert!(expected != Color32::BLACK && expected != Color32::WHITE);
+        assert!(rendered.pixels.iter().all(|p| *p == expected));
+    }
+
+    #[test]
+    fn redaction_samples_only_the_screenshot() {
+        //Checkerboard, so that the redaction changes the pixels
+        let mut image = ColorImage::new([48, 32], Color32::WHITE);
+        for y in 0..32 {
+            for x in 0..48 {
+                if (x + y) % 2 == 0 {
+                    image[(x, y)] = Color32::from_gray(40);
+                }
+            }
+        }
+        let area = Rect::from_min_max(pos2(8., 8.), pos2(40., 24.));
+        let filled = |r, color| DrawingType::FilledRectangle { r, s: Stroke::new(1., color), rounding: 0., l: Default::default() };
+        let drawings = [
+            filled(Rect::from_min_max(pos2(10., 10.), pos2(20., 20.)), Color32::RED),
+            DrawingType::Pixelate { r: area, s: Stroke::new(2., Color32::BLACK) },
+            filled(Rect::from_min_max(pos2(30., 10.), pos2(36., 20.)), Color32::BLUE),
+        ];
+        let output = render_drawings(&image, &drawings).unwrap();
+
+        //The pixels are the ones shown on the canva: the redacted screenshot, without the drawing under it
+        let ([x0, y0, x1, y1], redacted) = redacted_area(&image, area, Redaction::Pixelate, 2.).unwrap();
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if !(30..36).contains(&x) || !(10..20).contains(&y) {
+                    assert_eq!(output[(x, y)], redacted[(x - x0, y - y0)], "pixel {x}, {y}");
+                }
+            }
+        }
+        assert!((x0..x1).flat_map(|x| (y0..y1).map(move |y| (x, y))).all(|p| output[p] != Color32::RED));
+
+        //The drawings made after the redaction are painted on it
+        assert_eq!(output[(33, 15)], Color32::BLUE);
+        //and the pixels out of it are untouched
+        assert_eq!(output[(2, 2)], image[(2, 2)]);
+    }
+
+    #[test]
+    fn highlight_multiplies_the_pixels() {
+        //White paper on the left, black text on the right
+        let mut image = ColorImage::new([20, 10], Color32::WHITE);
+        for y in 0..10 {
+            for x in 10..20 {
+                image[(x, y)] = Color32::BLACK;
+            }
+        }
+        let color = Color32::from_rgb(255, 200, 0);
+        let drawings = [DrawingType::Highlighter {
+            r: Rect::from_min_max(pos2(0., 0.), pos2(20., 10.)),
+            s: Stroke::new(1., color),
+            l: Default::default(),
+        }];
+        let output = render_drawings(&image, &drawings).unwrap();
+
+        //The paper takes the full color and the text stays black
+        assert_eq!(output[(5, 5)], color);
+        assert_eq!(output[(15, 5)], Color32::BLACK);
+    }
+}
@@ -0,0 +1,188 @@
+use egui::{emath::RectTransform, pos2, vec2, Color32, ColorImage, Context, Painter, Rect, TextureOptions};
+
+use crate::krustygrab::KrustyGrab;
+
+///Side of the pixelate blocks for every unit of the thickness, in screenshot pixels
+pub const PIXELATE_BLOCK: f32 = 4.0;
+///Standard deviation of the blur for every unit of the thickness, in screenshot pixels
+pub const BLUR_SIGMA: f32 = 2.0;
+
+///Way the pixels of a redacted area are destroyed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Redaction {
+    Pixelate,
+    Blur,
+}
+
+///Pixels covered by the rectangle, as `[min_x, min_y, max_x, max_y]` clamped inside the image
+pub fn pixel_bounds(rect: Rect, size: [usize; 2]) -> [usize; 4] {
+    let clamp = |v: f32, max: usize| (v.max(0.0) as usize).min(max);
+    let [x0, x1] = [clamp(rect.min.x.floor(), size[0]), clamp(rect.max.x.ceil(), size[0])];
+    let [y0, y1] = [clamp(rect.min.y.floor(), size[1]), clamp(rect.max.y.ceil(), size[1])];
+    [x0, y0, x1, y1]
+}
+
+///Replace the pixels in the rectangle with their redacted version, using the given thickness as strength.
+///Only the pixels inside the rectangle are read, and several of them are always merged in one value,
+///so the original content can not be recovered from the result.
+pub fn redact(image: &mut ColorImage, rect: Rect, redaction: Redaction, strength: f32) {
+    let bounds = pixel_bounds(rect, image.size);
+    if bounds[0] >= bounds[2] || bounds[1] >= bounds[3] {
+        return;
+    }
+
+    match redaction {
+        Redaction::Pixelate => pixelate(image, bounds, (strength * PIXELATE_BLOCK).round().max(2.0) as usize),
+        Redaction::Blur => {
+            let sigma = (strength * BLUR_SIGMA).max(1.0);
+            //A plain blur can be partially reverted, averaging blocks first discards the details for good
+            pixelate(image, bounds, (sigma as usize).max(2));
+            blur(image, bounds, sigma);
+        },
+    }
+}
+
+///Redacted copy of the screenshot pixels covered by the rectangle, with their bounds as given by [`pixel_bounds`].
+///Only the screenshot is sampled, so a redaction hides the drawings made before it, both on the canva and in the exported image.
+pub fn redacted_area(image: &ColorImage, rect: Rect, redaction: Redaction, strength: f32) -> Option<([usize; 4], ColorImage)> {
+    let bounds = pixel_bounds(rect, image.size);
+    let [x0, y0, x1, y1] = bounds;
+    if x0 >= x1 || y0 >= y1 {
+        return None;
+    }
+
+    let pixels = (y0..y1).flat_map(|y| image.pixels[y * image.width() + x0..y * image.width() + x1].iter().copied()).collect();
+    let mut area = ColorImage { size: [x1 - x0, y1 - y0], pixels };
+    redact(&mut area, Rect::from_min_size(pos2(0., 0.), vec2((x1 - x0) as f32, (y1 - y0) as f32)), redaction, strength);
+    Some((bounds, area))
+}
+
+///Fill every block of the area with the average color of its pixels
+fn pixelate(image: &mut ColorImage, [x0, y0, x1, y1]: [usize; 4], block: usize) {
+    let width = image.width();
+
+    for by in (y0..y1).step_by(block) {
+        for bx in (x0..x1).step_by(block) {
+            let (ex, ey) = ((bx + block).min(x1), (by + block).min(y1));
+
+            let mut sum = [0u32; 4];
+            for y in by..ey {
+                for x in bx..ex {
+                    let pixel = image.pixels[y * width + x].to_array();
+                    sum.iter_mut().zip(pixel).for_each(|(s, p)| *s += p as u32);
+                }
+            }
+
+            let count = ((ex - bx) * (ey - by)) as u32;
+            let [r, g, b, a] = sum.map(|s| ((s + count / 2) / count) as u8);
+            let average = Color32::from_rgba_premultiplied(r, g, b, a);
+            for y in by..ey {
+                image.pixels[y * width + bx..y * width + ex].fill(average);
+            }
+        }
+    }
+}
+
+///Gaussian blur of the area, separated in a horizontal and a vertical pass. Samples out of the area repeat its border.
+fn blur(image: &mut ColorImage, [x0, y0, x1, y1]: [usize; 4], sigma: f32) {
+    let radius = (sigma * 3.0).ceil() as isize;
+    let kernel: Vec<f32> = (-radius..=radius).map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp()).collect();
+    let total: f32 = kernel.iter().sum();
+    let kernel: Vec<f32> = kernel.into_iter().map(|k| k / total).collect();
+
+    let (w, h, width) = (x1 - x0, y1 - y0, image.width());
+    let mut area: Vec<[f32; 4]> = (y0..y1)
+        .flat_map(|y| image.pixels[y * width + x0..y * width + x1].iter())
+        .map(|p| p.to_array().map(|c| c as f32))
+        .collect();
+
+    let pass = |area: &[[f32; 4]], horizontal: bool| -> Vec<[f32; 4]> {
+        (0..w * h)
+            .map(|i| {
+                let (x, y) = ((i % w) as isize, (i / w) as isize);
+                let mut out = [0.0; 4];
+                for (k, weight) in (-radius..=radius).zip(&kernel) {
+                    let (sx, sy) = if horizontal {
+                        ((x + k).clamp(0, w as isize - 1), y)
+                    } else {
+                        (x, (y + k).clamp(0, h as isize - 1))
+                    };
+                    let sample = area[sy as usize * w + sx as usize];
+                    out.iter_mut().zip(sample).for_each(|(o, s)| *o += s * weight);
+                }
+                out
+            })
+            .collect()
+    };
+    area = pass(&area, true);
+    area = pass(&area, false);
+
+    for (i, pixel) in area.into_iter().enumerate() {
+        let [r, g, b, a] = pixel.map(|c| c.round().clamp(0.0, 255.0) as u8);
+        image.pixels[(y0 + i / w) * width + x0 + i % w] = Color32::from_rgba_premultiplied(r, g, b, a);
+    }
+}
+
+///Texture showing a redacted area on the canva, identified by the kind, the covered pixels and the strength
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RedactionKey {
+    redaction: Redaction,
+    bounds: [usize; 4],
+    strength: u32,
+}
+
+impl KrustyGrab {
+    ///Show the redacted pixels of the screenshot in the rectangle. The textures are computed once and kept while used,
+    ///until the screenshot changes.
+    pub fn show_redaction(&mut self, ctx: &Context, painter: &Painter, transform: &RectTransform, (redaction, rect, strength): (Redaction, Rect, f32)) -> Option<RedactionKey> {
+        let size = self.get_temp_image_size()?;
+        let bounds = pixel_bounds(rect, size);
+        let [x0, y0, x1, y1] = bounds;
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+        let key = RedactionKey { redaction, bounds, strength: strength.to_bits() };
+
+        if !self.redaction_textures.contains_key(&key) {
+            let (_, area) = redacted_area(self.get_temp_image_ref()?, rect, redaction, strength)?;
+            let options = match redaction {
+                Redaction::Pixelate => TextureOptions::NEAREST,
+                Redaction::Blur => TextureOptions::LINEAR,
+            };
+            self.redaction_textures.insert(key, ctx.load_texture("Redaction", area, options));
+        }
+
+        let texture = &self.redaction_textures[&key];
+        let pixels = Rect::from_min_max(pos2(x0 as f32, y0 as f32), pos2(x1 as f32, y1 as f32));
+        painter.image(texture.id(), transform.transform_rect(pixels), Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)), Color32::WHITE);
+        Some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{LayerId, RawInput};
+
+    use super::*;
+
+    #[test]
+    fn textures_are_dropped_when_the_screenshot_changes() {
+        let ctx = Context::default();
+        let _ = ctx.run(RawInput::default(), |_| {});
+        let area = Rect::from_min_max(pos2(0., 0.), pos2(8., 8.));
+        let painter = Painter::new(ctx.clone(), LayerId::background(), area);
+        let transform = RectTransform::identity(area);
+        let redaction = (Redaction::Pixelate, area, 1.);
+
+        let mut app = KrustyGrab::default();
+        app.set_temp_image(Some(ColorImage::new([8, 8], Color32::WHITE)));
+        let key = app.show_redaction(&ctx, &painter, &transform, redaction).unwrap();
+        assert!(app.redaction_textures.contains_key(&key));
+
+        //Same geometry on a different screenshot: the texture must be computed again
+        app.set_temp_image(Some(ColorImage::new([8, 8], Color32::BLACK)));
+        assert!(app.redaction_textures.is_empty());
+        assert_eq!(app.show_redaction(&ctx, &painter, &transform, redaction), Some(key));
+        assert_eq!(app.redaction_textures.len(), 1);
+    }
+}
@@ -0,0 +1,472 @@
+use egui::{emath::RectTransform, epaint::text::Fonts, vec2, Color32, Context, CursorIcon, Key, Painter, Pos2, Rect, Rgba, Stroke, Vec2};
+
+use crate::{
+    krustygrab::{GrabStatus, KrustyGrab},
+    painting::{arrow::arrow_points, drawing::DrawingType, highlight::MARKER_WIDTH, path::polygon_contains, shapes::ellipse_distance},
+};
+
+///Drawing selected with the select tool
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub index: usize,
+    ///Handle being dragged, Move while dragging the whole drawing and None when not dragging
+    pub grab: GrabStatus,
+    ///Drawing and its bounds when the resize started. Every step resizes the original, so that the errors do not accumulate.
+    original: Option<(DrawingType, Rect)>,
+    ///Pointer position of the previous step of the move, in screenshot pixels
+    last_pos: Pos2,
+}
+
+impl DrawingType {
+    ///Rectangle containing the drawing, in screenshot pixels
+    pub fn bounds(&self, fonts: &Fonts) -> Rect {
+        match self {
+            DrawingType::Brush { points, .. }
+            | DrawingType::Polyline { points, .. }
+            | DrawingType::Polygon { points, .. }
+            | DrawingType::FilledPolygon { points, .. } => Rect::from_points(points),
+            DrawingType::Marker { points, s, .. } => Rect::from_points(points).expand(s.width * MARKER_WIDTH / 2.),
+            DrawingType::Line { points, .. } => Rect::from_two_pos(points[0], points[1]),
+            DrawingType::Rectangle { r, .. }
+            | DrawingType::FilledRectangle { r, .. }
+            | DrawingType::Highlighter { r, .. }
+            | DrawingType::Ellipse { r, .. }
+            | DrawingType::FilledEllipse { r, .. }
+            | DrawingType::Pixelate { r, .. }
+            | DrawingType::Blur { r, .. } => *r,
+            DrawingType::Circle { c, r, .. } | DrawingType::FilledCircle { c, r, .. } | DrawingType::StepMarker { c, r, .. } => {
+                Rect::from_center_size(*c, Vec2::splat(r * 2.))
+            },
+            DrawingType::Arrow { p, v, c, .. } => Rect::from_points(&arrow_points(*p, *p + *v, *c)),
+            DrawingType::Text { p, t, o, .. } => o.bounds(t, *p, fonts),
+        }
+    }
+
+    ///Check if the position, in screenshot pixels, is on the drawing. The outlines are hit within the tolerance from their stroke.
+    pub fn hit_test(&self, pos: Pos2, tolerance: f32, fonts: &Fonts) -> bool {
+        let reach = tolerance + self.stroke().width / 2.;
+
+        match self {
+            DrawingType::Brush { points, .. } => match points.as_slice() {
+                [point] => point.distance(pos) <= reach,
+                points => points.windows(2).any(|p| segment_distance(pos, p[0], p[1]) <= reach),
+            },
+            DrawingType::Marker { points, s, .. } => {
+                let reach = tolerance + s.width * MARKER_WIDTH / 2.;
+                match points.as_slice() {
+                    [point] => point.distance(pos) <= reach,
+                    points => points.windows(2).any(|p| segment_distance(pos, p[0], p[1]) <= reach),
+                }
+            },
+            DrawingType::Line { points, .. } => segment_distance(pos, points[0], points[1]) <= reach,
+            DrawingType::Polyline { points, .. } => points.windows(2).any(|p| segment_distance(pos, p[0], p[1]) <= reach),
+            DrawingType::Polygon { points, .. } => closed_segments(points).any(|(a, b)| segment_distance(pos, a, b) <= reach),
+            DrawingType::FilledPolygon { points, .. } => {
+                polygon_contains(points, pos) || closed_segments(points).any(|(a, b)| segment_distance(pos, a, b) <= tolerance)
+            },
+            DrawingType::Rectangle { r, .. } => r.expand(reach).contains(pos) && !r.shrink(reach).contains(pos),
+            DrawingType::FilledRectangle { r, .. }
+            | DrawingType::Highlighter { r, .. }
+            | DrawingType::Pixelate { r, .. }
+            | DrawingType::Blur { r, .. } => r.expand(tolerance).contains(pos),
+            DrawingType::Circle { c, r, .. } => (pos.distance(*c) - r).abs() <= reach,
+            DrawingType::FilledCircle { c, r, .. } | DrawingType::StepMarker { c, r, .. } => pos.distance(*c) <= r + tolerance,
+            DrawingType::Ellipse { r, .. } => ellipse_distance(*r, pos).abs() <= reach,
+            DrawingType::FilledEllipse { r, .. } => ellipse_distance(*r, pos) <= tolerance,
+            DrawingType::Arrow { p, v, c, .. } => arrow_points(*p, *p + *v, *c).windows(2).any(|s| segment_distance(pos, s[0], s[1]) <= reach),
+            DrawingType::Text { .. } => self.bounds(fonts).expand(tolerance).contains(pos),
+        }
+    }
+
+    ///Stretch the drawing so that its bounds move from `from` to `to`
+    pub fn resize(&mut self, from: Rect, to: Rect) {
+        //A side of zero length can not be scaled, it is only moved
+        let ratio = |to: f32, from: f32| if from > f32::EPSILON { to / from } else { 1. };
+        let scale = vec2(ratio(to.width(), from.width()), ratio(to.height(), from.height()));
+        let map = |p: Pos2| to.center() + (p - from.center()) * scale;
+
+        match self {
+            DrawingType::Brush { points, .. }
+            | DrawingType::Marker { points, .. }
+            | DrawingType::Polyline { points, .. }
+            | DrawingType::Polygon { points, .. }
+            | DrawingType::FilledPolygon { points, .. } => points.iter_mut().for_each(|p| *p = map(*p)),
+            DrawingType::Line { points, .. } => points.iter_mut().for_each(|p| *p = map(*p)),
+            DrawingType::Rectangle { r, .. }
+            | DrawingType::FilledRectangle { r, .. }
+            | DrawingType::Highlighter { r, .. }
+            | DrawingType::Ellipse { r, .. }
+            | DrawingType::FilledEllipse { r, .. }
+            | DrawingType::Pixelate { r, .. }
+            | DrawingType::Blur { r, .. } => {
+                *r = Rect::from_two_pos(map(r.min), map(r.max));
+            },
+            DrawingType::Circle { c, r, .. } | DrawingType::FilledCircle { c, r, .. } | DrawingType::StepMarker { c, r, .. } => {
+                *c = map(*c);
+                *r *= (scale.x + scale.y) / 2.;
+            },
+            DrawingType::Arrow { p, v, c, .. } => {
+                let tip = map(*p + *v);
+                *p = map(*p);
+                *v = tip - *p;
+                if let Some(c) = c {
+                    *c = map(*c);
+                }
+            },
+            //The size of the text follows the height of its bounds
+            DrawingType::Text { p, o, .. } => {
+                *p = map(*p);
+                o.size = (o.size * scale.y).max(1.);
+            },
+        }
+    }
+}
+
+///Distance of the point from the segment between `a` and `b`
+fn segment_distance(point: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let segment = b - a;
+    let length = segment.length_sq();
+    if length <= f32::EPSILON {
+        return point.distance(a);
+    }
+    let t = ((point - a).dot(segment) / length).clamp(0., 1.);
+    point.distance(a + segment * t)
+}
+
+///Segments of the closed path through the points, the last one joining the last point to the first
+fn closed_segments(points: &[Pos2]) -> impl Iterator<Item = (Pos2, Pos2)> + '_ {
+    points.iter().zip(points.iter().cycle().skip(1)).map(|(a, b)| (*a, *b))
+}
+
+///Handles used to resize the bounds of the selected drawing, in window coordinates
+fn handles(bounds: Rect) -> [(GrabStatus, Rect); 8] {
+    let point = |center| Rect::from_center_size(center, Vec2::splat(KrustyGrab::GRABBABLE_POINTS_SIZE));
+    [
+        (GrabStatus::TopLeft, point(bounds.left_top())),
+        (GrabStatus::TopMid, point(bounds.center_top())),
+        (GrabStatus::TopRight, point(bounds.right_top())),
+        (GrabStatus::MidLeft, point(bounds.left_center())),
+        (GrabStatus::MidRight, point(bounds.right_center())),
+        (GrabStatus::BotLeft, point(bounds.left_bottom())),
+        (GrabStatus::BotMid, point(bounds.center_bottom())),
+        (GrabStatus::BotRight, point(bounds.right_bottom())),
+    ]
+}
+
+fn grab_cursor(grab: GrabStatus) -> CursorIcon {
+    match grab {
+        GrabStatus::TopLeft => CursorIcon::ResizeNorthWest,
+        GrabStatus::TopMid => CursorIcon::ResizeNorth,
+        GrabStatus::TopRight => CursorIcon::ResizeNorthEast,
+        GrabStatus::MidLeft => CursorIcon::ResizeWest,
+        GrabStatus::MidRight => CursorIcon::ResizeEast,
+        GrabStatus::BotLeft => CursorIcon::ResizeSouthWest,
+        GrabStatus::BotMid => CursorIcon::ResizeSouth,
+        GrabStatus::BotRight => CursorIcon::ResizeSouthEast,
+        GrabStatus::Move | GrabStatus::Control => CursorIcon::Grabbing,
+        GrabStatus::None | GrabStatus::Select => CursorIcon::Default,
+    }
+}
+
+///Bounds obtained dragging the given handle to the position. Dragging a side over the opposite one swaps them.
+fn resized_bounds(bounds: Rect, grab: GrabStatus, pos: Pos2) -> Rect {
+    let (mut min, mut max) = (bounds.min, bounds.max);
+    match grab {
+        GrabStatus::TopLeft => min = pos,
+        GrabStatus::TopMid => min.y = pos.y,
+        GrabStatus::TopRight => (min.y, max.x) = (pos.y, pos.x),
+        GrabStatus::MidLeft => min.x = pos.x,
+        GrabStatus::MidRight => max.x = pos.x,
+        GrabStatus::BotLeft => (min.x, max.y) = (pos.x, pos.y),
+        GrabStatus::BotMid => max.y = pos.y,
+        GrabStatus::BotRight => max = pos,
+        GrabStatus::Move | GrabStatus::Control | GrabStatus::None | GrabStatus::Select => {},
+    }
+    Rect::from_two_pos(min, max)
+}
+
+impl KrustyGrab {
+    ///Distance, in window pixels, within which a drawing is selected
+    pub const SELECT_TOLERANCE: f32 = 6.0;
+
+    ///Manage the select tool while the pointer is on the canva.
+    ///`pointer` is the position in the window, `mouse` the same position in screenshot pixels.
+    pub fn select_on_canva(&mut self, ctx: &Context, transform: &RectTransform, pointer: Pos2, mouse: Pos2) {
+        let tolerance = KrustyGrab::SELECT_TOLERANCE / transform.scale().x;
+        let (pressed, down) = ctx.input(|i| (i.pointer.primary_pressed(), i.pointer.primary_down()));
+
+        let selected = self.document.selection.clone().and_then(|s| Some((self.document.drawings().get(s.index)?.clone(), s)));
+        let Some((drawing, mut selection)) = selected else {
+            if pressed {
+                self.select_at(ctx, mouse, tolerance);
+            }
+            return;
+        };
+        let bounds = ctx.fonts(|fonts| drawing.bounds(fonts));
+        //The control point of a curved drawing is over the handles of its bounds
+        let control = drawing
+            .control_point()
+            .filter(|c| transform.transform_pos(*c).distance(pointer) <= KrustyGrab::GRABBABLE_POINTS_SIZE)
+            .map(|_| GrabStatus::Control);
+        let handle = control.or_else(|| handles(transform.transform_rect(bounds)).into_iter().find(|(_, r)| r.contains(pointer)).map(|(grab, _)| grab));
+
+        if pressed {
+            match handle {
+                Some(grab) => {
+                    self.document.end_edit();
+                    selection.grab = grab;
+                    selection.original = Some((drawing, bounds));
+                    self.document.selection = Some(selection);
+                },
+                None => self.select_at(ctx, mouse, tolerance),
+            }
+        } else if down {
+            match (selection.grab, &selection.original) {
+                (GrabStatus::Move, _) => {
+                    self.document.translate(selection.index, mouse - selection.last_pos);
+                    selection.last_pos = mouse;
+                },
+                (GrabStatus::Control, Some((original, _))) => {
+                    let mut curved = original.clone();
+                    curved.set_control_point(mouse);
+                    self.document.modify(selection.index, curved);
+                },
+                (GrabStatus::None, _) | (GrabStatus::Select, _) | (_, None) => {},
+                (grab, Some((original, bounds))) => {
+                    let mut resized = original.clone();
+                    resized.resize(*bounds, resized_bounds(*bounds, grab, mouse));
+                    self.document.modify(selection.index, resized);
+                },
+            }
+            ctx.set_cursor_icon(grab_cursor(selection.grab));
+            self.document.selection = Some(selection);
+        } else if let Some(grab) = handle {
+            ctx.set_cursor_icon(grab_cursor(grab));
+        } else if ctx.fonts(|fonts| drawing.hit_test(mouse, tolerance, fonts)) {
+            ctx.set_cursor_icon(CursorIcon::Grab);
+        }
+    }
+
+    ///Select the topmost drawing in the position, starting to move it. Nothing is selected if there is no drawing.
+    fn select_at(&mut self, ctx: &Context, mouse: Pos2, tolerance: f32) {
+        self.document.end_edit();
+
+        let hit = ctx.fonts(|fonts| self.document.drawings().iter().rposition(|d| d.hit_test(mouse, tolerance, fonts)));
+        self.document.selection = hit.map(|index| Selection { index, grab: GrabStatus::Move, original: None, last_pos: mouse });
+    }
+
+    ///Complete the move or resize of the selected drawing
+    pub fn end_select_grab(&mut self) {
+        if let Some(selection) = &mut self.document.selection {
+            if selection.grab != GrabStatus::None {
+                selection.grab = GrabStatus::None;
+                selection.original = None;
+                self.document.end_edit();
+            }
+        }
+    }
+
+    ///Color and thickness shown by the tools: the ones of the selected drawing, so that they can be changed, otherwise the ones of the tool
+    pub fn shown_style(&self) -> (Rgba, f32) {
+        match self.document.selection.as_ref().and_then(|s| self.document.drawings().get(s.index)) {
+            Some(drawing) => (Rgba::from(drawing.stroke().color), drawing.stroke().width),
+            None => (self.document.tool.color, self.document.tool.thickness),
+        }
+    }
+
+    ///Change the color and thickness of the selected drawing, or of the tool if no drawing is selected
+    pub fn set_style(&mut self, color: Rgba, thickness: f32) {
+        match &self.document.selection {
+            Some(selection) => self.document.restyle(selection.index, Stroke::new(thickness, color)),
+            None => {
+                self.document.tool.color = color;
+                self.document.tool.thickness = thickness;
+            },
+        }
+    }
+
+    ///Draw the bounds and the handles of the selected drawing, deleting it if Delete is pressed
+    pub fn show_selection(&mut self, ctx: &Context, painter: &Painter, transform: &RectTransform) {
+        let Some(selection) = &self.document.selection else {
+            return;
+        };
+        let index = selection.index;
+        if index >= self.document.drawings().len() {
+            self.document.selection = None;
+            return;
+        }
+
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(Key::Delete) || i.key_pressed(Key::Backspace)) {
+            tracing::info!("Deleted {:?}", self.document.drawings()[index]);
+            self.document.selection = None;
+            self.document.delete(index);
+            return;
+        }
+
+        let bounds = transform.transform_rect(ctx.fonts(|fonts| self.document.drawings()[index].bounds(fonts)));
+        painter.rect_stroke(bounds.expand(2.), 0.0, Stroke::new(1.0, KrustyGrab::ADJUST_POINTS_COLOR));
+        painter.rect_stroke(bounds.expand(3.), 0.0, Stroke::new(1.0, Color32::from_black_alpha(150)));
+
+        if selection.grab == GrabStatus::None || selection.grab == GrabStatus::Move {
+            for (_, handle) in handles(bounds) {
+                painter.rect_filled(handle, KrustyGrab::ADJUST_POINTS_ROUNDING, KrustyGrab::ADJUST_POINTS_COLOR);
+                painter.rect_stroke(handle, KrustyGrab::ADJUST_POINTS_ROUNDING, Stroke::new(1.0, Color32::from_black_alpha(150)));
+            }
+        }
+
+        //The control point is joined to the ends of the curve it bends
+        if let DrawingType::Arrow { p, v, c: Some(c), .. } = &self.document.drawings()[index] {
+            let (p, tip, c) = (transform.transform_pos(*p), transform.transform_pos(*p + *v), transform.transform_pos(*c));
+            painter.line_segment([p, c], Stroke::new(1.0, KrustyGrab::ADJUST_POINTS_COLOR));
+            painter.line_segment([c, tip], Stroke::new(1.0, KrustyGrab::ADJUST_POINTS_COLOR));
+            painter.circle(c, KrustyGrab::GRABBABLE_POINTS_SIZE / 2., KrustyGrab::ADJUST_POINTS_COLOR, Stroke::new(1.0, Color32::from_black_alpha(150)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, FontDefinitions, RawInput};
+
+    use super::*;
+    use crate::painting::{arrow::{ArrowHeads, ArrowOptions}, style::{Dash, LineStyle}};
+
+    fn fonts() -> Fonts {
+        Fonts::new(1.0, 1024, FontDefinitions::default())
+    }
+
+    fn stroke() -> Stroke {
+        Stroke::new(2., Color32::RED)
+    }
+
+    #[test]
+    fn distance_from_a_segment() {
+        let (a, b) = (pos2(0., 0.), pos2(10., 0.));
+        assert_eq!(segment_distance(pos2(5., 3.), a, b), 3.);
+        //Past the ends the distance is from the nearest end
+        assert_eq!(segment_distance(pos2(13., 4.), a, b), 5.);
+        assert_eq!(segment_distance(pos2(-3., -4.), a, b), 5.);
+        //A segment of zero length is a point
+        assert_eq!(segment_distance(pos2(3., 4.), a, a), 5.);
+    }
+
+    #[test]
+    fn outlines_are_hit_only_near_the_stroke() {
+        let fonts = fonts();
+        let r = Rect::from_min_max(pos2(0., 0.), pos2(100., 100.));
+
+        let outline = DrawingType::Rectangle { r, s: stroke(), rounding: 0., l: Default::default() };
+        assert!(outline.hit_test(pos2(0., 50.), 2., &fonts));
+        assert!(outline.hit_test(pos2(-2.5, 50.), 2., &fonts));
+        assert!(!outline.hit_test(pos2(-4., 50.), 2., &fonts));
+        assert!(!outline.hit_test(pos2(50., 50.), 2., &fonts));
+
+        let filled = DrawingType::FilledRectangle { r, s: stroke(), rounding: 0., l: Default::default() };
+        assert!(filled.hit_test(pos2(50., 50.), 2., &fonts));
+        assert!(!filled.hit_test(pos2(-4., 50.), 2., &fonts));
+
+        let circle = DrawingType::Circle { c: pos2(0., 0.), r: 10., s: stroke(), l: Default::default() };
+        assert!(circle.hit_test(pos2(12., 0.), 2., &fonts));
+        assert!(!circle.hit_test(pos2(0., 0.), 2., &fonts));
+
+        let dot = DrawingType::Brush { points: vec![pos2(5., 5.)], s: stroke(), end: true, l: Default::default() };
+        assert!(dot.hit_test(pos2(7., 5.), 2., &fonts));
+        assert!(!dot.hit_test(pos2(9., 5.), 2., &fonts));
+    }
+
+    #[test]
+    fn resize_maps_the_bounds() {
+        let from = Rect::from_min_max(pos2(0., 0.), pos2(10., 10.));
+        let to = Rect::from_min_max(pos2(10., 10.), pos2(30., 20.));
+
+        let mut rect = DrawingType::Rectangle { r: from, s: stroke(), rounding: 0., l: Default::default() };
+        rect.resize(from, to);
+        assert!(matches!(rect, DrawingType::Rectangle { r, .. } if r == to));
+
+        let mut arrow = DrawingType::Arrow { p: pos2(0., 10.), v: vec2(10., -10.), s: stroke(), o: Default::default(), c: None, l: Default::default() };
+        arrow.resize(from, to);
+        assert!(matches!(arrow, DrawingType::Arrow { p, v, .. } if p == pos2(10., 20.) && v == vec2(20., -10.)));
+    }
+
+    #[test]
+    fn sides_of_zero_length_are_only_moved() {
+        //Horizontal line: its height can not be scaled
+        let from = Rect::from_min_max(pos2(0., 5.), pos2(10., 5.));
+        let to = Rect::from_min_max(pos2(0., 8.), pos2(20., 12.));
+
+        let mut line = DrawingType::Brush { points: vec![pos2(0., 5.), pos2(10., 5.)], s: stroke(), end: true, l: Default::default() };
+        line.resize(from, to);
+        assert!(matches!(line, DrawingType::Brush { points, .. } if points == vec![pos2(0., 10.), pos2(20., 10.)]));
+    }
+
+    #[test]
+    fn dragging_a_side_over_the_opposite_swaps_them() {
+        let bounds = Rect::from_min_max(pos2(10., 10.), pos2(20., 20.));
+        assert_eq!(resized_bounds(bounds, GrabStatus::MidRight, pos2(30., 0.)), Rect::from_min_max(pos2(10., 10.), pos2(30., 20.)));
+        assert_eq!(resized_bounds(bounds, GrabStatus::MidRight, pos2(5., 0.)), Rect::from_min_max(pos2(5., 10.), pos2(10., 20.)));
+        assert_eq!(resized_bounds(bounds, GrabStatus::TopLeft, pos2(25., 25.)), Rect::from_min_max(pos2(20., 20.), pos2(25., 25.)));
+        assert_eq!(resized_bounds(bounds, GrabStatus::Move, pos2(0., 0.)), bounds);
+    }
+
+    #[test]
+    fn selecting_shows_the_style_without_changing_the_tool() {
+        let ctx = Context::default();
+        let _ = ctx.run(RawInput::default(), |_| {});
+
+        let mut app = KrustyGrab::default();
+        let tool = (app.document.tool.color, app.document.tool.thickness);
+        let r = Rect::from_min_max(pos2(0., 0.), pos2(10., 10.));
+        app.document.push(DrawingType::FilledRectangle { r, s: Stroke::new(7., Color32::BLUE), rounding: 0., l: Default::default() });
+
+        app.select_at(&ctx, pos2(5., 5.), 1.);
+        assert_eq!(app.document.selection.as_ref().map(|s| s.index), Some(0));
+        assert_eq!(app.shown_style(), (Rgba::from(Color32::BLUE), 7.));
+        assert_eq!((app.document.tool.color, app.document.tool.thickness), tool);
+
+        //Changing the style restyles the drawing only
+        app.set_style(Rgba::from(Color32::GREEN), 3.);
+        assert_eq!(app.document.drawings()[0].stroke(), Stroke::new(3., Color32::GREEN));
+        assert_eq!((app.document.tool.color, app.document.tool.thickness), tool);
+
+        //Without a selection the tool is shown and changed
+        app.select_at(&ctx, pos2(50., 50.), 1.);
+        assert!(app.document.selection.is_none());
+        assert_eq!(app.shown_style(), tool);
+        app.set_style(Rgba::from(Color32::GREEN), 3.);
+        assert_eq!((app.document.tool.color, app.document.tool.thickness), (Rgba::from(Color32::GREEN), 3.));
+    }
+
+    #[test]
+    fn options_of_the_selected_arrow_do_not_change_the_tool() {
+        let ctx = Context::default();
+        let _ = ctx.run(RawInput::default(), |_| {});
+
+        let mut app = KrustyGrab::default();
+        let tool = app.document.tool.arrow;
+        app.document.push(DrawingType::Arrow { p: pos2(0., 0.), v: vec2(20., 0.), s: stroke(), o: tool, c: None, l: Default::default() });
+        app.select_at(&ctx, pos2(10., 0.), 1.);
+
+        let options = ArrowOptions { heads: ArrowHeads::Double, ..tool };
+        app.set_arrow_options(options, true);
+        assert!(matches!(app.document.drawings()[0], DrawingType::Arrow { o, c: Some(_), .. } if o == options));
+        assert_eq!(app.document.tool.arrow, tool);
+        assert!(!app.document.tool.curved_arrow);
+    }
+
+    #[test]
+    fn line_style_of_the_selection_does_not_change_the_tool() {
+        let ctx = Context::default();
+        let _ = ctx.run(RawInput::default(), |_| {});
+
+        let mut app = KrustyGrab::default();
+        let r = Rect::from_min_max(pos2(0., 0.), pos2(10., 10.));
+        app.document.push(DrawingType::FilledRectangle { r, s: stroke(), rounding: 0., l: Default::default() });
+        app.select_at(&ctx, pos2(5., 5.), 1.);
+
+        let dashed = LineStyle { dash: Dash::Dashed, ..Default::default() };
+        app.set_line_style(dashed);
+        assert_eq!(app.document.drawings()[0].line_style(), Some(dashed));
+        assert_eq!(app.shown_line_style(), dashed);
+        assert_eq!(app.document.tool.line, LineStyle::default());
+    }
+}
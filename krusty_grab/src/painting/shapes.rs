@@ -0,0 +1,75 @@
+use egui::{Color32, Context, Pos2, Rect, Shape, Stroke, Vec2};
+
+use crate::{
+    krustygrab::KrustyGrab,
+    painting::{document::DrawingMode, path::snap_angle},
+};
+
+///Points on the ellipse inscribed in the rectangle, about one every pixel of the radius
+fn ellipse_points(r: Rect) -> Vec<Pos2> {
+    let radius = r.size() / 2.;
+    let segments = radius.max_elem().clamp(16., 256.) as usize;
+    (0..segments)
+        .map(|i| {
+            let angle = std::f32::consts::TAU * i as f32 / segments as f32;
+            r.center() + Vec2::new(radius.x * angle.cos(), radius.y * angle.sin())
+        })
+        .collect()
+}
+
+///Outline of the ellipse inscribed in the rectangle, given in the coordinates of the painting area
+pub fn ellipse_stroke(r: Rect, stroke: Stroke) -> Shape {
+    Shape::closed_line(ellipse_points(r), stroke)
+}
+
+///Ellipse inscribed in the rectangle filled with the color, given in the coordinates of the painting area
+pub fn ellipse_filled(r: Rect, color: Color32) -> Shape {
+    Shape::convex_polygon(ellipse_points(r), color, Stroke::NONE)
+}
+
+///Approximate distance of the point from the outline of the ellipse inscribed in the rectangle, negative inside it
+pub fn ellipse_distance(r: Rect, pos: Pos2) -> f32 {
+    let radius = r.size() / 2.;
+    let offset = pos - r.center();
+    if radius.min_elem() <= f32::EPSILON {
+        return r.distance_to_pos(pos);
+    }
+
+    //Along the line from the center, the outline is where the normalized distance is one
+    let normalized = (offset / radius).length();
+    if normalized <= f32::EPSILON {
+        return -radius.min_elem();
+    }
+    offset.length() * (1. - 1. / normalized)
+}
+
+impl KrustyGrab {
+    ///Start and end of the drag with the current tool, after applying the modifiers held.
+    ///Shift snaps the lines to the angle step and makes the boxes of rectangles and ellipses square, Alt draws the shapes from their center.
+    pub fn constrain_drag(&self, ctx: &Context, start: Pos2, end: Pos2) -> (Pos2, Pos2) {
+        let (shift, alt) = ctx.input(|i| (i.modifiers.shift, i.modifiers.alt));
+        let mode = self.document.tool.mode;
+        let boxed = matches!(
+            mode,
+            DrawingMode::Rectangle | DrawingMode::FilledRectangle | DrawingMode::Ellipse | DrawingMode::FilledEllipse
+        );
+        //Circles are always round, so only Alt changes them
+        let centered = boxed || matches!(mode, DrawingMode::Circle | DrawingMode::FilledCircle);
+
+        let end = match mode {
+            DrawingMode::Line if shift => snap_angle(start, end),
+            _ if boxed && shift => {
+                let offset = end - start;
+                let side = offset.x.abs().max(offset.y.abs());
+                start + Vec2::new(side.copysign(offset.x), side.copysign(offset.y))
+            },
+            _ => end,
+        };
+
+        if centered && alt {
+            (start - (end - start), end)
+        } else {
+            (start, end)
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use egui::{Color32, ComboBox, DragValue, Pos2, Shape, Stroke, Ui, Widget};
+use serde::{Deserialize, Serialize};
+
+use crate::{krustygrab::KrustyGrab, painting::document::DrawingMode};
+
+///Turn of the stroke, in radians, above which the corner is drawn rounded
+const ROUND_JOIN_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
+
+///Curve fitted on the points of a brush stroke once completed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Smoothing {
+    None,
+    ///Corners cut twice, the stroke moves slightly inside them
+    #[default]
+    Chaikin,
+    ///Curve passing through every point
+    CatmullRom,
+}
+
+impl Smoothing {
+    pub const ALL: [Smoothing; 3] = [Smoothing::None, Smoothing::Chaikin, Smoothing::CatmullRom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Smoothing::None => "No smoothing",
+            Smoothing::Chaikin => "Chaikin",
+            Smoothing::CatmullRom => "Catmull-Rom",
+        }
+    }
+}
+
+///Processing of the brush strokes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct BrushOptions {
+    pub smoothing: Smoothing,
+    ///Largest distance of the removed points from the simplified stroke, in screenshot pixels. 0 keeps every point.
+    pub tolerance: f32,
+}
+
+impl Default for BrushOptions {
+    fn default() -> Self {
+        Self { smoothing: Smoothing::default(), tolerance: 0.5 }
+    }
+}
+
+impl BrushOptions {
+    ///Simplify the points of a completed stroke, then smooth them.
+    ///The smoothed stroke is simplified again with a smaller tolerance, dropping the points added on the straight parts.
+    pub fn apply(&self, points: &[Pos2]) -> Vec<Pos2> {
+        let points = simplify(points, self.tolerance);
+        let smoothed = match self.smoothing {
+            Smoothing::None => return points,
+            Smoothing::Chaikin => chaikin(&chaikin(&points)),
+            Smoothing::CatmullRom => catmull_rom(&points),
+        };
+        simplify(&smoothed, self.tolerance / 4.)
+    }
+}
+
+///Distance of the point from the segment from `a` to `b`
+fn segment_distance(p: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_sq() > f32::EPSILON { ((p - a).dot(ab) / ab.length_sq()).clamp(0., 1.) } else { 0. };
+    p.distance(a + ab * t)
+}
+
+///Ramer-Douglas-Peucker simplification, keeping the points farther than the tolerance from the simplified stroke
+pub fn simplify(points: &[Pos2], tolerance: f32) -> Vec<Pos2> {
+    if points.len() < 3 || tolerance <= 0. {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    let (first, last) = (0, points.len() - 1);
+    keep[first] = true;
+    keep[last] = true;
+
+    //Ranges still to be simplified, without recursion for the long strokes
+    let mut ranges = vec![(first, last)];
+    while let Some((start, end)) = ranges.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| (i, segment_distance(points[i], points[start], points[end])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                ranges.push((start, i));
+                ranges.push((i, end));
+            }
+        }
+    }
+
+    points.iter().zip(keep).filter(|(_, keep)| *keep).map(|(p, _)| *p).collect()
+}
+
+///One step of Chaikin's corner cutting, keeping the ends of the stroke
+pub fn chaikin(points: &[Pos2]) -> Vec<Pos2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut smoothed = vec![points[0]];
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        smoothed.push(a.lerp(b, 0.25));
+        smoothed.push(a.lerp(b, 0.75));
+    }
+    //The cuts next to the ends are replaced by the ends themselves
+    smoothed.remove(1);
+    smoothed.pop();
+    smoothed.push(points[points.len() - 1]);
+    smoothed
+}
+
+///Catmull-Rom spline through the points, about one point every 2 pixels
+pub fn catmull_rom(points: &[Pos2]) -> Vec<Pos2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut smoothed = vec![points[0]];
+    for i in 0..points.len() - 1 {
+        //The ends are repeated to have a tangent on the first and last segments
+        let (p0, p1, p2) = (points[i.saturating_sub(1)], points[i], points[i + 1]);
+        let p3 = points[(i + 2).min(points.len() - 1)];
+        let samples = (p1.distance(p2) / 2.).clamp(1., 16.) as usize;
+
+        for s in 1..=samples {
+            let t = s as f32 / samples as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let point = (p1.to_vec2() * 2.
+                + (p2 - p0) * t
+                + (p0.to_vec2() * 2. - p1.to_vec2() * 5. + p2.to_vec2() * 4. - p3.to_vec2()) * t2
+                + (p1.to_vec2() * 3. - p0.to_vec2() - p2.to_vec2() * 3. + p3.to_vec2()) * t3)
+                * 0.5;
+            smoothed.push(point.to_pos2());
+        }
+    }
+    smoothed
+}
+
+///Stroke drawn as a single path, with round ends and round sharp corners.
+///The rounding is only added to opaque strokes, where the overlapping discs can not be seen.
+pub fn stroke_shapes(points: Vec<Pos2>, stroke: Stroke) -> Vec<Shape> {
+    if stroke.color.a() < Color32::WHITE.a() || points.len() < 2 {
+        return vec![Shape::line(points, stroke)];
+    }
+
+    let disc = |p: Pos2| Shape::circle_filled(p, stroke.width / 2., stroke.color);
+    let mut shapes = vec![disc(points[0])];
+    shapes.extend(
+        points
+            .windows(3)
+            .filter(|p| {
+                let (a, b) = (p[1] - p[0], p[2] - p[1]);
+                a.length_sq() > f32::EPSILON && b.length_sq() > f32::EPSILON && a.normalized().dot(b.normalized()) < ROUND_JOIN_ANGLE.cos()
+            })
+            .map(|p| disc(p[1])),
+    );
+    shapes.push(disc(points[points.len() - 1]));
+    shapes.push(Shape::line(points, stroke));
+    shapes
+}
+
+impl KrustyGrab {
+    ///Smoothing and simplification of the freehand strokes, shown while drawing with the brush or the marker
+    pub fn brush_options(&mut self, ui: &mut Ui) {
+        if !matches!(self.document.tool.mode, DrawingMode::Brush | DrawingMode::Marker) {
+            return;
+        }
+        let brush = &mut self.document.tool.brush;
+
+        let mut changed = false;
+        ComboBox::from_id_source("Brush_smoothing")
+            .selected_text(brush.smoothing.name())
+            .width(100.)
+            .show_ui(ui, |ui| {
+                for smoothing in Smoothing::ALL {
+                    changed |= ui.selectable_value(&mut brush.smoothing, smoothing, smoothing.name()).changed();
+                }
+            })
+            .response
+            .on_hover_text_at_pointer("Smooth the strokes once completed");
+
+        changed |= DragValue::new(&mut brush.tolerance)
+            .prefix("Simplify: ")
+            .suffix(" px")
+            .speed(0.05)
+            .clamp_range(0.0..=10.0)
+            .max_decimals(1)
+            .ui(ui)
+            .on_hover_text_at_pointer("Remove the points of the strokes closer than this to the simplified stroke")
+            .changed();
+
+        if changed {
+            tracing::info!("Brush options changed to {:?}", brush);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+
+    use super::*;
+
+    ///Distance of the point from the closest segment of the stroke
+    fn stroke_distance(stroke: &[Pos2], p: Pos2) -> f32 {
+        stroke.windows(2).map(|s| segment_distance(p, s[0], s[1])).fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn simplify_keeps_the_ends_within_the_tolerance() {
+        let points: Vec<Pos2> = (0..=20).map(|i| pos2(i as f32, if i % 2 == 0 { 0. } else { 0.3 })).chain([pos2(20., 10.)]).collect();
+        let simplified = simplify(&points, 0.5);
+
+        assert_eq!(simplified.first(), points.first());
+        assert_eq!(simplified.last(), points.last());
+        //The zigzag within the tolerance is removed, the corner is kept
+        assert_eq!(simplified, vec![pos2(0., 0.), pos2(20., 0.), pos2(20., 10.)]);
+        assert!(points.iter().all(|p| stroke_distance(&simplified, *p) <= 0.5));
+
+        //Below the tolerance every point is kept
+        assert_eq!(simplify(&points, 0.1), points);
+        assert_eq!(simplify(&points, 0.), points);
+    }
+
+    #[test]
+    fn chaikin_cuts_every_corner() {
+        let points = [pos2(0., 0.), pos2(4., 0.), pos2(4., 4.), pos2(8., 4.)];
+        let smoothed = chaikin(&points);
+
+        //Two points for every segment, the ends replacing the cuts next to them
+        assert_eq!(smoothed.len(), 2 * points.len() - 2);
+        assert_eq!(smoothed, vec![pos2(0., 0.), pos2(3., 0.), pos2(4., 1.), pos2(4., 3.), pos2(5., 4.), pos2(8., 4.)]);
+        assert_eq!(chaikin(&smoothed).len(), 2 * smoothed.len() - 2);
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_the_points() {
+        let points = [pos2(0., 0.), pos2(10., 5.), pos2(20., -5.), pos2(30., 0.)];
+        let smoothed = catmull_rom(&points);
+
+        assert!(smoothed.len() > points.len());
+        assert_eq!(smoothed.first(), points.first());
+        for point in points {
+            assert!(smoothed.iter().any(|p| p.distance(point) < 1e-3), "{point:?} is not on the curve");
+        }
+    }
+
+    #[test]
+    fn short_strokes_are_unchanged() {
+        let cases: [&[Pos2]; 3] = [&[], &[pos2(1., 1.)], &[pos2(1., 1.), pos2(5., 3.)]];
+        let options = BrushOptions { smoothing: Smoothing::CatmullRom, tolerance: 2. };
+        for points in cases {
+            assert_eq!(simplify(points, 1.), points);
+            assert_eq!(chaikin(points), points);
+            assert_eq!(catmull_rom(points), points);
+            assert_eq!(BrushOptions::default().apply(points), points);
+            assert_eq!(options.apply(points), points);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use egui::{epaint::text::Fonts, Color32, FontFamily, FontId, Pos2, Rgba, Shape, Stroke};
+use serde::{Deserialize, Serialize};
+
+use crate::painting::drawing::DrawingType;
+
+///Radius of a step marker for every unit of the thickness, in screenshot pixels
+pub const STEP_RADIUS: f32 = 4.0;
+///Smallest radius of a step marker, in screenshot pixels
+pub const STEP_MIN_RADIUS: f32 = 10.0;
+
+///Way the number of a step marker is written
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum StepStyle {
+    #[default]
+    Number,
+    Letter,
+    Roman,
+}
+
+impl StepStyle {
+    pub const ALL: [StepStyle; 3] = [StepStyle::Number, StepStyle::Letter, StepStyle::Roman];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StepStyle::Number => "Numbers (1, 2, 3)",
+            StepStyle::Letter => "Letters (A, B, C)",
+            StepStyle::Roman => "Roman (I, II, III)",
+        }
+    }
+
+    ///Label of the step with the given number, starting from 1
+    pub fn label(&self, n: usize) -> String {
+        match self {
+            StepStyle::Number => n.to_string(),
+            //Letters and roman numerals have no zero, it is written in digits
+            StepStyle::Letter | StepStyle::Roman if n == 0 => n.to_string(),
+            //After Z the labels continue with AA, AB...
+            StepStyle::Letter => {
+                let mut label = Vec::new();
+                let mut n = n;
+                while n > 0 {
+                    n -= 1;
+                    label.push(b'A' + (n % 26) as u8);
+                    n /= 26;
+                }
+                label.iter().rev().map(|&c| c as char).collect()
+            },
+            //Roman numerals stop at 3999, the larger numbers are written in digits
+            StepStyle::Roman if n > 3999 => n.to_string(),
+            StepStyle::Roman => {
+                const NUMERALS: [(usize, &str); 13] = [
+                    (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+                    (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+                ];
+                let mut label = String::new();
+                let mut n = n;
+                for (value, numeral) in NUMERALS {
+                    while n >= value {
+                        label.push_str(numeral);
+                        n -= value;
+                    }
+                }
+                label
+            },
+        }
+    }
+}
+
+///Radius of a new step marker drawn with the given thickness
+pub fn step_radius(thickness: f32) -> f32 {
+    (thickness * STEP_RADIUS).max(STEP_MIN_RADIUS)
+}
+
+///Number the step markers following their order in the drawings, so that the sequence has no gaps
+pub fn renumber_steps(drawings: &mut [DrawingType]) {
+    drawings
+        .iter_mut()
+        .filter_map(|d| match d {
+            DrawingType::StepMarker { n, .. } => Some(n),
+            _ => None,
+        })
+        .enumerate()
+        .for_each(|(i, n)| *n = i + 1);
+}
+
+///Number of the next step marker added after the given drawings
+pub fn next_step(drawings: &[DrawingType]) -> usize {
+    drawings.iter().filter(|d| matches!(d, DrawingType::StepMarker { .. })).count() + 1
+}
+
+///Shapes of a step marker: a disc of the stroke color with its label written inside.
+///Center and radius are the ones of the painting area.
+pub fn step_shapes(c: Pos2, r: f32, label: String, s: &Stroke, fonts: &Fonts) -> Vec<Shape> {
+    //The label is black or white, whichever is more visible on the disc
+    let luminance = Rgba::from(s.color).intensity();
+    let text_color = if luminance > 0.5 { Color32::BLACK } else { Color32::WHITE };
+
+    //Longer labels are written smaller to stay inside the disc
+    let mut galley = fonts.layout_no_wrap(label.clone(), FontId::new(r * 1.2, FontFamily::Proportional), text_color);
+    let max_width = r * 1.6;
+    if galley.size().x > max_width {
+        let size = r * 1.2 * max_width / galley.size().x;
+        galley = fonts.layout_no_wrap(label, FontId::new(size, FontFamily::Proportional), text_color);
+    }
+
+    vec![
+        Shape::circle_filled(c, r, s.color),
+        Shape::galley(c - galley.size() / 2., galley),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letter_labels_continue_after_z() {
+        let labels: Vec<String> = [1, 2, 26, 27, 28, 52, 53, 702, 703].into_iter().map(|n| StepStyle::Letter.label(n)).collect();
+        assert_eq!(labels, ["A", "B", "Z", "AA", "AB", "AZ", "BA", "ZZ", "AAA"]);
+    }
+
+    #[test]
+    fn roman_labels() {
+        let labels: Vec<String> = [1, 4, 9, 14, 40, 90, 400, 1994, 3999].into_iter().map(|n| StepStyle::Roman.label(n)).collect();
+        assert_eq!(labels, ["I", "IV", "IX", "XIV", "XL", "XC", "CD", "MCMXCIV", "MMMCMXCIX"]);
+    }
+
+    #[test]
+    fn numbers_without_a_label_are_written_in_digits() {
+        assert_eq!(StepStyle::Roman.label(4000), "4000");
+        assert_eq!(StepStyle::Roman.label(0), "0");
+        assert_eq!(StepStyle::Letter.label(0), "0");
+        assert_eq!(StepStyle::Number.label(0), "0");
+        assert_eq!(StepStyle::Number.label(12), "12");
+    }
+}
@@ -0,0 +1,225 @@
+use egui::{
+    epaint::{tessellator::path::rounded_rectangle, CircleShape, PathShape, RectShape},
+    Color32, ComboBox, DragValue, Shape, Slider, Stroke, Ui, Vec2, Widget,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{krustygrab::KrustyGrab, painting::drawing::DrawingType};
+
+///Pattern of the outlines
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dash {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl Dash {
+    pub const ALL: [Dash; 3] = [Dash::Solid, Dash::Dashed, Dash::Dotted];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dash::Solid => "Solid",
+            Dash::Dashed => "Dashed",
+            Dash::Dotted => "Dotted",
+        }
+    }
+}
+
+///Pattern and opacity of a drawing, applied on top of its stroke
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct LineStyle {
+    pub dash: Dash,
+    ///Length of the dashes and distance between the dots, in screenshot pixels
+    pub dash_length: f32,
+    ///Multiplies the alpha of the color, from 0 to 1
+    pub opacity: f32,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self { dash: Dash::Solid, dash_length: 10.0, opacity: 1.0 }
+    }
+}
+
+impl LineStyle {
+    ///Break the outline of the shape following the pattern, keeping its fill. `scale` maps screenshot pixels on the painting area.
+    pub fn dash(&self, shape: Shape, scale: f32) -> Vec<Shape> {
+        if self.dash == Dash::Solid {
+            return vec![shape];
+        }
+
+        //The fill is kept as a separate shape without outline, the transparent ones are skipped when painting
+        let (mut points, closed, fill, stroke) = match shape {
+            Shape::LineSegment { points, stroke } => (points.to_vec(), false, Shape::Noop, stroke),
+            Shape::Path(PathShape { points, closed, fill, stroke }) => {
+                let fill = Shape::Path(PathShape { points: points.clone(), closed, fill, stroke: Stroke::NONE });
+                (points, closed, fill, stroke)
+            },
+            Shape::Rect(RectShape { rect, rounding, fill, stroke }) => {
+                let mut points = Vec::new();
+                rounded_rectangle(&mut points, rect, rounding);
+                (points, true, Shape::rect_filled(rect, rounding, fill), stroke)
+            },
+            Shape::Circle(CircleShape { center, radius, fill, stroke }) => {
+                let segments = radius.clamp(16., 256.) as usize;
+                let points = (0..segments)
+                    .map(|i| center + Vec2::angled(std::f32::consts::TAU * i as f32 / segments as f32) * radius)
+                    .collect();
+                (points, true, Shape::circle_filled(center, radius, fill), stroke)
+            },
+            shape => return vec![shape],
+        };
+        let mut shapes = vec![fill];
+        if stroke.is_empty() {
+            return shapes;
+        }
+        if closed {
+            points.extend(points.first().copied());
+        }
+
+        let length = (self.dash_length * scale).max(1.);
+        match self.dash {
+            Dash::Dashed => shapes.extend(Shape::dashed_line(&points, stroke, length, length * 0.6 + stroke.width)),
+            Dash::Dotted => shapes.extend(Shape::dotted_line(&points, stroke.color, length.max(stroke.width * 1.5), stroke.width / 2.)),
+            Dash::Solid => {},
+        }
+        shapes
+    }
+
+    ///Apply the opacity to the colors of the shape
+    pub fn fade(&self, shape: Shape) -> Shape {
+        if self.opacity >= 1. {
+            return shape;
+        }
+        let fade = |color: Color32| color.linear_multiply(self.opacity);
+        let fade_stroke = |stroke: Stroke| Stroke::new(stroke.width, fade(stroke.color));
+
+        match shape {
+            Shape::LineSegment { points, stroke } => Shape::LineSegment { points, stroke: fade_stroke(stroke) },
+            Shape::Path(path) => Shape::Path(PathShape { fill: fade(path.fill), stroke: fade_stroke(path.stroke), ..path }),
+            Shape::Rect(rect) => Shape::Rect(RectShape { fill: fade(rect.fill), stroke: fade_stroke(rect.stroke), ..rect }),
+            Shape::Circle(circle) => Shape::Circle(CircleShape { fill: fade(circle.fill), stroke: fade_stroke(circle.stroke), ..circle }),
+            Shape::Mesh(mut mesh) => {
+                mesh.vertices.iter_mut().for_each(|v| v.color = fade(v.color));
+                Shape::Mesh(mesh)
+            },
+            Shape::Vec(shapes) => Shape::Vec(shapes.into_iter().map(|s| self.fade(s)).collect()),
+            shape => shape,
+        }
+    }
+}
+
+
+impl DrawingType {
+    ///Pattern and opacity of the drawings made with the shape tools
+    pub fn line_style(&self) -> Option<LineStyle> {
+        match self {
+            DrawingType::Brush { l, .. }
+            | DrawingType::Rectangle { l, .. }
+            | DrawingType::FilledRectangle { l, .. }
+            | DrawingType::Highlighter { l, .. }
+            | DrawingType::Marker { l, .. }
+            | DrawingType::Circle { l, .. }
+            | DrawingType::FilledCircle { l, .. }
+            | DrawingType::Ellipse { l, .. }
+            | DrawingType::FilledEllipse { l, .. }
+            | DrawingType::Arrow { l, .. }
+            | DrawingType::Line { l, .. }
+            | DrawingType::Polyline { l, .. }
+            | DrawingType::Polygon { l, .. }
+            | DrawingType::FilledPolygon { l, .. } => Some(*l),
+            DrawingType::Text { .. } | DrawingType::Pixelate { .. } | DrawingType::Blur { .. } | DrawingType::StepMarker { .. } => None,
+        }
+    }
+
+    pub fn set_line_style(&mut self, style: LineStyle) {
+        match self {
+            DrawingType::Brush { l, .. }
+            | DrawingType::Rectangle { l, .. }
+            | DrawingType::FilledRectangle { l, .. }
+            | DrawingType::Highlighter { l, .. }
+            | DrawingType::Marker { l, .. }
+            | DrawingType::Circle { l, .. }
+            | DrawingType::FilledCircle { l, .. }
+            | DrawingType::Ellipse { l, .. }
+            | DrawingType::FilledEllipse { l, .. }
+            | DrawingType::Arrow { l, .. }
+            | DrawingType::Line { l, .. }
+            | DrawingType::Polyline { l, .. }
+            | DrawingType::Polygon { l, .. }
+            | DrawingType::FilledPolygon { l, .. } => *l = style,
+            DrawingType::Text { .. } | DrawingType::Pixelate { .. } | DrawingType::Blur { .. } | DrawingType::StepMarker { .. } => {},
+        }
+    }
+}
+
+impl KrustyGrab {
+    ///Pattern and opacity of the new drawings, next to the thickness.
+    ///The style of the selected drawing is shown and changed instead of the tool one.
+    pub fn line_style_options(&mut self, ui: &mut Ui) {
+        let mut line = self.shown_line_style();
+        let mut opacity = line.opacity * 100.;
+
+        let mut changed = false;
+        ComboBox::from_id_source("Line_style")
+            .selected_text(line.dash.name())
+            .width(70.)
+            .show_ui(ui, |ui| {
+                for dash in Dash::ALL {
+                    changed |= ui.selectable_value(&mut line.dash, dash, dash.name()).changed();
+                }
+            })
+            .response
+            .on_hover_text_at_pointer("Change the pattern of the lines");
+
+        if line.dash != Dash::Solid {
+            changed |= DragValue::new(&mut line.dash_length)
+                .prefix("Dash: ")
+                .speed(0.2)
+                .clamp_range(2.0..=100.0)
+                .ui(ui)
+                .on_hover_text_at_pointer("Change the length of the dashes")
+                .changed();
+        }
+
+        changed |= Slider::new(&mut opacity, 5.0..=100.0)
+            .suffix("%")
+            .integer()
+            .ui(ui)
+            .on_hover_text_at_pointer("Change the opacity")
+            .changed();
+
+        if changed {
+            line.opacity = opacity / 100.;
+            tracing::info!("Line style changed to {:?}", line);
+            self.set_line_style(line);
+        }
+    }
+
+    ///Index and line style of the selected drawing, if it has one
+    fn selected_line_style(&self) -> Option<(usize, LineStyle)> {
+        let index = self.document.selection.as_ref()?.index;
+        Some((index, self.document.drawings().get(index)?.line_style()?))
+    }
+
+    ///Line style shown by the tools: the one of the selected drawing, otherwise the one of the tool
+    pub fn shown_line_style(&self) -> LineStyle {
+        self.selected_line_style().map_or(self.document.tool.line, |(_, line)| line)
+    }
+
+    ///Change the line style of the selected drawing, or of the tool if the selection has no line style
+    pub fn set_line_style(&mut self, style: LineStyle) {
+        match self.selected_line_style() {
+            Some((index, line)) if line != style => {
+                let mut drawing = self.document.drawings()[index].clone();
+                drawing.set_line_style(style);
+                self.document.modify(index, drawing);
+            },
+            Some(_) => {},
+            None => self.document.tool.line = style,
+        }
+    }
+}
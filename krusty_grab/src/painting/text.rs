@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use egui::{
+    color_picker::{color_edit_button_srgba, Alpha},
+    epaint::text::{Fonts, LayoutJob, TextFormat},
+    vec2, Align, Color32, ComboBox, Context, CursorIcon, DragValue, FontFamily, FontId, Galley, Id, Key, Pos2, Rect, RichText, Shape, Stroke, TextStyle, Widget, Window,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::krustygrab::KrustyGrab;
+
+///Font family of a text annotation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextFamily {
+    #[default]
+    Proportional,
+    Monospace,
+}
+
+impl TextFamily {
+    pub const ALL: [TextFamily; 2] = [TextFamily::Proportional, TextFamily::Monospace];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextFamily::Proportional => "Sans",
+            TextFamily::Monospace => "Mono",
+        }
+    }
+
+    fn to_egui(self) -> FontFamily {
+        match self {
+            TextFamily::Proportional => FontFamily::Proportional,
+            TextFamily::Monospace => FontFamily::Monospace,
+        }
+    }
+}
+
+///Box drawn behind a text annotation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextFrame {
+    #[default]
+    None,
+    ///Box filled with the background color
+    Background,
+    ///Box outlined with the color and thickness of the text
+    Outline,
+}
+
+impl TextFrame {
+    pub const ALL: [TextFrame; 3] = [TextFrame::None, TextFrame::Background, TextFrame::Outline];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextFrame::None => "No box",
+            TextFrame::Background => "Background",
+            TextFrame::Outline => "Outline",
+        }
+    }
+}
+
+///Alignment of the lines of a text annotation, also deciding which side of the text is on its position
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    pub const ALL: [TextAlign; 3] = [TextAlign::Left, TextAlign::Center, TextAlign::Right];
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            TextAlign::Left => "⏴",
+            TextAlign::Center => "⏺",
+            TextAlign::Right => "⏵",
+        }
+    }
+
+    fn to_egui(self) -> Align {
+        match self {
+            TextAlign::Left => Align::Min,
+            TextAlign::Center => Align::Center,
+            TextAlign::Right => Align::Max,
+        }
+    }
+}
+
+///Appearance of a text annotation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct TextOptions {
+    pub family: TextFamily,
+    ///Font size in screenshot pixels
+    pub size: f32,
+    pub bold: bool,
+    pub italic: bool,
+    pub frame: TextFrame,
+    pub background: Color32,
+    pub align: TextAlign,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            family: TextFamily::default(),
+            size: KrustyGrab::BASE_TEXT_SIZE,
+            bold: false,
+            italic: false,
+            frame: TextFrame::default(),
+            background: Color32::from_black_alpha(160),
+            align: TextAlign::default(),
+        }
+    }
+}
+
+impl TextOptions {
+    ///Lay out the text, with the sizes multiplied by the scale. The lines are placed below `y = 0`, aligned on `x = 0`.
+    pub fn layout(&self, text: &str, color: Color32, scale: f32, fonts: &Fonts) -> Arc<Galley> {
+        let format = TextFormat {
+            font_id: FontId::new(self.size * scale, self.family.to_egui()),
+            color,
+            italics: self.italic,
+            ..Default::default()
+        };
+        let mut job = LayoutJob::single_section(text.to_owned(), format);
+        job.halign = self.align.to_egui();
+        fonts.layout_job(job)
+    }
+
+    ///Space between the text and its box, without scaling
+    fn padding(&self) -> f32 {
+        self.size * 0.25
+    }
+
+    ///Rectangle covered by the text and its box, with `pos` and the sizes multiplied by `scale` in the same coordinates
+    fn frame_rect(&self, galley: &Galley, pos: Pos2, scale: f32) -> Rect {
+        galley.rect.translate(pos.to_vec2()).expand(self.padding() * scale)
+    }
+
+    ///Rectangle covered by the text placed in `pos`, in screenshot pixels
+    pub fn bounds(&self, text: &str, pos: Pos2, fonts: &Fonts) -> Rect {
+        let galley = self.layout(text, Color32::WHITE, 1.0, fonts);
+        match self.frame {
+            TextFrame::None => galley.rect.translate(pos.to_vec2()),
+            TextFrame::Background | TextFrame::Outline => self.frame_rect(&galley, pos, 1.0),
+        }
+    }
+
+    ///Shapes of the text placed in `pos`. Position and scale are the ones of the painting area.
+    pub fn shapes(&self, text: &str, s: &Stroke, pos: Pos2, scale: f32, fonts: &Fonts) -> Vec<Shape> {
+        let galley = self.layout(text, s.color, scale, fonts);
+        let rounding = self.padding() * scale / 2.;
+        let mut shapes = Vec::new();
+
+        match self.frame {
+            TextFrame::None => {},
+            TextFrame::Background => shapes.push(Shape::rect_filled(self.frame_rect(&galley, pos, scale), rounding, self.background)),
+            TextFrame::Outline => shapes.push(Shape::rect_stroke(self.frame_rect(&galley, pos, scale), rounding, Stroke::new(s.width * scale, s.color))),
+        }
+
+        //There is no bold font, so the glyphs are painted twice with a small horizontal offset
+        if self.bold {
+            let offset = (self.size * scale / 25.).max(0.5);
+            shapes.push(Shape::galley(pos + vec2(offset, 0.), galley.clone()));
+        }
+        shapes.push(Shape::galley(pos, galley));
+        shapes
+    }
+}
+
+impl KrustyGrab {
+    ///Editor of the text being written, placed close to its position. `canvas` is the area where the screenshot is shown.
+    pub fn text_editor(&mut self, ctx: &Context, canvas: Rect) {
+        let Some(edit) = self.document.text_edit.clone() else {
+            return;
+        };
+
+        if ctx.input(|i| i.key_pressed(Key::Escape)) {
+            self.document.end_text();
+            return;
+        }
+
+        //The editor is placed below the text, or above it when there is no space at the bottom of the canva
+        let text_pos = self.adjust_drawing_pos(edit.pos, true);
+        let editor_pos = if text_pos.y + 170.0 > canvas.max.y {
+            text_pos - vec2(0.0, 170.0)
+        } else {
+            text_pos + vec2(0.0, 20.0)
+        };
+
+        let mut text = edit.text;
+        let mut options = self.document.tool.text;
+        let mut done = false;
+
+        Window::new("Text")
+            .id(Id::from("Text_editor"))
+            .title_bar(false)
+            .resizable(false)
+            .fixed_pos(editor_pos.max(canvas.min))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ComboBox::from_id_source("Text_family")
+                        .selected_text(options.family.name())
+                        .width(60.)
+                        .show_ui(ui, |ui| {
+                            for family in TextFamily::ALL {
+                                ui.selectable_value(&mut options.family, family, family.name());
+                            }
+                        });
+
+                    DragValue::new(&mut options.size)
+                        .clamp_range(6.0..=300.0)
+                        .speed(0.5)
+                        .suffix(" px")
+                        .ui(ui)
+                        .on_hover_text("Font size");
+
+                    ui.toggle_value(&mut options.bold, RichText::new("B").strong()).on_hover_text("Bold");
+                    ui.toggle_value(&mut options.italic, RichText::new("I").italics()).on_hover_text("Italic");
+
+                    ui.separator();
+                    for align in TextAlign::ALL {
+                        ui.selectable_value(&mut options.align, align, align.icon())
+                            .on_hover_text(format!("Align {:?}", align).to_lowercase());
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ComboBox::from_id_source("Text_frame")
+                        .selected_text(options.frame.name())
+                        .show_ui(ui, |ui| {
+                            for frame in TextFrame::ALL {
+                                ui.selectable_value(&mut options.frame, frame, frame.name());
+                            }
+                        });
+
+                    if options.frame == TextFrame::Background {
+                        color_edit_button_srgba(ui, &mut options.background, Alpha::BlendOrAdditive).on_hover_text("Background color");
+                    }
+                });
+
+                let editor = egui::TextEdit::multiline(&mut text)
+                    .id(Id::from("Text_editor_input"))
+                    .desired_rows(3)
+                    .hint_text("Write here, Esc to finish")
+                    .font(FontId::new(15.0, options.family.to_egui()))
+                    .ui(ui);
+
+                if edit.focus {
+                    editor.request_focus();
+                }
+
+                ui.horizontal(|ui| {
+                    done = ui
+                        .button(RichText::new("Done").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked();
+                });
+            });
+
+        if let Some(edit) = &mut self.document.text_edit {
+            edit.focus = false;
+        }
+
+        if options != self.document.tool.text || text != self.document.text_edit.as_ref().map_or("", |e| e.text.as_str()) {
+            self.document.tool.text = options;
+            self.document.set_text(text);
+        }
+
+        if done {
+            self.document.end_text();
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{anyhow, Error};
+use egui::{pos2, vec2, Color32, ColorImage, Rect};
+use image::{imageops::{self, FilterType}, RgbaImage};
+use screenshots::Screen;
+
+///Environment variable used to replace the real screens with a fake backend.
+///It can contain the path of an image to be used as screen content or `pattern` to generate one.
+pub const FAKE_CAPTURE_ENV: &str = "KRUSTYGRAB_FAKE_CAPTURE";
+
+///A connected display. Position and size are expressed in desktop coordinates (points).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Display {
+    pub id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub is_primary: bool,
+}
+
+impl Display {
+    ///Area of the desktop covered by the display
+    pub fn rect(&self) -> Rect {
+        Rect::from_min_size(pos2(self.x as f32, self.y as f32), vec2(self.width as f32, self.height as f32))
+    }
+}
+
+///Screens to be captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenSelection {
+    ///The display with the given index
+    Single(usize),
+    ///All the displays, stitched as a single image of the virtual desktop
+    All,
+}
+
+impl Default for ScreenSelection {
+    fn default() -> Self {
+        ScreenSelection::Single(0)
+    }
+}
+
+///Source of the screenshots. Abstracts the platform capture so it can be replaced, for example in tests.
+pub trait CaptureBackend: Send + Sync {
+    ///List the connected displays
+    fn displays(&self) -> Result<Vec<Display>, Error>;
+
+    ///Capture the whole content of the display with the given index
+    fn capture_display(&self, index: usize) -> Result<ColorImage, Error>;
+
+    ///Capture an area of the display with the given index. The region is expressed in image pixels.
+    fn capture_region(&self, index: usize, region: Rect) -> Result<ColorImage, Error> {
+        let image = self.capture_display(index)?;
+        check_region(region, image.width(), image.height())?;
+        Ok(image.region(&region, None))
+    }
+
+    ///Capture every display and compose them in a single image covering the whole virtual desktop
+    fn capture_all(&self) -> Result<ColorImage, Error> {
+        let displays = self.displays()?;
+        let captures = (0..displays.len())
+            .map(|i| self.capture_display(i))
+            .collect::<Result<Vec<ColorImage>, Error>>()?;
+
+        Ok(stitch_displays(&displays, &captures))
+    }
+}
+
+///Compose the captures of the displays according to their position on the desktop.
+///Displays with a lower scale factor are upscaled to the highest one, areas not covered by any display are left black.
+pub fn stitch_displays(displays: &[Display], captures: &[ColorImage]) -> ColorImage {
+    let bounds = displays
+        .iter()
+        .map(Display::rect)
+        .fold(Rect::NOTHING, |acc, r| acc.union(r));
+    if !bounds.is_positive() {
+        return ColorImage::new([0, 0], Color32::BLACK);
+    }
+
+    let scale = displays.iter().map(|d| d.scale_factor).fold(1.0, f32::max);
+    let width = (bounds.width() * scale).ceil() as usize;
+    let height = (bounds.height() * scale).ceil() as usize;
+    let mut desktop = ColorImage::new([width, height], Color32::BLACK);
+
+    for (display, capture) in displays.iter().zip(captures) {
+        let dest = display.rect().translate(-bounds.min.to_vec2());
+        let (dest_x, dest_y) = ((dest.min.x * scale).round() as usize, (dest.min.y * scale).round() as usize);
+        //Rounding may move the display past the desktop border, clamp it to the remaining space
+        let dest_w = ((dest.width() * scale).round() as usize).min(width.saturating_sub(dest_x));
+        let dest_h = ((dest.height() * scale).round() as usize).min(height.saturating_sub(dest_y));
+        if dest_w == 0 || dest_h == 0 {
+            continue;
+        }
+
+        let capture = if capture.size != [dest_w, dest_h] {
+            resize(capture, dest_w, dest_h)
+        } else {
+            capture.clone()
+        };
+
+        for y in 0..dest_h {
+            let src_row = &capture.pixels[y * dest_w..(y + 1) * dest_w];
+            let dst_start = (dest_y + y) * width + dest_x;
+            desktop.pixels[dst_start..dst_start + dest_w].copy_from_slice(src_row);
+        }
+    }
+
+    desktop
+}
+
+///Resize the image with a bilinear filter
+fn resize(image: &ColorImage, width: usize, height: usize) -> ColorImage {
+    let buffer = RgbaImage::from_raw(image.width() as u32, image.height() as u32, image.as_raw().to_vec())
+        .expect("ColorImage buffer always matches its size");
+    let resized = imageops::resize(&buffer, width as u32, height as u32, FilterType::Triangle);
+
+    ColorImage::from_rgba_unmultiplied([width, height], resized.as_raw())
+}
+
+///Check that the region is not empty and lies inside an image of the given size
+fn check_region(region: Rect, width: usize, height: usize) -> Result<(), Error> {
+    let bounds = Rect::from_min_size(pos2(0.0, 0.0), vec2(width as f32, height as f32));
+
+    if !bounds.contains_rect(region) || region.width() < 1.0 || region.height() < 1.0 {
+        return Err(anyhow!("Region {:?} is outside of the display ({}x{})", region, width, height));
+    }
+    Ok(())
+}
+
+///Return the backend to be used by the application: the real screens, unless [`FAKE_CAPTURE_ENV`] is set
+pub fn default_backend() -> Arc<dyn CaptureBackend> {
+    match std::env::var(FAKE_CAPTURE_ENV) {
+        Ok(source) if source == "pattern" => Arc::new(FakeBackend::pattern(FakeBackend::default_displays())),
+        Ok(source) => match FakeBackend::from_file(PathBuf::from(&source)) {
+            Ok(backend) => Arc::new(backend),
+            Err(e) => {
+                tracing::error!("Unable to load the fake capture from {source:?}: {e}");
+                Arc::new(FakeBackend::pattern(FakeBackend::default_displays()))
+            },
+        },
+        Err(_) => Arc::new(ScreenshotsBackend),
+    }
+}
+
+///Capture the real screens through the `screenshots` crate
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ScreenshotsBackend;
+
+impl ScreenshotsBackend {
+    fn screen(index: usize) -> Result<Screen, Error> {
+        Screen::all()?
+            .get(index)
+            .copied()
+            .ok_or_else(|| anyhow!("Screen {} not found", index + 1))
+    }
+}
+
+impl CaptureBackend for ScreenshotsBackend {
+    fn displays(&self) -> Result<Vec<Display>, Error> {
+        Ok(Screen::all()?
+            .iter()
+            .map(|s| Display {
+                id: s.display_info.id,
+                x: s.display_info.x,
+                y: s.display_info.y,
+                width: s.display_info.width,
+                height: s.display_info.height,
+                scale_factor: s.display_info.scale_factor,
+                is_primary: s.display_info.is_primary,
+            })
+            .collect())
+    }
+
+    fn capture_display(&self, index: usize) -> Result<ColorImage, Error> {
+        let image = Self::screen(index)?.capture()?;
+
+        Ok(ColorImage::from_rgba_unmultiplied(
+            [image.width() as usize, image.height() as usize],
+            image.rgba(),
+        ))
+    }
+
+    fn capture_region(&self, index: usize, region: Rect) -> Result<ColorImage, Error> {
+        let screen = Self::screen(index)?;
+
+        //The area of the screenshots crate is expressed in points
+        let scale = screen.display_info.scale_factor;
+        check_region(
+            region,
+            (screen.display_info.width as f32 * scale) as usize,
+            (screen.display_info.height as f32 * scale) as usize,
+        )?;
+        let image = screen.capture_area(
+            (region.min.x / scale) as i32,
+            (region.min.y / scale) as i32,
+            (region.width() / scale) as u32,
+            (region.height() / scale) as u32,
+        )?;
+
+        Ok(ColorImage::from_rgba_unmultiplied(
+            [image.width() as usize, image.height() as usize],
+            image.rgba(),
+        ))
+    }
+}
+
+///Content returned by the [`FakeBackend`]
+#[derive(Clone)]
+enum FakeSource {
+    ///A generated pattern, different for every display
+    Pattern,
+    ///The same image for every display
+    Image(ColorImage),
+}
+
+///Backend that does not need any screen, used to run the capture flows on headless machines
+#[derive(Clone)]
+pub struct FakeBackend {
+    displays: Vec<Display>,
+    source: FakeSource,
+}
+
+impl FakeBackend {
+    ///Single full HD display
+    pub fn default_displays() -> Vec<Display> {
+        vec![Display { id: 0, x: 0, y: 0, width: 1920, height: 1080, scale_factor: 1.0, is_primary: true }]
+    }
+
+    ///Backend generating a test pattern sized as each of the given displays
+    pub fn pattern(displays: Vec<Display>) -> Self {
+        Self { displays, source: FakeSource::Pattern }
+    }
+
+    ///Backend returning the given image for a single display of the same size
+    pub fn from_image(image: ColorImage) -> Self {
+        let display = Display {
+            id: 0,
+            x: 0,
+            y: 0,
+            width: image.width() as u32,
+            height: image.height() as u32,
+            scale_factor: 1.0,
+            is_primary: true,
+        };
+        Self { displays: vec![display], source: FakeSource::Image(image) }
+    }
+
+    ///Backend returning the content of the image file for a single display of the same size
+    pub fn from_file(path: PathBuf) -> Result<Self, Error> {
+        let image = image::open(path)?.into_rgba8();
+        let image = ColorImage::from_rgba_unmultiplied([image.width() as usize, image.height() as usize], image.as_raw());
+        Ok(Self::from_image(image))
+    }
+
+    ///Replace the displays reported by the backend
+    pub fn with_displays(mut self, displays: Vec<Display>) -> Self {
+        self.displays = displays;
+        self
+    }
+
+    ///Checkerboard tinted with the display index, with a gradient to make positions recognizable
+    fn pattern_image(index: usize, display: &Display) -> ColorImage {
+        let width = (display.width as f32 * display.scale_factor) as usize;
+        let height = (display.height as f32 * display.scale_factor) as usize;
+        let tint = [(200, 60, 60), (60, 200, 60), (60, 60, 200), (200, 200, 60)][index % 4];
+
+        let mut image = ColorImage::new([width, height], Color32::BLACK);
+        for y in 0..height {
+            for x in 0..width {
+                let gradient = ((x + y) * 255 / (width + height).max(1)) as u8;
+                image.pixels[y * width + x] = if (x / 32 + y / 32) % 2 == 0 {
+                    Color32::from_rgb(tint.0, tint.1, tint.2)
+                } else {
+                    Color32::from_rgb(gradient, gradient, gradient)
+                };
+            }
+        }
+        image
+    }
+}
+
+impl CaptureBackend for FakeBackend {
+    fn displays(&self) -> Result<Vec<Display>, Error> {
+        Ok(self.displays.clone())
+    }
+
+    fn capture_display(&self, index: usize) -> Result<ColorImage, Error> {
+        let display = self
+            .displays
+            .get(index)
+            .ok_or_else(|| anyhow!("Screen {} not found", index + 1))?;
+
+        match &self.source {
+            FakeSource::Pattern => Ok(Self::pattern_image(index, display)),
+            FakeSource::Image(image) => Ok(image.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Stroke;
+
+    use super::*;
+    use crate::{
+        krustygrab::KrustyGrab,
+        painting::drawing::DrawingType,
+        screenshot::screen_capture::{save_image, screens_number, take_screen, EncoderSettings},
+    };
+
+    ///Image with a different color in every pixel
+    fn numbered_image(width: usize, height: usize) -> ColorImage {
+        let mut image = ColorImage::new([width, height], Color32::BLACK);
+        for y in 0..height {
+            for x in 0..width {
+                image[(x, y)] = Color32::from_rgb(x as u8, y as u8, 128);
+            }
+        }
+        image
+    }
+
+    fn display(id: u32, x: i32, y: i32, width: u32, height: u32, scale_factor: f32) -> Display {
+        Display { id, x, y, width, height, scale_factor, is_primary: id == 0 }
+    }
+
+    #[test]
+    fn take_screen_returns_the_display_content() {
+        let image = numbered_image(40, 30);
+        let backend = FakeBackend::from_image(image.clone());
+
+        assert_eq!(screens_number(&backend).unwrap(), 1);
+        let screen = take_screen(&backend, ScreenSelection::Single(0)).unwrap();
+        assert!(screen == image);
+        assert!(take_screen(&backend, ScreenSelection::Single(1)).is_err());
+    }
+
+    #[test]
+    fn pattern_is_sized_as_the_display() {
+        let displays = vec![display(0, 0, 0, 20, 10, 2.0), display(1, 20, 0, 10, 10, 1.0)];
+        let backend = FakeBackend::pattern(displays);
+
+        assert_eq!(screens_number(&backend).unwrap(), 2);
+        assert_eq!(backend.capture_display(0).unwrap().size, [40, 20]);
+        assert_eq!(backend.capture_display(1).unwrap().size, [10, 10]);
+        assert!(backend.capture_display(0).unwrap()[(0, 0)] != backend.capture_display(1).unwrap()[(0, 0)]);
+    }
+
+    #[test]
+    fn capture_region_crops_the_display() {
+        let backend = FakeBackend::from_image(numbered_image(40, 30));
+
+        let region = backend.capture_region(0, Rect::from_min_max(pos2(5., 7.), pos2(15., 12.))).unwrap();
+        assert_eq!(region.size, [10, 5]);
+        assert_eq!(region[(0, 0)], Color32::from_rgb(5, 7, 128));
+        assert_eq!(region[(9, 4)], Color32::from_rgb(14, 11, 128));
+    }
+
+    #[test]
+    fn capture_region_rejects_invalid_regions() {
+        let backend = FakeBackend::from_image(numbered_image(40, 30));
+
+        for region in [
+            Rect::from_min_max(pos2(30., 20.), pos2(50., 40.)),
+            Rect::from_min_max(pos2(-5., 0.), pos2(10., 10.)),
+            Rect::from_min_max(pos2(5., 5.), pos2(5., 10.)),
+        ] {
+            assert!(backend.capture_region(0, region).is_err(), "{region:?} must be rejected");
+        }
+        assert!(check_region(Rect::from_min_max(pos2(0., 0.), pos2(40., 30.)), 40, 30).is_ok());
+    }
+
+    #[test]
+    fn displays_are_stitched_by_position_and_scale() {
+        //A HiDPI display on the left of a standard one, lower by 10 points
+        let displays = vec![display(0, 0, 0, 20, 10, 2.0), display(1, 20, 10, 10, 10, 1.0)];
+        let backend = FakeBackend::pattern(displays.clone());
+
+        let desktop = take_screen(&backend, ScreenSelection::All).unwrap();
+        assert_eq!(desktop.size, [60, 40]);
+
+        let left = backend.capture_display(0).unwrap();
+        assert_eq!(desktop[(0, 0)], left[(0, 0)]);
+        //The standard display is upscaled to the highest scale factor
+        let right = backend.capture_display(1).unwrap();
+        assert_eq!(desktop[(40, 20)], right[(0, 0)]);
+        //Areas out of every display stay black
+        assert_eq!(desktop[(50, 5)], Color32::BLACK);
+        assert_eq!(desktop[(5, 30)], Color32::BLACK);
+
+        assert!(stitch_displays(&displays, &[left, right]) == desktop);
+    }
+
+    #[test]
+    fn displays_with_negative_origin_and_fractional_scale_are_stitched() {
+        //A 1.5x display above and on the left of the primary one, plus a standard one on its right
+        let displays = vec![
+            display(0, 0, 0, 10, 10, 1.0),
+            display(1, -7, -5, 7, 5, 1.5),
+            display(2, 10, -3, 3, 13, 1.0),
+        ];
+        let backend = FakeBackend::pattern(displays.clone());
+
+        let desktop = take_screen(&backend, ScreenSelection::All).unwrap();
+        //Desktop from (-7, -5) to (13, 10), scaled by 1.5
+        assert_eq!(desktop.size, [30, 23]);
+
+        let primary = backend.capture_display(0).unwrap();
+        let secondary = backend.capture_display(1).unwrap();
+        assert_eq!(secondary.size, [10, 7]);
+        assert_eq!(desktop[(0, 0)], secondary[(0, 0)]);
+        //The primary display starts at (7, 5) points from the desktop origin
+        assert_eq!(desktop[(11, 8)], primary[(0, 0)]);
+        //The rightmost display reaches the border of the desktop without overflowing it
+        assert!(desktop[(29, 22)] != Color32::BLACK);
+        assert_eq!(desktop[(29, 0)], Color32::BLACK);
+    }
+
+    #[test]
+    fn captures_out_of_the_desktop_are_clamped() {
+        //Rounding places the second display on the border of the desktop
+        let displays = vec![display(0, 0, 0, 3, 3, 1.5), display(1, 3, 0, 0, 3, 1.0)];
+        let captures = vec![ColorImage::new([5, 5], Color32::RED), ColorImage::new([1, 5], Color32::GREEN)];
+
+        let desktop = stitch_displays(&displays, &captures);
+        assert_eq!(desktop.size, [5, 5]);
+        assert!(desktop.pixels.iter().all(|p| *p == Color32::RED));
+    }
+
+    #[test]
+    fn cropped_and_annotated_screenshot_is_saved() {
+        let backend: Arc<dyn CaptureBackend> = Arc::new(FakeBackend::from_image(numbered_image(64, 48)));
+        let mut app = KrustyGrab::default().with_capture_backend(backend);
+
+        let screen = take_screen(app.capture_backend.as_ref(), ScreenSelection::Single(0)).unwrap();
+        app.set_temp_image(Some(screen));
+        app.set_select_area(Some(Rect::from_min_max(pos2(8., 4.), pos2(40., 36.))));
+        app.document.push(DrawingType::FilledRectangle {
+            r: Rect::from_min_max(pos2(20., 20.), pos2(30., 30.)),
+            s: Stroke::new(1., Color32::RED),
+            rounding: 0.,
+            l: Default::default(),
+        });
+
+        let annotated = app.annotated_image().unwrap();
+        assert_eq!(annotated.size, [32, 32]);
+        assert_eq!(annotated[(0, 0)], Color32::from_rgb(8, 4, 128));
+        assert_eq!(annotated[(17, 21)], Color32::RED);
+
+        let path = std::env::temp_dir().join(format!("krustygrab_fake_capture_{}.png", std::process::id()));
+        save_image(annotated.clone(), path.clone(), &EncoderSettings::default()).unwrap();
+        let saved = image::open(&path).unwrap().into_rgba8();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((saved.width(), saved.height()), (32, 32));
+        let pixels: Vec<u8> = annotated.pixels.iter().flat_map(|p| p.to_array()).collect();
+        assert_eq!(saved.as_raw(), &pixels);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use egui::{ColorImage, Context};
+
+use crate::screenshot::{
+    capture_backend::{CaptureBackend, ScreenSelection},
+    screen_capture::take_screen,
+};
+
+///Time left to the application window to fade out before the screenshot is taken
+pub const HIDE_DELAY: Duration = Duration::from_millis(150);
+
+///Interval between two checks of the cancel request during the countdown
+const TICK: Duration = Duration::from_millis(100);
+
+///States reported by the worker to the application
+pub enum CaptureState {
+    ///Seconds left before the screenshot
+    Countdown(u64),
+    ///The countdown is over, the window has to be hidden
+    Capturing,
+    Done(ColorImage),
+    Failed(String),
+    Cancelled,
+}
+
+///Screenshot taken in a background thread, after an optional delay
+pub struct CaptureWorker {
+    receiver: Receiver<CaptureState>,
+    cancel: Arc<AtomicBool>,
+    remaining: Option<u64>,
+    crop: bool,
+}
+
+impl CaptureWorker {
+    ///Start the countdown and take the screenshot once it is over. The context is used to wake up the UI at every change of state.
+    ///If `crop` is set, the area selection has to be shown once the screenshot is delivered.
+    pub fn spawn(backend: Arc<dyn CaptureBackend>, selection: ScreenSelection, delay: Duration, crop: bool, ctx: &Context) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let worker_cancel = cancel.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || Self::run(backend, selection, delay, worker_cancel, sender, ctx));
+
+        Self {
+            receiver,
+            cancel,
+            remaining: (!delay.is_zero()).then(|| delay.as_secs_f32().ceil() as u64),
+            crop,
+        }
+    }
+
+    fn run(backend: Arc<dyn CaptureBackend>, selection: ScreenSelection, delay: Duration, cancel: Arc<AtomicBool>, sender: Sender<CaptureState>, ctx: Context) {
+        //Every send is followed by a repaint request, so that the state is processed also when there is no user input
+        let notify = |state: CaptureState| {
+            let sent = sender.send(state).is_ok();
+            ctx.request_repaint();
+            sent
+        };
+
+        let deadline = Instant::now() + delay;
+        let mut last_second = None;
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                notify(CaptureState::Cancelled);
+                return;
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+
+            let second = remaining.as_secs_f32().ceil() as u64;
+            if last_second != Some(second) {
+                last_second = Some(second);
+                if !notify(CaptureState::Countdown(second)) {
+                    return;
+                }
+            }
+            thread::sleep(remaining.min(TICK));
+        }
+
+        if !notify(CaptureState::Capturing) {
+            return;
+        }
+        thread::sleep(HIDE_DELAY);
+
+        match take_screen(backend.as_ref(), selection) {
+            Ok(image) => notify(CaptureState::Done(image)),
+            Err(e) => notify(CaptureState::Failed(e.to_string())),
+        };
+    }
+
+    ///Ask the worker to stop. Ignored if the screenshot is already being taken.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    ///Collect the states sent by the worker since the last call
+    pub fn poll(&mut self) -> Vec<CaptureState> {
+        let states: Vec<CaptureState> = self.receiver.try_iter().collect();
+
+        for state in &states {
+            match state {
+                CaptureState::Countdown(s) => self.remaining = Some(*s),
+                _ => self.remaining = None,
+            }
+        }
+        states
+    }
+
+    ///Seconds left before the screenshot, if the countdown is ongoing
+    pub fn remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+
+    pub fn is_crop(&self) -> bool {
+        self.crop
+    }
+}
@@ -1 +1,4 @@
-pub mod screen_capture;
\ No newline at end of file
+pub mod screen_capture;
+pub mod capture_backend;
+pub mod capture_worker;
+pub mod naming;
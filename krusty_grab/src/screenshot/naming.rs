@@ -0,0 +1,222 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Error};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::screenshot::capture_backend::ScreenSelection;
+
+///Template giving the same names used before the templates were configurable, in local time
+pub const DEFAULT_TEMPLATE: &str = "{date:%Y_%m_%d}-{time:%H_%M_%S}";
+
+///Tokens accepted by the templates, shown as help in the preferences
+pub const TEMPLATE_HELP: &str = "{date[:format]}  date, default format %Y_%m_%d
+{time[:format]}  time, default format %H_%M_%S
+{screen}  captured screen, starting from 1, or \"all\"
+{width} {height} {size}  size of the saved image
+{counter[:0N]}  counter increased at every save, padded to N digits";
+
+///What to do when the name given by the template is already used
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum CollisionPolicy {
+    ///Append a number to the name
+    #[default]
+    Suffix,
+    Overwrite,
+    ///Ask the user
+    Prompt,
+}
+
+impl CollisionPolicy {
+    pub const ALL: [CollisionPolicy; 3] = [CollisionPolicy::Suffix, CollisionPolicy::Overwrite, CollisionPolicy::Prompt];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CollisionPolicy::Suffix => "Add a number",
+            CollisionPolicy::Overwrite => "Overwrite",
+            CollisionPolicy::Prompt => "Ask",
+        }
+    }
+}
+
+///Naming of the files saved without choosing the path
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct NamingSettings {
+    pub template: String,
+    pub collision: CollisionPolicy,
+    ///Value of the next `{counter}`
+    pub counter: u64,
+}
+
+impl Default for NamingSettings {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_TEMPLATE.to_string(),
+            collision: CollisionPolicy::default(),
+            counter: 1,
+        }
+    }
+}
+
+///Values replacing the tokens of a template
+pub struct NamingContext {
+    pub time: DateTime<Local>,
+    pub screen: ScreenSelection,
+    pub size: [usize; 2],
+    pub counter: u64,
+}
+
+impl NamingContext {
+    pub fn now(screen: ScreenSelection, size: [usize; 2], counter: u64) -> Self {
+        Self { time: Local::now(), screen, size, counter }
+    }
+}
+
+///Build a file name, without extension, replacing the tokens of the template.
+///Characters not allowed in file names are replaced with `_`.
+pub fn render_template(template: &str, context: &NamingContext) -> Result<String, Error> {
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("Missing \"}}\" after \"{}\"", &rest[start..]))?;
+        name.push_str(&render_token(&rest[start + 1..start + end], context)?);
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+
+    let name: String = name
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c })
+        .collect();
+
+    if name.trim().is_empty() {
+        bail!("The template gives an empty name");
+    }
+    Ok(name)
+}
+
+fn render_token(token: &str, context: &NamingContext) -> Result<String, Error> {
+    let (name, argument) = match token.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (token, None),
+    };
+
+    match (name, argument) {
+        ("date", format) => format_time(&context.time, format.unwrap_or("%Y_%m_%d")),
+        ("time", format) => format_time(&context.time, format.unwrap_or("%H_%M_%S")),
+        ("screen", None) => Ok(match context.screen {
+            ScreenSelection::Single(i) => (i + 1).to_string(),
+            ScreenSelection::All => "all".to_string(),
+        }),
+        ("width", None) => Ok(context.size[0].to_string()),
+        ("height", None) => Ok(context.size[1].to_string()),
+        ("size", None) => Ok(format!("{}x{}", context.size[0], context.size[1])),
+        ("counter", None) => Ok(context.counter.to_string()),
+        ("counter", Some(padding)) => {
+            let digits: usize = padding
+                .parse()
+                .map_err(|_| anyhow!("Invalid counter padding \"{padding}\""))?;
+            Ok(format!("{:0digits$}", context.counter))
+        },
+        (name, Some(_)) if ["screen", "width", "height", "size"].contains(&name) => bail!("Token {{{name}}} takes no format"),
+        (name, _) => bail!("Unknown token {{{name}}}"),
+    }
+}
+
+///Format the time, checking the format first since chrono panics when displaying an invalid one
+fn format_time(time: &DateTime<Local>, format: &str) -> Result<String, Error> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|i| matches!(i, Item::Error)) {
+        bail!("Invalid date format \"{format}\"");
+    }
+    Ok(time.format_with_items(items.into_iter()).to_string())
+}
+
+///Path not used by any file, obtained appending `_1`, `_2`, ... to the name if the given one exists
+pub fn unique_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_string());
+    (1..)
+        .map(|i| {
+            let mut candidate = path.with_file_name(format!("{stem}_{i}"));
+            if let Some(extension) = &extension {
+                candidate.set_extension(extension);
+            }
+            candidate
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("There is always an unused name")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn context(screen: ScreenSelection, counter: u64) -> NamingContext {
+        let time = Local.with_ymd_and_hms(2023, 5, 4, 13, 2, 9).unwrap();
+        NamingContext { time, screen, size: [1920, 1080], counter }
+    }
+
+    #[test]
+    fn tokens_are_replaced() {
+        let context = context(ScreenSelection::Single(0), 7);
+        assert_eq!(render_template(DEFAULT_TEMPLATE, &context).unwrap(), "2023_05_04-13_02_09");
+        assert_eq!(render_template("{date}-{time}", &context).unwrap(), "2023_05_04-13_02_09");
+        assert_eq!(render_template("{date:%d.%m}_{time:%Hh}", &context).unwrap(), "04.05_13h");
+        assert_eq!(render_template("screen{screen}", &context).unwrap(), "screen1");
+        assert_eq!(render_template("{width}_{height}_{size}", &context).unwrap(), "1920_1080_1920x1080");
+        assert_eq!(render_template("shot{counter}", &context).unwrap(), "shot7");
+        assert_eq!(render_template("shot{counter:04}", &context).unwrap(), "shot0007");
+
+        let context = self::context(ScreenSelection::All, 12345);
+        assert_eq!(render_template("{screen}_{counter:2}", &context).unwrap(), "all_12345");
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        let context = context(ScreenSelection::Single(0), 1);
+        for template in ["{unknown}", "{counter:x}", "{width:3}", "{date:%Q}", "capture{date", "", "{date:}", "  "] {
+            assert!(render_template(template, &context).is_err(), "{template:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn characters_not_allowed_in_file_names_are_replaced() {
+        let context = context(ScreenSelection::Single(0), 1);
+        assert_eq!(render_template("a/b\\c:d*e?f\"g<h>i|j", &context).unwrap(), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(render_template("{time:%H:%M}", &context).unwrap(), "13_02");
+    }
+
+    #[test]
+    fn used_paths_get_a_number() {
+        let folder = std::env::temp_dir().join(format!("krustygrab_unique_path_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+
+        let path = folder.join("capture.png");
+        assert_eq!(unique_path(&path), path);
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(unique_path(&path), folder.join("capture_1.png"));
+        std::fs::write(folder.join("capture_1.png"), b"").unwrap();
+        assert_eq!(unique_path(&path), folder.join("capture_2.png"));
+
+        let path = folder.join("capture");
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(unique_path(&path), folder.join("capture_1"));
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1,74 +1,397 @@
 // #![allow(unused)]
 use std::{
-    fs::File,
+    fmt,
+    io::{self, Cursor, Write},
     path::PathBuf,
 };
 
 use anyhow::Error;
+use color_quant::NeuQuant;
 use egui::ColorImage;
-use image::{ImageBuffer, ImageFormat, Rgba};
-use screenshots::Screen;
+use image::{
+    codecs::png::{self, PngEncoder},
+    error::{EncodingError, ImageFormatHint, LimitError, LimitErrorKind},
+    imageops, DynamicImage, ImageBuffer, ImageEncoder, ImageError, ImageFormat, ImageOutputFormat, Rgba, RgbaImage,
+};
+use serde::{Deserialize, Serialize};
 
-///Take a screenshot and converts it in a egui::ColorImage
-pub fn take_screen(screen_src: usize) -> Result<ColorImage, Error> {
-    let screen = Screen::all()?[screen_src];
+use crate::{
+    krustygrab::Format,
+    screenshot::capture_backend::{CaptureBackend, ScreenSelection},
+};
 
-    match screen.capture() {
-        Ok(image) => Ok(ColorImage::from_rgba_unmultiplied(
-            [image.width() as usize, image.height() as usize],
-            image.rgba(),
-        )),
-        Err(e) => Err(e),
+///Take a screenshot and converts it in a egui::ColorImage
+pub fn take_screen(backend: &dyn CaptureBackend, screen_src: ScreenSelection) -> Result<ColorImage, Error> {
+    match screen_src {
+        ScreenSelection::Single(index) => backend.capture_display(index),
+        ScreenSelection::All => backend.capture_all(),
     }
 }
 
 ///Return the number of connected screens
-pub fn screens_number() -> usize {
-    Screen::all()
-        .expect("The screens should be retrieved")
-        .len()
+pub fn screens_number(backend: &dyn CaptureBackend) -> Result<usize, Error> {
+    Ok(backend.displays()?.len())
+}
+
+///Reasons why a screenshot could not be saved
+#[derive(Debug)]
+pub enum SaveError {
+    ///The extension of the path does not match any supported format
+    UnsupportedExtension(Option<String>),
+    ///The file could not be created or written
+    Io(io::Error),
+    ///The image could not be encoded in the requested format
+    Encode(ImageError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::UnsupportedExtension(Some(ext)) => write!(f, "Unsupported file extension \".{ext}\""),
+            SaveError::UnsupportedExtension(None) => write!(f, "The file name has no extension"),
+            SaveError::Io(e) => write!(f, "Unable to write the file: {e}"),
+            SaveError::Encode(e) => write!(f, "Unable to encode the image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SaveError::UnsupportedExtension(_) => None,
+            SaveError::Io(e) => Some(e),
+            SaveError::Encode(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<ImageError> for SaveError {
+    fn from(e: ImageError) -> Self {
+        match e {
+            ImageError::IoError(e) => SaveError::Io(e),
+            e => SaveError::Encode(e),
+        }
+    }
+}
+
+///Compression level of the PNG encoder
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PngCompression {
+    Default,
+    Fast,
+    Best,
+}
+
+impl PngCompression {
+    pub const ALL: [PngCompression; 3] = [PngCompression::Default, PngCompression::Fast, PngCompression::Best];
+
+    fn to_image(self) -> png::CompressionType {
+        match self {
+            PngCompression::Default => png::CompressionType::Default,
+            PngCompression::Fast => png::CompressionType::Fast,
+            PngCompression::Best => png::CompressionType::Best,
+        }
+    }
+}
+
+///Filter applied to the rows before the PNG compression
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PngFilter {
+    NoFilter,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    Adaptive,
+}
+
+impl PngFilter {
+    pub const ALL: [PngFilter; 6] = [PngFilter::NoFilter, PngFilter::Sub, PngFilter::Up, PngFilter::Avg, PngFilter::Paeth, PngFilter::Adaptive];
+
+    fn to_image(self) -> png::FilterType {
+        match self {
+            PngFilter::NoFilter => png::FilterType::NoFilter,
+            PngFilter::Sub => png::FilterType::Sub,
+            PngFilter::Up => png::FilterType::Up,
+            PngFilter::Avg => png::FilterType::Avg,
+            PngFilter::Paeth => png::FilterType::Paeth,
+            PngFilter::Adaptive => png::FilterType::Adaptive,
+        }
+    }
+}
+
+///Settings of the encoders, the ones used depend on the format of the saved file
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct EncoderSettings {
+    ///Jpeg quality, from 1 to 100
+    pub jpeg_quality: u8,
+    pub png_compression: PngCompression,
+    pub png_filter: PngFilter,
+    ///Colors of the Gif palette, from 2 to 256
+    pub gif_colors: u16,
+    pub gif_dithering: bool,
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        Self {
+            jpeg_quality: 75,
+            png_compression: PngCompression::Default,
+            png_filter: PngFilter::Adaptive,
+            gif_colors: 256,
+            gif_dithering: false,
+        }
+    }
 }
 
-pub fn save_image(image: ColorImage, save_path: PathBuf) -> Result<(), Error> {
+///Save the image in the format given by the extension of the path, with the given encoder settings
+pub fn save_image(image: ColorImage, save_path: PathBuf, settings: &EncoderSettings) -> Result<(), SaveError> {
+    let format = match save_path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => Format::ALL
+            .into_iter()
+            .find(|f| f.extensions().contains(&ext.to_lowercase().as_str()))
+            .ok_or_else(|| SaveError::UnsupportedExtension(Some(ext.to_string())))?,
+        None => return Err(SaveError::UnsupportedExtension(None)),
+    };
+
     //Converting from COlorImage to Vec<u8> for saving
     let pix: Vec<u8> = image
         .pixels
         .iter()
-        .flat_map(|p| p.to_array().iter().copied().collect::<Vec<u8>>())
+        .flat_map(|p| p.to_array())
         .collect();
 
     let im: ImageBuffer<Rgba<u8>, Vec<_>> =
         ImageBuffer::from_vec(image.width() as u32, image.height() as u32, pix)
-            .expect("Unable to obtain ImageBuffer from vec");
-    
-    match save_path.clone().extension() {
-        Some(ext) => {
-            match ext.to_str().expect("Path string must be convertable") {
-                "png" => {
-                    im.save_with_format(save_path, ImageFormat::Png)
-                        .expect("Unable to save the image");
-
-                    return Ok(());
-                },
-                "jpg" => {
-                    im.save_with_format(save_path, ImageFormat::Jpeg)
-                        .expect("Unable to save the image");
-        
-                    return Ok(());
-                },
-                "gif" => {
-                    let buffer = File::create(save_path).expect("Unable to create image file");
-                    let mut gif_encoder = image::codecs::gif::GifEncoder::new_with_speed(buffer, 30);
-        
-                    let frame = image::Frame::new(im);
-                    gif_encoder
-                        .encode_frame(frame)
-                        .expect("Unable to encode gif frame");
-                    return Ok(());
-                },
-                _ => unreachable!("How did you ended up here??"),
-            };
+            .expect("ColorImage buffer always matches its size");
+
+    //The image is encoded in memory, so that a failed encoding does not leave a broken file
+    let mut buffer = Cursor::new(Vec::new());
+
+    match format {
+        Format::Png => PngEncoder::new_with_quality(&mut buffer, settings.png_compression.to_image(), settings.png_filter.to_image())
+            .write_image(im.as_raw(), im.width(), im.height(), image::ColorType::Rgba8)?,
+        //Jpeg does not support the alpha channel
+        Format::Jpeg => DynamicImage::ImageRgba8(im)
+            .to_rgb8()
+            .write_to(&mut buffer, ImageOutputFormat::Jpeg(settings.jpeg_quality.clamp(1, 100)))?,
+        Format::Gif => write_gif(im, &mut buffer, settings)?,
+        Format::WebP => {
+            //The image crate can not encode WebP, libwebp is used in lossless mode
+            let encoded = webp::Encoder::from_rgba(im.as_raw(), im.width(), im.height())
+                .encode_simple(true, 100.0)
+                .map_err(|e| ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(ImageFormat::WebP), format!("{e:?}"))))?;
+            buffer.write_all(&encoded)?;
         },
-        None => unreachable!("File saving extension must be Some"),
+        Format::Bmp => im.write_to(&mut buffer, ImageOutputFormat::Bmp)?,
+        Format::Tiff => im.write_to(&mut buffer, ImageOutputFormat::Tiff)?,
+        Format::Qoi => im.write_to(&mut buffer, ImageOutputFormat::Qoi)?,
+    };
+
+    std::fs::write(save_path, buffer.into_inner())?;
+    Ok(())
+}
+
+///Reduce the image to the palette size of the settings, optionally with dithering, and write it as a Gif
+fn write_gif<W: Write>(mut im: RgbaImage, writer: &mut W, settings: &EncoderSettings) -> Result<(), ImageError> {
+    let encoding_error = |e: gif::EncodingError| ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(ImageFormat::Gif), e));
+
+    let (width, height) = match (u16::try_from(im.width()), u16::try_from(im.height())) {
+        (Ok(w), Ok(h)) => (w, h),
+        _ => return Err(ImageError::Limits(LimitError::from_kind(LimitErrorKind::DimensionError))),
+    };
+
+    let quantizer = NeuQuant::new(10, settings.gif_colors.clamp(2, 256) as usize, im.as_raw());
+    if settings.gif_dithering {
+        imageops::dither(&mut im, &quantizer);
+    }
+
+    let palette_rgba = quantizer.color_map_rgba();
+    let palette: Vec<u8> = palette_rgba.chunks_exact(4).flat_map(|c| [c[0], c[1], c[2]]).collect();
+
+    //Gif has a single transparent color: the most transparent of the palette, used for every pixel mapped to a mostly transparent color
+    let transparent = palette_rgba
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(_, c)| c[3] < 128)
+        .min_by_key(|(_, c)| c[3])
+        .map(|(i, _)| i as u8);
+
+    let indexes: Vec<u8> = im
+        .pixels()
+        .map(|p| {
+            let index = quantizer.index_of(&p.0);
+            match transparent {
+                Some(t) if palette_rgba[index * 4 + 3] < 128 => t,
+                _ => index as u8,
+            }
+        })
+        .collect();
+
+    let frame = gif::Frame::from_palette_pixels(width, height, &indexes, &palette, transparent);
+    let mut encoder = gif::Encoder::new(writer, width, height, &[]).map_err(encoding_error)?;
+    encoder.write_frame(&frame).map_err(encoding_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+
+    use super::*;
+
+    #[test]
+    fn unsupported_extensions_are_rejected() {
+        let image = ColorImage::new([2, 2], Color32::WHITE);
+        let path = std::env::temp_dir().join("krustygrab_unsupported.svg");
+
+        assert!(matches!(save_image(image.clone(), path.clone(), &EncoderSettings::default()), Err(SaveError::UnsupportedExtension(Some(ext))) if ext == "svg"));
+        assert!(matches!(save_image(image, PathBuf::from("no_extension"), &EncoderSettings::default()), Err(SaveError::UnsupportedExtension(None))));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn missing_folder_is_an_io_error() {
+        let image = ColorImage::new([2, 2], Color32::WHITE);
+        let path = std::env::temp_dir().join("krustygrab_missing_folder").join("shot.png");
+
+        assert!(matches!(save_image(image, path, &EncoderSettings::default()), Err(SaveError::Io(_))));
+    }
+
+    #[test]
+    fn failed_encoding_leaves_the_file_untouched() {
+        let path = std::env::temp_dir().join(format!("krustygrab_failed_{}.gif", std::process::id()));
+        std::fs::write(&path, b"previous content").unwrap();
+
+        //Gif sides are limited to 16 bits
+        let image = ColorImage::new([70_000, 1], Color32::WHITE);
+        assert!(matches!(save_image(image.clone(), path.clone(), &EncoderSettings::default()), Err(SaveError::Encode(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), b"previous content");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(save_image(image, path.clone(), &EncoderSettings::default()).is_err());
+        assert!(!path.exists());
+    }
+
+    ///Image with transparent and colored pixels
+    fn sample_image() -> ColorImage {
+        let mut image = ColorImage::new([7, 5], Color32::TRANSPARENT);
+        for y in 0..5 {
+            for x in 0..7 {
+                if (x + y) % 3 != 0 {
+                    image[(x, y)] = Color32::from_rgb(x as u8 * 30, y as u8 * 50, 200);
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn every_format_is_saved() {
+        let image = sample_image();
+        let expected: Vec<u8> = image.pixels.iter().flat_map(|p| p.to_array()).collect();
+
+        for format in Format::ALL {
+            let path = std::env::temp_dir().join(format!("krustygrab_format_{}.{}", std::process::id(), format));
+            save_image(image.clone(), path.clone(), &EncoderSettings::default()).unwrap();
+            let saved = image::open(&path).unwrap().into_rgba8();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!((saved.width(), saved.height()), (7, 5), "{format:?}");
+            //Lossless formats keep every pixel, alpha included
+            if matches!(format, Format::Png | Format::WebP | Format::Bmp | Format::Tiff | Format::Qoi) {
+                assert_eq!(saved.as_raw(), &expected, "{format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn extensions_are_case_insensitive() {
+        let path = std::env::temp_dir().join(format!("krustygrab_upper_{}.WEBP", std::process::id()));
+        save_image(sample_image(), path.clone(), &EncoderSettings::default()).unwrap();
+        assert_eq!(image::image_dimensions(&path).unwrap(), (7, 5));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn oversized_webp_is_an_encoding_error() {
+        //WebP sides are limited to 16383 pixels
+        let path = std::env::temp_dir().join(format!("krustygrab_oversized_{}.webp", std::process::id()));
+        let image = ColorImage::new([20_000, 1], Color32::WHITE);
+
+        assert!(matches!(save_image(image, path.clone(), &EncoderSettings::default()), Err(SaveError::Encode(_))));
+        assert!(!path.exists());
+    }
+
+    ///Gradient with many different colors
+    fn gradient_image() -> ColorImage {
+        let mut image = ColorImage::new([64, 64], Color32::BLACK);
+        for y in 0..64 {
+            for x in 0..64 {
+                image[(x, y)] = Color32::from_rgb(x as u8 * 4, y as u8 * 4, (x + y) as u8 * 2);
+            }
+        }
+        image
+    }
+
+    ///Encode the image through a temporary file, each call uses its own file since tests run in parallel
+    fn encoded(image: &ColorImage, extension: &str, settings: &EncoderSettings) -> Vec<u8> {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("krustygrab_settings_{}_{}.{}", std::process::id(), id, extension));
+        save_image(image.clone(), path.clone(), settings).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn png_settings_keep_the_pixels() {
+        let image = gradient_image();
+        let expected: Vec<u8> = image.pixels.iter().flat_map(|p| p.to_array()).collect();
+
+        for png_compression in PngCompression::ALL {
+            for png_filter in PngFilter::ALL {
+                let settings = EncoderSettings { png_compression, png_filter, ..Default::default() };
+                let decoded = image::load_from_memory(&encoded(&image, "png", &settings)).unwrap().into_rgba8();
+                assert_eq!(decoded.as_raw(), &expected, "{png_compression:?} {png_filter:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn jpeg_quality_changes_the_size() {
+        let image = gradient_image();
+        let low = encoded(&image, "jpg", &EncoderSettings { jpeg_quality: 10, ..Default::default() });
+        let high = encoded(&image, "jpg", &EncoderSettings { jpeg_quality: 100, ..Default::default() });
+        assert!(low.len() < high.len());
+    }
+
+    #[test]
+    fn gif_palette_is_limited_to_the_colors() {
+        let image = gradient_image();
+
+        for gif_dithering in [false, true] {
+            let settings = EncoderSettings { gif_colors: 8, gif_dithering, ..Default::default() };
+            let decoded = image::load_from_memory(&encoded(&image, "gif", &settings)).unwrap().into_rgba8();
+            let colors: std::collections::HashSet<[u8; 4]> = decoded.pixels().map(|p| p.0).collect();
+            assert!(colors.len() <= 8, "{} colors with dithering {gif_dithering}", colors.len());
+        }
+    }
+
+    #[test]
+    fn gif_keeps_transparent_pixels() {
+        let image = sample_image();
+        let decoded = image::load_from_memory(&encoded(&image, "gif", &EncoderSettings::default())).unwrap().into_rgba8();
+
+        for (pixel, original) in decoded.pixels().zip(&image.pixels) {
+            assert_eq!(pixel.0[3] == 0, original.a() == 0);
+        }
     }
 }
@@ -1,20 +1,41 @@
-use std::borrow::Cow;
-
 use egui::{
-    pos2, Button, CentralPanel, Color32, Context, CursorIcon, Id, LayerId, Layout, Painter, Pos2,
+    emath::RectTransform, pos2, vec2, Button, CentralPanel, Color32, Context, CursorIcon, Id, LayerId, Layout, Painter, Pos2,
     Rect, Vec2, Order,
 };
 use egui_extras::RetainedImage;
-use arboard::{Clipboard, ImageData};
 
 
 use crate::krustygrab::{GrabStatus, KrustyGrab, WindowStatus};
 
 impl KrustyGrab {
     const OVERLAY_COLOR: Color32 = Color32::from_black_alpha(100);
-    const ADJUST_POINTS_COLOR: Color32 = Color32::from_rgb(255, 255, 255);
-    const ADJUST_POINTS_ROUNDING: f32 = 9.0;
-    const GRABBABLE_POINTS_SIZE: f32 = 10.0;
+    pub const ADJUST_POINTS_COLOR: Color32 = Color32::from_rgb(255, 255, 255);
+    pub const ADJUST_POINTS_ROUNDING: f32 = 9.0;
+    pub const GRABBABLE_POINTS_SIZE: f32 = 10.0;
+
+    ///Transform from the screenshot pixels, in which the selected area is expressed, to the part of the window where the screenshot is visualized.
+    ///The screenshot is fitted in the window keeping its aspect ratio, so that screenshots of all the screens or with a different scale factor can be selected too.
+    fn crop_view(&self, window_size: Vec2) -> RectTransform {
+        let image = self.get_temp_image().expect("Image must be defined");
+        let image_rect = Rect::from_min_size(pos2(0.0, 0.0), vec2(image.width() as f32, image.height() as f32));
+
+        let scale = (window_size.x / image_rect.width()).min(window_size.y / image_rect.height());
+        let view = Rect::from_center_size((window_size / 2.0).to_pos2(), image_rect.size() * scale);
+
+        RectTransform::from_to(image_rect, view)
+    }
+
+    ///Size of the screenshot, used as boundary for the selected area
+    fn crop_image_size(&self) -> Vec2 {
+        let image = self.get_temp_image().expect("Image must be defined");
+        vec2(image.width() as f32, image.height() as f32)
+    }
+
+    ///Show the crop window, remembering the selected area to restore it if the crop is cancelled
+    pub fn start_crop(&mut self) {
+        self.set_prev_area(self.get_selected_area());
+        self.set_window_status(WindowStatus::Crop);
+    }
 
     ///Manage the visualization of the area selection.
     pub fn crop_screen_window(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
@@ -23,6 +44,9 @@ impl KrustyGrab {
             frame.set_fullscreen(true);
         }
 
+        //Area selected when the crop started, restored by Cancel and recorded in the history by Save
+        let prev_area = self.get_prev_area();
+
         CentralPanel::default().show(ctx, |_ui| {
             let window_size = frame.info().window_info.size;
             let mut painter = ctx.layer_painter(LayerId::background());
@@ -54,50 +78,42 @@ impl KrustyGrab {
 
                             let pointer_pos = ctx.pointer_hover_pos();
 
-                            if pointer_pos.is_some(){
+                            if let Some(pointer_pos) = pointer_pos {
                                 //Save button area
-                                if save_rect.contains(pointer_pos.unwrap())
+                                if save_rect.contains(pointer_pos)
                                 {
                                     ctx.set_cursor_icon(CursorIcon::PointingHand);
                                     save.highlight();
     
                                     //If clicked
                                     if ctx.input(|i| i.pointer.primary_clicked()) {
+                                        self.record_crop(prev_area);
+
                                         if self.get_selected_area().is_some() {
                                             //Save the screen part inside the selected area.
                                             let im = self.get_temp_image()
                                                 .unwrap()
                                                 .region(&self.get_selected_area().unwrap(), None);
-                                        
-                                            let mut clipboard = Clipboard::new().expect("Unable to create clipboard");
-                                            if let Err(e) = clipboard.set_image(ImageData { width: im.width(), height: im.height(), bytes: Cow::from(im.as_raw().clone())}) {
-                                                tracing::error!("Unable to copy in the clipboard: {e:?}");
+
+                                            //The clipboard receives the selected part with the drawings rendered on it
+                                            if let Some(annotated) = self.annotated_image_or_notify() {
+                                                self.copy_to_clipboard(&annotated);
                                             }
                                             
                                             self.set_definitive_image(Some(im));
                                         }
     
-                                        ctx.memory_mut(|mem| {
-                                            mem.data.insert_temp(Id::from("Prev_area"), self.get_selected_area());
-                                        });
-    
                                         pressed = true;
                                     }
                                 }
                                 //Cancel button
-                                else if cancel_rect.contains(pointer_pos.unwrap())
+                                else if cancel_rect.contains(pointer_pos)
                                 {
                                     ctx.set_cursor_icon(CursorIcon::PointingHand);
                                     cancel.highlight();
     
                                     if ctx.input(|i| i.pointer.primary_clicked()) {
                                         //restore the prev area
-                                        let prev_area = ctx.memory_mut(|mem| {
-                                            mem
-                                                .data
-                                                .get_temp::<Option<Rect>>(Id::from("Prev_area"))
-                                        }).unwrap_or_else(|| {None});
-
                                         self.set_select_area(prev_area);
     
                                         pressed = true;
@@ -116,16 +132,17 @@ impl KrustyGrab {
             }
             else{
                 //Setting the visualization area and the screenshot as background
+                let view = self.crop_view(window_size);
                 painter.set_clip_rect(Rect::from_min_size(pos2(0.0, 0.0), window_size));
                 painter.image(
                     image.texture_id(ctx),
-                    Rect::from_min_size(pos2(0.0, 0.0), window_size),
+                    *view.to(),
                     Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
                     Color32::WHITE,
                 );
     
                 //render the drawings
-                self.show_drawings_in_select(ctx, &painter);
+                self.show_drawings(ctx, &painter, &view);
     
                 //Show the selected area if present
                 self.show_selected_area(ctx, frame, &mut painter);
@@ -160,20 +177,21 @@ impl KrustyGrab {
         {
             self.set_grab_status(GrabStatus::Select);
 
-            //Get drag initial and actual position
-            let mut init_pos = ctx.input(|i| {
+            //Get drag initial and actual position, converted in screenshot coordinates
+            let to_image = self.crop_view(frame.info().window_info.size).inverse();
+            let mut init_pos = to_image.transform_pos(ctx.input(|i| {
                 i.pointer
                     .press_origin()
                     .expect("Press origin must be defined")
-            });
-            let mut drag_pos = ctx
+            }));
+            let mut drag_pos = to_image.transform_pos(ctx
                 .pointer_hover_pos()
-                .expect("Hover position must be some");
+                .expect("Hover position must be some"));
 
             //Update the saved area
             if init_pos != drag_pos {
                 //Eventually modify to keep them inside boundaries/have the correct min and max 
-                (init_pos, drag_pos) = self.adjust_coordinates(init_pos, drag_pos, frame.info().window_info.size);
+                (init_pos, drag_pos) = self.adjust_coordinates(init_pos, drag_pos, self.crop_image_size());
                 self.set_select_area(Some(Rect::from_min_max(init_pos, drag_pos)));
             }
         }
@@ -191,6 +209,7 @@ impl KrustyGrab {
         //Check if the area is Some, otherwise draw the background overlay on all the screen
         match self.get_selected_area() {
             Some(sel) => {
+                let sel = self.crop_view(window_size).transform_rect(sel);
                 let min_x = sel.min.x;
                 let max_x = sel.max.x;
 
@@ -238,9 +257,12 @@ impl KrustyGrab {
         frame: &mut eframe::Frame,
         painter: &mut Painter,
     ) {
-        let sel = self
-            .get_selected_area()
-            .expect("Selected area must be Some");
+        //Handle points are managed in window coordinates
+        let sel = self.crop_view(frame.info().window_info.size).transform_rect(
+            self
+                .get_selected_area()
+                .expect("Selected area must be Some")
+        );
 
         let grab_status = self.get_grab_status();
 
@@ -325,7 +347,9 @@ impl KrustyGrab {
         pos: Pos2,
         status: GrabStatus,
     ) {
-        //Retrieve previous area values
+        //Retrieve previous area values. The pointer position is converted in screenshot coordinates as the area
+        let to_image = self.crop_view(frame.info().window_info.size).inverse();
+        let pos = to_image.transform_pos(pos);
         let sel = self
             .get_selected_area()
             .expect("Selected area must be Some when updating it");
@@ -337,6 +361,7 @@ impl KrustyGrab {
             match self.get_grab_status() {
                 GrabStatus::None => self.set_grab_status(status), //Set the passed status if enters with None
                 GrabStatus::Select => unreachable!("Should not be in Select mode during area updating"), //Unreachable code, should panic if reached
+                GrabStatus::Control => unreachable!("The area has no control point"),
                 GrabStatus::TopLeft => new_min = pos,
                 GrabStatus::TopMid => new_min = pos2(sel.min.x, pos.y),
                 GrabStatus::TopRight => {
@@ -356,17 +381,15 @@ impl KrustyGrab {
 
                     //Save the distance from center of the selected area and the cursor if the Move operation just started
                     let center_distance =
-                        match ctx.memory(|mem| mem.data.get_temp(Id::from("Center_distance"))) {
+                        match self.get_center_distance() {
                             Some(distance) => distance,
                             None => {
-                                let start_coord = ctx
+                                let start_coord = to_image.transform_pos(ctx
                                     .pointer_interact_pos()
-                                    .expect("Pointer position must be found")
+                                    .expect("Pointer position must be found"))
                                     .to_vec2();
                                 let distance = start_coord - sel.center().to_vec2();
-                                ctx.memory_mut(|mem| {
-                                    mem.data.insert_temp(Id::from("Center_distance"), distance)
-                                });
+                                self.set_center_distance(Some(distance));
                                 distance
                             }
                         };
@@ -374,12 +397,12 @@ impl KrustyGrab {
                     //Updated center considering the pointer position
                     let mut new_center = pos2(pos.x - center_distance.x, pos.y - center_distance.y);
 
-                    //Check if the position of the new center in order to keep it inside the visualized window aka the screenshot area
+                    //Check if the position of the new center in order to keep it inside the screenshot area
                     {
                         let size = sel.size();
-                        let window_size = frame.info().window_info.size;
+                        let image_size = self.crop_image_size();
 
-                        new_center = new_center.clamp((size / 2.).to_pos2(), (window_size.to_pos2() - pos2(size[0] / 2., size[1] / 2.)).to_pos2());
+                        new_center = new_center.clamp((size / 2.).to_pos2(), (image_size.to_pos2() - pos2(size[0] / 2., size[1] / 2.)).to_pos2());
                     }
 
                     //Update the area withe the new center
@@ -389,23 +412,23 @@ impl KrustyGrab {
 
             //Update the selected area, after checks, if not in Move mode (area updated in the match clause)
             if self.get_grab_status() != GrabStatus::Move {
-                (new_min, new_max) = self.adjust_coordinates(new_min, new_max, frame.info().window_info.size);
+                (new_min, new_max) = self.adjust_coordinates(new_min, new_max, self.crop_image_size());
                 self.set_select_area(Some(Rect::from_min_max(new_min, new_max)));
             }
         }
         //Mouse button released -> reset status and used values
         else {
             if self.get_grab_status() == GrabStatus::Move {
-                ctx.memory_mut(|mem| mem.data.remove::<Vec2>(Id::from("Center_distance")));
+                self.set_center_distance(None);
             }
             self.set_grab_status(GrabStatus::None);
         }
     }
 
-    ///Checks inside the visualized window and which one is the min 
-    fn adjust_coordinates(&mut self, start: Pos2, end: Pos2, window_size: Vec2) -> (Pos2, Pos2) {
-        let mut init_pos = start.clamp(pos2(0., 0.), window_size.to_pos2());
-        let mut end_pos = end.clamp(pos2(0., 0.), window_size.to_pos2());
+    ///Checks inside the screenshot and which one is the min 
+    fn adjust_coordinates(&mut self, start: Pos2, end: Pos2, image_size: Vec2) -> (Pos2, Pos2) {
+        let mut init_pos = start.clamp(pos2(0., 0.), image_size.to_pos2());
+        let mut end_pos = end.clamp(pos2(0., 0.), image_size.to_pos2());
 
         //Status needed during area manipolation in order to set the right one when min and max positions gets inverted
         let mut grab_status = self.get_grab_status();
@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+
+use egui::{Align2, ColorImage, Context, CursorIcon, Layout, RichText, TextStyle, Window};
+
+use crate::{krustygrab::{Format, KrustyGrab}, screenshot::screen_capture::save_image};
+
+///Failed operation, kept until the user retries or dismisses it
+pub struct PendingFileError {
+    ///Image to save again. None for the failures that can not be retried from the notification, e.g. of a file dialog.
+    pub image: Option<ColorImage>,
+    pub path: Option<PathBuf>,
+    ///Operation that failed, e.g. "save" or "open"
+    pub action: &'static str,
+    pub error: String,
+    ///Set for the quick saves, so that a successful retry uses up their number of the file names
+    pub quick_save: bool,
+}
+
+impl KrustyGrab {
+    ///Notify a failed operation that can not be retried, like opening an image or showing a file dialog
+    pub fn notify_file_error(&mut self, action: &'static str, path: Option<PathBuf>, error: String) {
+        tracing::error!("Unable to {} {:?}: {}", action, path, error);
+        self.file_error = Some(PendingFileError { image: None, path, action, error, quick_save: false });
+    }
+
+    ///Save the image in the given path. If it fails, the image is kept and the error is notified so the save can be retried.
+    ///Return true if the image has been saved.
+    pub fn save_or_notify(&mut self, image: ColorImage, path: PathBuf) -> bool {
+        match save_image(image.clone(), path.clone(), &self.config.encoder) {
+            Ok(()) => {
+                tracing::info!("Image saved in {:?}", path);
+                self.file_error = None;
+                true
+            },
+            Err(e) => {
+                tracing::error!("Unable to save the image in {:?}: {}", path, e);
+                self.file_error = Some(PendingFileError { image: Some(image), path: Some(path), action: "save", error: e.to_string(), quick_save: false });
+                false
+            },
+        }
+    }
+
+    ///Return the screenshot with the drawings rendered on it, notifying the failure if it can not be rendered
+    pub fn annotated_image_or_notify(&mut self) -> Option<ColorImage> {
+        let image = self.annotated_image();
+        if image.is_none() {
+            self.notify_file_error("render the annotated screenshot", None, "The drawings could not be rendered".to_string());
+        }
+        image
+    }
+
+    ///Dismiss the notification of the last failure
+    pub fn dismiss_file_error(&mut self) {
+        self.file_error = None;
+    }
+
+    ///Save again the kept image in its path, or in a new one if given
+    pub fn retry_save(&mut self, new_path: Option<PathBuf>) {
+        let pending = match self.file_error.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+
+        match (pending.image, new_path.or(pending.path)) {
+            (Some(image), Some(path)) if pending.quick_save => self.quick_save_to(image, path),
+            (Some(image), Some(path)) => {
+                self.save_or_notify(image, path);
+            },
+            _ => tracing::warn!("The last failure can not be retried"),
+        }
+    }
+
+    ///Notification shown when the last operation failed, with the options to retry it when the image is kept
+    pub fn file_error_window(&mut self, ctx: &Context) {
+        let (title, message, error, can_save_as, can_retry) = match &self.file_error {
+            Some(pending) => (
+                format!("Unable to {}", pending.action),
+                pending.path.as_ref().map(|path| path.display().to_string()),
+                pending.error.clone(),
+                pending.image.is_some(),
+                pending.image.is_some() && pending.path.is_some(),
+            ),
+            None => return,
+        };
+
+        Window::new(RichText::new(title).text_style(TextStyle::Body))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+            .show(ctx, |ui| {
+                if let Some(message) = message {
+                    ui.label(RichText::new(message).text_style(TextStyle::Body));
+                }
+                ui.label(RichText::new(error).text_style(TextStyle::Small));
+                ui.add_space(5.);
+
+                ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
+                    if ui
+                        .button(RichText::new("Dismiss").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.dismiss_file_error();
+                    }
+
+                    if can_save_as && ui
+                        .button(RichText::new("Save as").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        if let Ok(Some(new_path)) = Format::file_dialog()
+                            .show_save_single_file()
+                        {
+                            self.retry_save(Some(new_path));
+                        }
+                    }
+
+                    if can_retry && ui
+                        .button(RichText::new("Retry").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.retry_save(None);
+                    }
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+
+    use super::*;
+
+    #[test]
+    fn failed_save_keeps_the_image() {
+        let mut app = KrustyGrab::default();
+        let path = std::env::temp_dir().join("krustygrab_no_format.unknown");
+        app.save_or_notify(ColorImage::new([2, 2], Color32::WHITE), path.clone());
+
+        let pending = app.file_error.as_ref().unwrap();
+        assert!(pending.image.is_some());
+        assert_eq!(pending.path.as_ref(), Some(&path));
+        assert_eq!(pending.action, "save");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn failed_save_can_be_retried_in_another_path() {
+        let mut app = KrustyGrab::default();
+        app.save_or_notify(ColorImage::new([2, 2], Color32::WHITE), PathBuf::from("no_format.unknown"));
+
+        let path = std::env::temp_dir().join(format!("krustygrab_retry_{}.png", std::process::id()));
+        app.retry_save(Some(path.clone()));
+        assert!(app.file_error.is_none());
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_errors_are_notified_without_retry() {
+        let mut app = KrustyGrab::default();
+        app.notify_file_error("open", Some(PathBuf::from("missing.png")), "Not found".to_string());
+
+        let pending = app.file_error.as_ref().unwrap();
+        assert!(pending.image.is_none());
+        assert_eq!(pending.action, "open");
+
+        //Nothing to save again, the notification is only dismissed
+        app.retry_save(Some(std::env::temp_dir().join("krustygrab_never_saved.png")));
+        assert!(app.file_error.is_none());
+
+        app.notify_file_error("copy in the clipboard", None, "No clipboard".to_string());
+        app.dismiss_file_error();
+        assert!(app.file_error.is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use egui::{Color32, ColorImage, Context, CursorIcon, Rect, RichText, ScrollArea, TextStyle, Window};
+
+use crate::{
+    krustygrab::KrustyGrab,
+    painting::{
+        document::{HistoryStep, ScreenChange},
+        history::{Command, ImageState},
+    },
+};
+
+impl KrustyGrab {
+    ///Undo the last edit. Return false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let step = self.document.undo();
+        self.apply_history_step(step)
+    }
+
+    ///Redo the last undone edit. Return false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let step = self.document.redo();
+        self.apply_history_step(step)
+    }
+
+    ///Undo or redo until the given number of edits is applied
+    pub fn jump_to_history(&mut self, applied: usize) {
+        while self.document.history().done().count() > applied && self.undo() {}
+        while self.document.history().done().count() < applied && self.redo() {}
+    }
+
+    ///Show a new image, e.g. a screenshot, removing the drawings. The previous image can be restored with undo.
+    pub fn replace_image(&mut self, image: ColorImage) {
+        let before = ImageState {
+            image: self.get_temp_image(),
+            selected_area: self.get_selected_area(),
+            drawings: self.document.drawings().to_vec(),
+        };
+        let after = ImageState { image: Some(image), selected_area: None, drawings: Vec::new() };
+
+        self.set_temp_image(after.image.clone());
+        self.set_select_area(None);
+        self.document.record(Command::ReplaceImage { before: Box::new(before), after: Box::new(after) });
+    }
+
+    ///Record the change of the selected area confirmed in the crop window
+    pub fn record_crop(&mut self, before: Option<Rect>) {
+        let after = self.get_selected_area();
+        if before != after {
+            self.document.record(Command::Crop { before, after });
+        }
+    }
+
+    ///Show the screenshot resulting from an undo or redo. Return false if nothing was undone or redone.
+    fn apply_history_step(&mut self, step: HistoryStep) -> bool {
+        match step {
+            HistoryStep::Nothing => return false,
+            HistoryStep::Drawings => {},
+            HistoryStep::Screen(ScreenChange::Area(area)) => self.show_area(area),
+            HistoryStep::Screen(ScreenChange::Image { image, selected_area }) => {
+                self.set_temp_image(image);
+                self.show_area(selected_area);
+            },
+        }
+        true
+    }
+
+    ///Select an area of the screenshot, showing only its content
+    pub(crate) fn show_area(&mut self, area: Option<Rect>) {
+        self.set_select_area(area);
+        let screen = match (self.get_temp_image(), area) {
+            (Some(image), Some(area)) => Some(image.region(&area, None)),
+            (image, None) => image,
+            (None, Some(_)) => None,
+        };
+        self.set_definitive_image(screen);
+    }
+
+    ///List of the edits. Clicking an edit brings the screenshot back to the state right after it.
+    pub fn history_window(&mut self, ctx: &Context) {
+        let mut open = true;
+        let mut jump = None;
+
+        Window::new(RichText::new("History").text_style(TextStyle::Body))
+            .open(&mut open)
+            .default_width(200.)
+            .show(ctx, |ui| {
+                ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                    let history = self.document.history();
+                    let applied = history.done().count();
+
+                    if ui
+                        .selectable_label(applied == 0, RichText::new("Start").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        jump = Some(0);
+                    }
+
+                    for (i, command) in history.done().enumerate() {
+                        if ui
+                            .selectable_label(i + 1 == applied, RichText::new(command.label()).text_style(TextStyle::Body))
+                            .on_hover_cursor(CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            jump = Some(i + 1);
+                        }
+                    }
+
+                    //The undone edits are greyed, they can be redone until a new edit is made
+                    for (i, command) in history.undone().enumerate() {
+                        if ui
+                            .selectable_label(false, RichText::new(command.label()).text_style(TextStyle::Body).color(Color32::GRAY))
+                            .on_hover_cursor(CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            jump = Some(applied + i + 1);
+                        }
+                    }
+                });
+            });
+
+        if let Some(applied) = jump {
+            self.jump_to_history(applied);
+        }
+        self.history_window = open;
+    }
+}
@@ -1,38 +1,32 @@
-use std::{borrow::Cow, thread, time::Duration};
+use std::{borrow::Cow, time::Duration};
 
-use egui::{Context, TopBottomPanel, menu, RichText, TextStyle, Layout, Button, ColorImage, CentralPanel, Widget, Id, CursorIcon};
+use egui::{Context, TopBottomPanel, menu, RichText, TextStyle, Layout, Button, ColorImage, CentralPanel, Widget, CursorIcon, Window, Align2, Key};
 use image::open;
-use crate::{krustygrab::{KrustyGrab, self}, painting::{icons::{icon_img, ICON_SIZE}, drawing::RedoList}, painting::drawing::DrawingType, screenshot::screen_capture::screens_number};
-pub use crate::screenshot::screen_capture::take_screen;
-use native_dialog::FileDialog;
+use crate::{krustygrab::{KrustyGrab, Format}, painting::{icons::{icon_img, ICON_SIZE}, project::PROJECT_EXTENSION}, screenshot::{screen_capture::screens_number, capture_backend::ScreenSelection, capture_worker::{CaptureWorker, CaptureState}}};
 use arboard::{Clipboard, ImageData};
+use native_dialog::FileDialog;
 
 impl KrustyGrab {
     ///It renders the maian window composed of the 3 panel. 
     pub fn main_window(&mut self, ctx: &Context, frame: &mut eframe::Frame){
         
-        self.render_top_panel(ctx, frame);
+        self.render_top_panel(ctx);
         self.render_bottom_panel(ctx);
         self.render_central_panel(ctx);
 
         // If we are cropping we should be in full screen
         if self.is_window_status_crop(){
             frame.set_fullscreen(true);
-        } else if self.is_window_status_save(){
-            // frame.set_fullscreen(false);   //TO CHECK
         }
 
-        // To hide the window while we are taking the screenshot
-        if self.is_window_status_save() {
-            frame.set_visible(true);
-        }
-        if self.screenshot_requested  {
-            frame.set_visible(false);
+        // Countdown of the delayed screenshot
+        if self.capture_worker.is_some() {
+            self.render_countdown(ctx);
         }
     }
     
     ///Render the top panel 
-    fn render_top_panel(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+    fn render_top_panel(&mut self, ctx: &Context) {
         //define a TopBottomPanel widget
         TopBottomPanel::top("top panel").show(ctx, |ui| {
             ui.add_space(3.);
@@ -41,38 +35,97 @@ impl KrustyGrab {
                 // Option menu
                 ui.menu_image_button(icon_img("gear", ctx), ICON_SIZE, |ui| {
                     
-                    ctx.memory_mut(|mem| mem.data.insert_temp(Id::from("SM_open"), true));
+                    self.document.canvas.settings_menu_open = true;
 
                     if ui
                         .button(RichText::new("📁 Open").text_style(TextStyle::Body))
                         .clicked()
                     {
-                        if let Some(path) = FileDialog::new()
-                            .add_filter("PNG", &["png"])
-                            .add_filter("JPG", &["jpg"])
-                            .add_filter("GIF", &["gif"])
-                            .show_open_single_file()
-                            .expect("Unable to visualize the file selection window") {
-                                // Import the image selected
-                                let open_image = open(path).expect("Unable to open the file");
-                                let open_image_vec = open_image.clone().as_mut_rgba8().unwrap().clone().into_vec();
-        
-                                let new_image = ColorImage::from_rgba_unmultiplied(
-                                    [open_image.width() as usize, open_image.height() as usize],
-                                    &open_image_vec
-                                );
+                        match Format::file_dialog()
+                            .show_open_single_file() {
+                                Ok(Some(path)) => {
+                                    // Import the image selected
+                                    match open(&path) {
+                                        Ok(open_image) => {
+                                            let open_image = open_image.into_rgba8();
+                                            let new_image = ColorImage::from_rgba_unmultiplied(
+                                                [open_image.width() as usize, open_image.height() as usize],
+                                                open_image.as_raw()
+                                            );
 
-                                self.set_temp_image(Some(new_image));
-                                
-                                //Remove eventual previous drawings 
-                                ctx.memory_mut(|mem| {
-                                    mem.data.remove::<RedoList>(Id::from("Redo_list"));
-                                    mem.data.remove::<Vec<DrawingType>>(Id::from("Drawing"));
-                                });
-                                ui.close_menu();
+                                            //The previous image and its drawings can be restored with undo
+                                            self.replace_image(new_image);
+                                        },
+                                        Err(e) => self.notify_file_error("open", Some(path), e.to_string()),
+                                    }
+                                    ui.close_menu();
+                                },
+                                Ok(None) => {},
+                                Err(e) => self.notify_file_error("show the file selection window", None, e.to_string()),
+                            }
+                    }
+
+                    // Resume the screenshot and annotations saved in a project
+                    if ui
+                        .button(RichText::new("📂 Open project").text_style(TextStyle::Body))
+                        .clicked()
+                    {
+                        match FileDialog::new()
+                            .add_filter("KrustyGrab project", &[PROJECT_EXTENSION])
+                            .show_open_single_file() {
+                                Ok(Some(path)) => {
+                                    match self.open_project(&path) {
+                                        Ok(()) => tracing::info!("Project opened from {:?}", path),
+                                        Err(e) => self.notify_file_error("open the project", Some(path), format!("{:#}", e)),
+                                    }
+                                    ui.close_menu();
+                                },
+                                Ok(None) => {},
+                                Err(e) => self.notify_file_error("show the file selection window", None, e.to_string()),
                             }
                     }
 
+                    // Save the screenshot with editable annotations
+                    if self.screen.is_some() && ui
+                        .button(RichText::new("💾 Save project").text_style(TextStyle::Body))
+                        .clicked()
+                    {
+                        match FileDialog::new()
+                            .add_filter("KrustyGrab project", &[PROJECT_EXTENSION])
+                            .show_save_single_file() {
+                                Ok(Some(mut path)) => {
+                                    path.set_extension(PROJECT_EXTENSION);
+                                    match self.save_project(&path) {
+                                        Ok(()) => tracing::info!("Project saved in {:?}", path),
+                                        Err(e) => self.notify_file_error("save the project", Some(path), format!("{:#}", e)),
+                                    }
+                                    ui.close_menu();
+                                },
+                                Ok(None) => {},
+                                Err(e) => self.notify_file_error("show the file selection window", None, e.to_string()),
+                            }
+                    }
+
+                    // Copy the annotated screenshot
+                    if self.screen.is_some() && ui
+                        .button(RichText::new("📋 Copy").text_style(TextStyle::Body))
+                        .clicked()
+                    {
+                        if let Some(im) = self.annotated_image_or_notify() {
+                            self.copy_to_clipboard(&im);
+                        }
+                        ui.close_menu();
+                    }
+
+                    // Show the list of the edits
+                    if ui
+                        .button(RichText::new("🕘 History").text_style(TextStyle::Body))
+                        .clicked()
+                    {
+                        self.history_window = !self.history_window;
+                        ui.close_menu();
+                    }
+
                     // Select light theme or dark theme
                     ui.menu_button(
                         RichText::new("🌙 Theme").text_style(TextStyle::Body),
@@ -126,7 +179,7 @@ impl KrustyGrab {
 
                 // Render painting commands if there is a screen
                 if self.screen.is_some() {
-                    self.render_drawing_toolbar(ctx, ui, frame);
+                    self.render_drawing_toolbar(ctx, ui);
                 }
 
                 // Buttons on the right to set the screenshot request
@@ -139,7 +192,7 @@ impl KrustyGrab {
                         .clicked()
                     {
                         tracing::info!("Screen button clicked");
-                        self.screenshot_requested = true;
+                        self.request_screenshot(ctx, false);
                     }
 
                     //Select area screenshot
@@ -150,8 +203,7 @@ impl KrustyGrab {
                         .clicked()
                     {
                         tracing::info!("DragScreen button clicked");
-                        self.set_window_status(krustygrab::WindowStatus::Crop);
-                        self.screenshot_requested = true;
+                        self.request_screenshot(ctx, true);
                     }
 
                     //Modify style (to have same font of the other button) 
@@ -166,16 +218,24 @@ impl KrustyGrab {
                     ).on_hover_text_at_pointer("Select delay");
 
                     //Screen selection
-                    if screens_number() != 1 {
-                        let screen_selected: usize = 1 + self.get_selected_screen();
+                    let screens = screens_number(self.capture_backend.as_ref()).unwrap_or(1);
+                    if screens != 1 {
+                        let screen_selected = match self.get_selected_screen() {
+                            ScreenSelection::Single(i) => "Screen ".to_string() + (i+1).to_string().as_str(),
+                            ScreenSelection::All => "All screens".to_string(),
+                        };
     
-                        ui.menu_button(RichText::new("Screen ".to_string() + screen_selected.to_string().as_str()).text_style(TextStyle::Body), |ui| {
-                            for i in 0..screens_number() {
+                        ui.menu_button(RichText::new(screen_selected).text_style(TextStyle::Body), |ui| {
+                            for i in 0..screens {
                                 if ui.button(RichText::new("Screen ".to_string() + (i+1).to_string().as_str()).text_style(TextStyle::Body)).clicked() {
-                                    self.set_selected_screen(i);
+                                    self.set_selected_screen(ScreenSelection::Single(i));
                                     ui.close_menu();
                                 }
                             }
+                            if ui.button(RichText::new("All screens").text_style(TextStyle::Body)).clicked() {
+                                self.set_selected_screen(ScreenSelection::All);
+                                ui.close_menu();
+                            }
                         }).response
                         .on_hover_cursor(CursorIcon::PointingHand)
                         .on_hover_text_at_pointer("Select screen");
@@ -215,30 +275,99 @@ impl KrustyGrab {
         });
     }
 
-    ///Used to take and set the screenshot to visualize. Used when screenshot or select crop area buttons are pressed
-    pub fn set_screenshot(&mut self, ctx: &Context) {
-        //Insert a delay (150ms) in order to let the fade out animation of the application to be completed
-        // + the eventual delay set from the user
-        thread::sleep(Duration::from_millis(150) + Duration::from_secs(self.config.screenshot_delay as u64));
+    ///Render the countdown of the delayed screenshot, with the button to cancel it
+    fn render_countdown(&mut self, ctx: &Context) {
+        let remaining = match self.capture_worker.as_ref().and_then(|w| w.remaining()) {
+            Some(r) => r,
+            None => return,
+        };
 
-        //take the screenshot, set it in our struct, copy on the clipboard
-        let screen_selected: usize = self.get_selected_screen();
-        let im = take_screen(screen_selected).expect("Problem taking the screenshot");
+        Window::new(RichText::new("Screenshot timer").text_style(TextStyle::Body))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.label(RichText::new(remaining.to_string()).text_style(TextStyle::Heading));
+                    ui.label(RichText::new("Screenshot in progress").text_style(TextStyle::Body));
+                    ui.add_space(5.);
 
-        self.set_temp_image(Some(im.clone()));
-        
-        //Copy the taken screenshot to the clipboard
-        let mut clipboard = Clipboard::new().expect("Unable to create clipboard");
-        if let Err(e) = clipboard.set_image(ImageData { width: im.width(), height: im.height(), bytes: Cow::from(im.as_raw().clone())}) {
-            tracing::error!("Unable to copy in the clipboard: {e:?}");
+                    if ui
+                        .button(RichText::new("Cancel").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                        || ctx.input(|i| i.key_pressed(Key::Escape))
+                    {
+                        tracing::info!("Screenshot cancelled");
+                        if let Some(worker) = &self.capture_worker {
+                            worker.cancel();
+                        }
+                    }
+                });
+            });
+    }
+
+    ///Start the screenshot in background, after the delay set from the user. If `crop` is set the area selection is shown when it is taken.
+    pub fn request_screenshot(&mut self, ctx: &Context, crop: bool) {
+        if self.capture_worker.is_some() {
+            tracing::warn!("A screenshot is already in progress");
+            return;
+        }
+
+        self.capture_worker = Some(CaptureWorker::spawn(
+            self.capture_backend.clone(),
+            self.get_selected_screen(),
+            Duration::from_secs(self.config.screenshot_delay as u64),
+            crop,
+            ctx,
+        ));
+    }
+
+    ///Process the states reported by the capture worker: the window is hidden while the screenshot is taken and the result is shown once delivered
+    pub fn update_capture(&mut self, frame: &mut eframe::Frame) {
+        let (states, crop) = match self.capture_worker.as_mut() {
+            Some(worker) => (worker.poll(), worker.is_crop()),
+            None => return,
+        };
+
+        for state in states {
+            match state {
+                CaptureState::Countdown(_) => {},
+                CaptureState::Capturing => frame.set_visible(false),
+                CaptureState::Done(im) => {
+                    self.set_screenshot(im);
+                    if crop {
+                        self.start_crop();
+                    }
+                    frame.set_visible(true);
+                    self.capture_worker = None;
+                },
+                CaptureState::Failed(e) => {
+                    self.notify_file_error("take the screenshot", None, e);
+                    frame.set_visible(true);
+                    self.capture_worker = None;
+                },
+                CaptureState::Cancelled => self.capture_worker = None,
+            }
         }
+    }
 
-        self.set_select_area(None);
+    ///Used to set the screenshot to visualize once it has been taken
+    pub fn set_screenshot(&mut self, im: ColorImage) {
+        //Copy the taken screenshot to the clipboard
+        self.copy_to_clipboard(&im);
 
-        //Remove eventual previous drawings 
-        ctx.memory_mut(|mem| {
-            mem.data.remove::<RedoList>(Id::from("Redo_list"));
-            mem.data.remove::<Vec<DrawingType>>(Id::from("Drawing"));
+        //The previous screenshot and its drawings can be restored with undo
+        self.replace_image(im);
+    }
+
+    ///Copy the image in the clipboard, notifying the failure
+    pub fn copy_to_clipboard(&mut self, im: &ColorImage) {
+        let copied = Clipboard::new().and_then(|mut clipboard| {
+            clipboard.set_image(ImageData { width: im.width(), height: im.height(), bytes: Cow::from(im.as_raw())})
         });
+        if let Err(e) = copied {
+            self.notify_file_error("copy in the clipboard", None, e.to_string());
+        }
     }
-}
\ No newline at end of file
+}
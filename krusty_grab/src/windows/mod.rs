@@ -1,3 +1,5 @@
 mod crop_screen_window;
 mod main_window;
-mod save_window;
\ No newline at end of file
+pub mod file_error_window;
+pub mod history_window;
+pub mod overwrite_window;
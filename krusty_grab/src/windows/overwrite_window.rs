@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+
+use egui::{Align2, ColorImage, Context, CursorIcon, Layout, RichText, TextStyle, Window};
+
+use crate::{
+    krustygrab::{KrustyGrab, KrustyGrabConfig},
+    screenshot::naming::{render_template, unique_path, CollisionPolicy, NamingContext, DEFAULT_TEMPLATE},
+};
+
+///Quick save waiting for the user to decide whether to overwrite an existing file
+pub struct PendingOverwrite {
+    pub image: ColorImage,
+    pub path: PathBuf,
+}
+
+impl KrustyGrab {
+    ///Save the image in the save folder, with the name given by the template of the configuration
+    pub fn quick_save(&mut self, image: ColorImage) {
+        let naming = &self.config.naming;
+        let context = NamingContext::now(self.get_selected_screen(), image.size, naming.counter);
+
+        let name = render_template(&naming.template, &context).unwrap_or_else(|e| {
+            tracing::error!("Invalid file name template, using the default one: {e}");
+            render_template(DEFAULT_TEMPLATE, &context).expect("Default template is valid")
+        });
+
+        let mut save_path = self.config.save_folder.clone();
+        save_path.push(name);
+        save_path.set_extension(self.config.save_format.to_string());
+
+        match self.config.naming.collision {
+            CollisionPolicy::Overwrite => self.quick_save_to(image, save_path),
+            CollisionPolicy::Suffix => self.quick_save_to(image, unique_path(&save_path)),
+            CollisionPolicy::Prompt if save_path.exists() => {
+                self.pending_overwrite = Some(PendingOverwrite { image, path: save_path });
+            },
+            CollisionPolicy::Prompt => self.quick_save_to(image, save_path),
+        }
+    }
+
+    ///Write the quick save in the given path. The counter of the file names is increased only once the file is written.
+    pub(crate) fn quick_save_to(&mut self, image: ColorImage, path: PathBuf) {
+        if self.save_or_notify(image, path) {
+            self.config.naming.counter += 1;
+            KrustyGrabConfig::store_name_counter(self.config.naming.counter);
+        } else if let Some(pending) = &mut self.file_error {
+            pending.quick_save = true;
+        }
+    }
+
+    ///Drop the pending quick save, leaving the existing file untouched
+    pub fn cancel_overwrite(&mut self) {
+        self.pending_overwrite = None;
+    }
+
+    ///Write the pending quick save over the existing file
+    pub fn overwrite_existing(&mut self) {
+        if let Some(pending) = self.pending_overwrite.take() {
+            self.quick_save_to(pending.image, pending.path);
+        }
+    }
+
+    ///Write the pending quick save next to the existing file, with a number appended to the name
+    pub fn keep_both(&mut self) {
+        if let Some(pending) = self.pending_overwrite.take() {
+            self.quick_save_to(pending.image, unique_path(&pending.path));
+        }
+    }
+
+    ///Ask whether to overwrite the file of the pending quick save
+    pub fn overwrite_window(&mut self, ctx: &Context) {
+        let path = match &self.pending_overwrite {
+            Some(pending) => pending.path.clone(),
+            None => return,
+        };
+
+        Window::new(RichText::new("File already exists").text_style(TextStyle::Body))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(RichText::new(format!("{} already exists", path.display())).text_style(TextStyle::Body));
+                ui.add_space(5.);
+
+                ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
+                    if ui
+                        .button(RichText::new("Cancel").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.cancel_overwrite();
+                    }
+
+                    if ui
+                        .button(RichText::new("Keep both").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.keep_both();
+                    }
+
+                    if ui
+                        .button(RichText::new("Overwrite").text_style(TextStyle::Body))
+                        .on_hover_cursor(CursorIcon::PointingHand)
+                        .clicked()
+                    {
+                        self.overwrite_existing();
+                    }
+                });
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Color32;
+
+    use super::*;
+
+    ///Application saving in its own folder of the temporary directory, with a fixed file name
+    fn app(folder: &str, collision: CollisionPolicy) -> KrustyGrab {
+        let mut app = KrustyGrab::default();
+        app.config.save_folder = std::env::temp_dir().join(format!("krustygrab_{}_{}", folder, std::process::id()));
+        app.config.naming.template = "capture".to_string();
+        app.config.naming.collision = collision;
+        app.config.naming.counter = 7;
+        app
+    }
+
+    ///Create the save folder with a file already using the name of the template
+    fn existing_file(app: &KrustyGrab) -> PathBuf {
+        std::fs::create_dir_all(&app.config.save_folder).unwrap();
+        let existing = app.config.save_folder.join(format!("capture.{}", app.config.save_format));
+        std::fs::write(&existing, b"previous").unwrap();
+        existing
+    }
+
+    #[test]
+    fn cancelled_overwrite_keeps_the_counter() {
+        let mut app = app("cancel", CollisionPolicy::Prompt);
+        let existing = existing_file(&app);
+
+        app.quick_save(ColorImage::new([2, 2], Color32::WHITE));
+        assert!(app.pending_overwrite.is_some());
+        assert_eq!(app.config.naming.counter, 7);
+
+        app.cancel_overwrite();
+        assert!(app.pending_overwrite.is_none());
+        assert_eq!(app.config.naming.counter, 7);
+        assert_eq!(std::fs::read(&existing).unwrap(), b"previous");
+        std::fs::remove_dir_all(&app.config.save_folder).unwrap();
+    }
+
+    #[test]
+    fn confirmed_overwrite_replaces_the_file() {
+        let mut app = app("overwrite", CollisionPolicy::Prompt);
+        let existing = existing_file(&app);
+
+        app.quick_save(ColorImage::new([2, 2], Color32::WHITE));
+        app.overwrite_existing();
+        assert!(app.pending_overwrite.is_none());
+        assert_eq!(app.config.naming.counter, 8);
+        assert_eq!(image::image_dimensions(&existing).unwrap(), (2, 2));
+        std::fs::remove_dir_all(&app.config.save_folder).unwrap();
+    }
+
+    #[test]
+    fn keep_both_saves_with_a_suffix() {
+        let mut app = app("keep_both", CollisionPolicy::Prompt);
+        let existing = existing_file(&app);
+
+        app.quick_save(ColorImage::new([2, 2], Color32::WHITE));
+        app.keep_both();
+        assert!(app.pending_overwrite.is_none());
+        assert_eq!(app.config.naming.counter, 8);
+        assert_eq!(std::fs::read(&existing).unwrap(), b"previous");
+        let suffixed = app.config.save_folder.join(format!("capture_1.{}", app.config.save_format));
+        assert_eq!(image::image_dimensions(&suffixed).unwrap(), (2, 2));
+        std::fs::remove_dir_all(&app.config.save_folder).unwrap();
+    }
+
+    #[test]
+    fn failed_quick_save_keeps_the_counter() {
+        //The folder does not exist, so the file can not be written
+        let mut app = app("missing", CollisionPolicy::Overwrite);
+        app.quick_save(ColorImage::new([2, 2], Color32::WHITE));
+
+        assert_eq!(app.config.naming.counter, 7);
+        assert!(app.file_error.as_ref().is_some_and(|pending| pending.quick_save));
+
+        //Once the folder exists the retry uses up the number
+        std::fs::create_dir_all(&app.config.save_folder).unwrap();
+        app.retry_save(None);
+        assert!(app.file_error.is_none());
+        assert_eq!(app.config.naming.counter, 8);
+        std::fs::remove_dir_all(&app.config.save_folder).unwrap();
+    }
+}